@@ -1,5 +1,14 @@
+pub mod annotate;
+pub mod book;
+pub mod endgame;
 pub mod evaluator;
-use crate::ai::evaluator::Evaluation;
+pub mod nnue;
+pub mod tablebase;
+pub mod test_suite;
+pub mod tuner;
+use crate::ai::book::{Book, BookSelection};
+use crate::ai::evaluator::{ClassicEvaluator, Evaluation, Evaluator};
+use crate::ai::tablebase::{Tablebases, TbResult, Wdl};
 use crate::{board::*, game_state};
 use crate::board_console_printer::print_bit_board;
 use crate::game_state::GameState;
@@ -8,22 +17,66 @@ use crate::move_generator::*;
 use rand::prelude::ThreadRng;
 use rand::Rng;
 use std::collections::HashMap;
+use std::fmt;
 use std::ops::Add;
+use std::sync::Arc;
 // use std::iter::Map;
 use std::time::{Duration, Instant};
 
+// deepest iterative deepening goes when searching on a clock
+const MAX_SEARCH_DEPTH: u8 = 64;
+// centipawns, way more than all the material on the board
+const MATE_SCORE: f32 = 100_000.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum AiSearch {
     AlphaBeta,
     Minimax,
     Random,
 }
 
+impl AiSearch {
+    pub fn parse(text: &str) -> Result<AiSearch, String> {
+        match text.to_lowercase().replace(['-', '_'], "").as_str() {
+            "alphabeta" => Ok(AiSearch::AlphaBeta),
+            "minimax" => Ok(AiSearch::Minimax),
+            "random" => Ok(AiSearch::Random),
+            _ => Err(format!(
+                "unknown search {}, try alphabeta, minimax or random",
+                text
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AiSearch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AiSearch::AlphaBeta => "alphabeta",
+            AiSearch::Minimax => "minimax",
+            AiSearch::Random => "random",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// how a search is going, handed to the progress callback after every depth finishes
+pub struct SearchInfo {
+    pub depth: u8,
+    // white's point of view
+    pub evaluation: Evaluation,
+    // the best line found, starting with the move to play
+    pub pv: Vec<Move>,
+    pub nodes: i64,
+    pub elapsed: Duration,
+}
+
+pub type Progress = Box<dyn FnMut(&SearchInfo)>;
+
 struct SearchResultCache {
     cache: HashMap<u64, (evaluator::Evaluation, Option<Move>)>,
 }
 
-//@todo : pass in an Evaluator struct, or Evaluation function
-// need to understand Box<> or something first
 pub struct Ai {
     rng: ThreadRng,
     color: Color,
@@ -37,6 +90,25 @@ pub struct Ai {
     hasher: Zobrist,
     transposition_table: HashMap<u64, (u8, evaluator::Evaluation, Option<Move>)>, // <board hash => (depth, eval, best_move)
     pub transposition_table_hits: u64,
+    evaluator: Box<dyn Evaluator>,
+    // what the last search thought of the position, white's point of view
+    last_evaluation: Option<Evaluation>,
+    // timed searches stop here, whatever depth they're on gets thrown out
+    deadline: Option<Instant>,
+    search_aborted: bool,
+    last_search_depth: u8,
+    // opening book, played instead of searching for the first book_max_plies plies
+    book: Option<Arc<Book>>,
+    book_max_plies: u16,
+    book_selection: BookSelection,
+    // endgame tablebases, probed at the root and in the search
+    tablebases: Option<Arc<Tablebases>>,
+    // think this long a move instead of searching to default_search_depth
+    move_time: Option<Duration>,
+    // pv_table[depth_to_go] is the best line from the node being searched at that depth
+    pv_table: Vec<Vec<Move>>,
+    last_pv: Vec<Move>,
+    progress: Option<Progress>,
 }
 
 impl Ai {
@@ -54,6 +126,19 @@ impl Ai {
             hasher: Zobrist::new(),
             transposition_table: HashMap::new(),
             transposition_table_hits: 0,
+            evaluator: Box::new(ClassicEvaluator::default()),
+            last_evaluation: None,
+            deadline: None,
+            search_aborted: false,
+            last_search_depth: 0,
+            book: None,
+            book_max_plies: 0,
+            book_selection: BookSelection::Weighted,
+            tablebases: None,
+            move_time: None,
+            pv_table: vec![],
+            last_pv: vec![],
+            progress: None,
         }
     }
 
@@ -71,6 +156,19 @@ impl Ai {
             hasher: Zobrist::new(),
             transposition_table: HashMap::new(),
             transposition_table_hits: 0,
+            evaluator: Box::new(ClassicEvaluator::default()),
+            last_evaluation: None,
+            deadline: None,
+            search_aborted: false,
+            last_search_depth: 0,
+            book: None,
+            book_max_plies: 0,
+            book_selection: BookSelection::Weighted,
+            tablebases: None,
+            move_time: None,
+            pv_table: vec![],
+            last_pv: vec![],
+            progress: None,
         }
     }
 
@@ -90,10 +188,188 @@ impl Ai {
         self.color
     }
 
+    pub fn last_evaluation(&self) -> Option<Evaluation> {
+        self.last_evaluation
+    }
+
+    // deepest search that finished for the last move
+    pub fn last_search_depth(&self) -> u8 {
+        self.last_search_depth
+    }
+
     pub fn time_elapsed(&self) -> Option<Duration> {
         self.time_elapsed_during_search
     }
 
+    pub fn new_with_evaluator(
+        color: Color,
+        search_fn: AiSearch,
+        evaluator: Box<dyn Evaluator>,
+    ) -> Ai {
+        let mut ai = Ai::new_with_search(color, search_fn);
+        ai.evaluator = evaluator;
+        ai
+    }
+
+    pub fn set_evaluator(&mut self, evaluator: Box<dyn Evaluator>) {
+        self.evaluator = evaluator;
+    }
+
+    // evaluate with a network instead of the hand written evaluation, None to switch back
+    pub fn set_nnue(&mut self, network: Option<Arc<nnue::Network>>) {
+        self.evaluator = match network {
+            Some(network) => Box::new(nnue::NnueEvaluator::new(network)),
+            None => Box::new(ClassicEvaluator::default()),
+        };
+    }
+
+    pub fn set_book(&mut self, book: Arc<Book>, max_plies: u16, selection: BookSelection) {
+        self.book = Some(book);
+        self.book_max_plies = max_plies;
+        self.book_selection = selection;
+    }
+
+    pub fn set_tablebases(&mut self, tablebases: Arc<Tablebases>) {
+        self.tablebases = Some(tablebases);
+    }
+
+    pub fn set_search(&mut self, search: AiSearch) {
+        self.ai_search_function = search;
+    }
+
+    pub fn search_function(&self) -> AiSearch {
+        self.ai_search_function
+    }
+
+    pub fn set_move_time(&mut self, move_time: Option<Duration>) {
+        self.move_time = move_time;
+    }
+
+    pub fn move_time(&self) -> Option<Duration> {
+        self.move_time
+    }
+
+    // the line the last search expected, just the move for book and tablebase moves
+    pub fn principal_variation(&self) -> &[Move] {
+        self.last_pv.as_slice()
+    }
+
+    // called with what the search found every time it finishes a depth
+    pub fn set_progress(&mut self, progress: Option<Progress>) {
+        self.progress = progress;
+    }
+
+    fn report_progress(
+        &mut self,
+        depth: u8,
+        evaluation: Evaluation,
+        nodes: i64,
+        elapsed: Duration,
+    ) {
+        let info = SearchInfo {
+            depth,
+            evaluation,
+            pv: self.last_pv.clone(),
+            nodes,
+            elapsed,
+        };
+        if let Some(progress) = self.progress.as_mut() {
+            progress(&info);
+        }
+    }
+
+    // the tablebase's best move if the position is in one, no search needed
+    fn tablebase_move(&mut self, board: &GameState) -> Option<Move> {
+        let (m, result) = self.tablebases.as_ref()?.best_move(board)?;
+        self.last_evaluation =
+            Some(self.tablebase_evaluation(result, board.get_player_to_move(), 0));
+        self.last_search_depth = 0;
+        self.minimax_calls = 0;
+        self.time_elapsed_during_search = Some(Duration::from_secs(0));
+        self.last_pv = vec![m];
+        Some(m)
+    }
+
+    // scored like a mate the search found, just dtm plies further down
+    fn tablebase_evaluation(
+        &self,
+        result: TbResult,
+        player_moving: Color,
+        depth_to_go: u8,
+    ) -> Evaluation {
+        let mated_player = match result.wdl {
+            Wdl::Draw => {
+                return Evaluation {
+                    score: 0.0,
+                    mated_player: None,
+                }
+            }
+            Wdl::Win => player_moving.opposite(),
+            Wdl::Loss => player_moving,
+        };
+        let score = MATE_SCORE + depth_to_go as f32 - result.dtm as f32;
+        Evaluation {
+            score: match mated_player {
+                Color::White => -score,
+                Color::Black => score,
+            },
+            mated_player: Some(mated_player),
+        }
+    }
+
+    fn probe_tablebases(
+        &self,
+        board: &GameState,
+        player_moving: Color,
+        depth_to_go: u8,
+    ) -> Option<Evaluation> {
+        let result = self.tablebases.as_ref()?.probe(board)?;
+        Some(self.tablebase_evaluation(result, player_moving, depth_to_go))
+    }
+
+    // a move out of the book if we're still in it, no search happens so there's no evaluation
+    fn book_move(&mut self, board: &GameState) -> Option<Move> {
+        let book = self.book.as_ref()?;
+        if book::game_ply(board) >= self.book_max_plies || board.player_to_move() != self.color {
+            return None;
+        }
+        let m = book.choose_move(board, self.book_selection, &mut self.rng)?;
+        self.last_evaluation = None;
+        self.last_search_depth = 0;
+        self.minimax_calls = 0;
+        self.time_elapsed_during_search = Some(Duration::from_secs(0));
+        self.last_pv = vec![m];
+        Some(m)
+    }
+
+    fn evaluate(&self, board: &GameState) -> Evaluation {
+        self.evaluator.evaluate(board)
+    }
+
+    // the player to move has no legal moves, the evaluators only look at the board so they
+    // don't score mates, do it here. the more depth left the sooner the mate so it's worth more
+    fn no_moves_evaluation(
+        &self,
+        board: &GameState,
+        player_moving: Color,
+        depth_to_go: u8,
+    ) -> Evaluation {
+        if generate_checks(board, player_moving).is_empty() {
+            return Evaluation {
+                score: 0.0,
+                mated_player: None,
+            };
+        }
+        let score = MATE_SCORE + depth_to_go as f32;
+        Evaluation {
+            score: match player_moving {
+                Color::White => -score,
+                Color::Black => score,
+            },
+            mated_player: Some(player_moving),
+        }
+    }
+
     // returns  evaluation, final board, lower_bound, upper_bound
     // white sets lower bound , and will accept no branch evaluated lower than that
     // black sets upper bound , and will accept no branch evaluated higher than that
@@ -117,11 +393,27 @@ impl Ai {
         //     }
         // }
 
+        let depth_idx = depth_to_go as usize;
+        if self.pv_table.len() <= depth_idx {
+            self.pv_table.resize(depth_idx + 1, vec![]);
+        }
+        self.pv_table[depth_idx].clear();
+
+        // out of time, nothing from here on gets used
+        if self.out_of_time() {
+            return (self.evaluate(board), None);
+        }
+
+        // the tablebase already knows how this ends
+        if let Some(eval) = self.probe_tablebases(board, player_moving, depth_to_go) {
+            return (eval, None);
+        }
+
         // end of recursion, depth_to_go = 0 so eval the board
         // also end recursion if someone lost a king
         if depth_to_go == 0 {
             self.minimax_calls = self.minimax_calls + 1;
-            return (evaluator::evaluate(board, None, None), None);
+            return (self.evaluate(board), None);
         }
 
         // search moves
@@ -138,9 +430,12 @@ impl Ai {
             Color::Black => Some(&moves_to_try),
         };
 
-        // if no moves return an eval
+        // if no moves it's mate or stalemate
         if moves_to_try.len() == 0 {
-            return (evaluator::evaluate(board, None, None), None);
+            return (
+                self.no_moves_evaluation(board, player_moving, depth_to_go),
+                None,
+            );
         }
 
         // dfs with bounds
@@ -154,6 +449,7 @@ impl Ai {
             // println!("trying move {}", a_move);
             // println!("black castle rights\n{:?}", board.get_castling_rights(Color::Black));
             board.make_move_mut(a_move);
+            self.evaluator.make_move(board, a_move);
             // println!("after make \nblack castle rights\n{:?}", board.get_castling_rights(Color::Black));
             let (eval, _m) = self.alpha_beta(
                 board,
@@ -163,8 +459,23 @@ impl Ai {
                 upper_bound,
             );
             board.unmake_move_mut(a_move);
+            self.evaluator.unmake_move(board, a_move);
             // println!("after unmake\n black castle rights\n{:?}", board.get_castling_rights(Color::Black));
 
+            // the line through this move is the best one so far, keep it
+            let improves = match best_eval {
+                None => true,
+                Some(best) => {
+                    (Color::White == player_moving && eval.score > best.score)
+                        || (Color::Black == player_moving && eval.score < best.score)
+                }
+            };
+            if improves {
+                let mut line = vec![*a_move];
+                line.extend_from_slice(&self.pv_table[depth_idx - 1]);
+                self.pv_table[depth_idx] = line;
+            }
+
             // set best_move and best eval if they're not set
             if best_move.is_none() {
                 best_move = Some(*a_move);
@@ -233,14 +544,14 @@ impl Ai {
     }
 
     fn choose_random_move(&mut self, board: &GameState) -> (evaluator::Evaluation, Option<Move>) {
-        let mut moves = gen_legal_moves(board, self.color);
+        let mut moves = gen_legal_moves(board, board.get_player_to_move());
         if moves.len() == 0 {
-            return (evaluator::evaluate(board, None, None), None);
+            return (self.evaluate(board), None);
         }
         let move_count = moves.iter().len();
         let i = self.rng.gen_range(0..move_count);
         let chosen_move = moves.remove(i);
-        (evaluator::evaluate(board, None, None), Some(chosen_move))
+        (self.evaluate(board), Some(chosen_move))
     }
 
     fn minimax(
@@ -250,20 +561,27 @@ impl Ai {
         depth: u8,
     ) -> (evaluator::Evaluation, Option<Move>) {
         // println!("{} :depth,  {} to move", depth, color);
+        // out of time, nothing from here on gets used
+        if self.out_of_time() {
+            return (self.evaluate(board), None);
+        }
+        if let Some(eval) = self.probe_tablebases(board, color, depth) {
+            return (eval, None);
+        }
         // end of recursion
         if depth == 0 {
             self.minimax_calls = self.minimax_calls + 1;
-            return (evaluator::evaluate(board, None, None), None);
+            return (self.evaluate(board), None);
         }
         // also end recursion if someone lost a king
         let kings = board.get_kings();
         if kings.len() < 2 {
-            return (evaluator::evaluate(board, None, None), None);
+            return (self.evaluate(board), None);
         }
         // search moves
         let moves_to_try = gen_legal_moves(board, color);
         if moves_to_try.len() == 0 {
-            return (evaluator::evaluate(board, None, None), None);
+            return (self.no_moves_evaluation(board, color, depth), None);
         }
 
         // dfs with recursion time
@@ -283,9 +601,11 @@ impl Ai {
             // what's the evaluation of the best board state starting from here ?
             // println!("trying : {}\n{} : depth", move_to_try, depth);
             board.make_move_mut(&mut move_to_try);
+            self.evaluator.make_move(board, &move_to_try);
             let (eval, _) = self.minimax(board, color.opposite(), depth - 1);
             // println!("unmaking : {}\n{} : depth\n found eval = {}", move_to_try, depth, eval.score);
             board.unmake_move_mut(&mut move_to_try);
+            self.evaluator.unmake_move(board, &move_to_try);
 
             if acc.is_none() {
                 return Some((eval, move_to_try));
@@ -300,12 +620,21 @@ impl Ai {
             }
             return acc;
         });
-        if best.is_none() {
-            return (evaluator::evaluate(board, None, None), None);
-        } else {
-            let (eval, m) = best.unwrap();
-            return (eval, Some(m));
+        match best {
+            None => (self.evaluate(board), None),
+            Some((eval, m)) => (eval, Some(m)),
+        }
+    }
+
+    fn out_of_time(&mut self) -> bool {
+        if !self.search_aborted
+            && self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            self.search_aborted = true;
         }
+        self.search_aborted
     }
 
     // do an exhaustive search , depth-first search
@@ -320,12 +649,12 @@ impl Ai {
         self.started_at = Instant::now();
         self.transposition_table_hits = 0;
         self.transposition_table = HashMap::new();
+        let mut search_board = board.clone_to_game_state();
+        self.evaluator.prepare(&mut search_board);
         let (eval, best_move): (Evaluation, Option<Move>) = match self.ai_search_function {
-            AiSearch::AlphaBeta => {
-                self.alpha_beta(&mut board.clone_to_game_state(), color, depth, None, None)
-            }
-            AiSearch::Minimax => self.minimax(&mut board.clone_to_game_state(), color, depth),
-            AiSearch::Random => self.choose_random_move(board),
+            AiSearch::AlphaBeta => self.alpha_beta(&mut search_board, color, depth, None, None),
+            AiSearch::Minimax => self.minimax(&mut search_board, color, depth),
+            AiSearch::Random => self.choose_random_move(&search_board),
         };
         // check move
         // if best_move.is_some() {
@@ -346,8 +675,14 @@ impl Ai {
         //     }
         // }
 
+        self.last_pv = match self.ai_search_function {
+            AiSearch::AlphaBeta => self.pv_table[depth as usize].clone(),
+            _ => best_move.into_iter().collect(),
+        };
+
         // print stuff here
         let elapsed = self.started_at.elapsed();
+        self.last_evaluation = Some(eval);
         self.time_elapsed_during_search = Some(elapsed);
         self.total_minimax_calls += self.minimax_calls as u128;
         self.total_time_elapsed_during_search = match self.total_time_elapsed_during_search {
@@ -358,17 +693,134 @@ impl Ai {
     }
 
     pub fn make_move(&mut self, board: &mut GameState, depth: Option<u8>) -> Option<Move> {
+        if let Some(m) = self.book_move(board) {
+            return Some(m);
+        }
+        if let Some(m) = self.tablebase_move(board) {
+            return Some(m);
+        }
         let search_depth = if depth.is_some() {
             depth.unwrap()
         } else {
             self.default_search_depth
         };
+        self.last_search_depth = search_depth;
         let m = self.search(board, search_depth, self.color);
         match m {
             None => None,
-            Some((_eval, m)) => m,
+            Some((eval, m)) => {
+                self.report_progress(
+                    search_depth,
+                    eval,
+                    self.minimax_calls,
+                    self.started_at.elapsed(),
+                );
+                m
+            }
         }
     }
+
+    // iterative deepening, search one ply deeper at a time until the time runs out
+    // and go with the deepest search that finished, depth 1 always finishes
+    pub fn make_move_with_time(
+        &mut self,
+        board: &mut GameState,
+        move_time: Duration,
+    ) -> Option<Move> {
+        if let Some(m) = self.book_move(board) {
+            return Some(m);
+        }
+        if let Some(m) = self.tablebase_move(board) {
+            return Some(m);
+        }
+        let (eval, m) = self.deepen(board, move_time, self.color)?;
+        self.last_evaluation = Some(eval);
+        m
+    }
+
+    fn deepen(
+        &mut self,
+        board: &mut GameState,
+        move_time: Duration,
+        color: Color,
+    ) -> Option<(Evaluation, Option<Move>)> {
+        let started_at = Instant::now();
+        let deadline = started_at + move_time;
+        let mut best: Option<(Evaluation, Option<Move>)> = None;
+        let mut best_pv = vec![];
+        let mut nodes = 0;
+        for depth in 1..=MAX_SEARCH_DEPTH {
+            self.search_aborted = false;
+            self.deadline = if depth > 1 { Some(deadline) } else { None };
+            let result = self.search(board, depth, color);
+            self.deadline = None;
+            nodes += self.minimax_calls;
+            if self.search_aborted {
+                break;
+            }
+            self.last_search_depth = depth;
+            best_pv = self.last_pv.clone();
+            if let Some((eval, _)) = result {
+                self.report_progress(depth, eval, nodes, started_at.elapsed());
+            }
+            let no_moves = result.is_none_or(|(_, m)| m.is_none());
+            best = result;
+            if no_moves
+                || Instant::now() >= deadline
+                || matches!(self.ai_search_function, AiSearch::Random)
+            {
+                break;
+            }
+        }
+        self.search_aborted = false;
+        self.last_pv = best_pv;
+        best
+    }
+
+    // searches for whoever's to move, for the move time if there is one otherwise the usual
+    // depth, no book, for hints, evals and going over games
+    // the board's left as it was
+    pub fn analyse(&mut self, board: &mut GameState) -> Option<(Evaluation, Option<Move>)> {
+        // the search would stop at the root probe without picking a move
+        if let Some(m) = self.tablebase_move(board) {
+            return self.last_evaluation.map(|eval| (eval, Some(m)));
+        }
+        let to_move = board.get_player_to_move();
+        if let Some(move_time) = self.move_time {
+            return self.deepen(board, move_time, to_move);
+        }
+        let depth = self.default_search_depth;
+        self.last_search_depth = depth;
+        self.search(board, depth, to_move)
+    }
+
+    // settings like depth=6,movetime=500,search=minimax
+    pub fn apply_settings(&mut self, settings: &str) -> Result<(), String> {
+        for setting in settings.split(',').filter(|setting| !setting.is_empty()) {
+            let (name, value) = match setting.split_once('=') {
+                Some(pair) => pair,
+                None => return Err(format!("{} should look like name=value", setting)),
+            };
+            match name {
+                "depth" => match value.parse::<u8>() {
+                    Ok(depth) => self.default_search_depth = depth,
+                    Err(err) => return Err(format!("bad depth {}: {}", value, err)),
+                },
+                "movetime" => match value.parse::<u64>() {
+                    Ok(ms) => self.set_move_time(Some(Duration::from_millis(ms))),
+                    Err(err) => return Err(format!("bad movetime {}: {}", value, err)),
+                },
+                "search" => self.set_search(AiSearch::parse(value)?),
+                _ => {
+                    return Err(format!(
+                        "unknown ai setting {}, try depth, movetime or search",
+                        name
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -484,10 +936,17 @@ mod bench {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ai::book::BookEntry;
+    use crate::ai::evaluator::MaterialEvaluator;
+    use crate::chess_notation;
+    use crate::hash::polyglot::polyglot_key;
     use crate::{
         chess_notation::fen_reader::{self, make_board, make_initial_board},
         game_state, move_generator,
     };
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::Arc;
     use std::time::{Duration, Instant};
     use Ai;
 
@@ -532,14 +991,223 @@ mod tests {
         // test_initial_board_at_depth(4);
     }
 
+    #[test]
+    fn test_principal_variation() {
+        let mut game_state = GameState::starting_game();
+        let mut ai = Ai::new(Color::White);
+        let depths = Rc::new(Cell::new(0));
+        let seen = depths.clone();
+        ai.set_progress(Some(Box::new(move |info: &SearchInfo| {
+            assert!(!info.pv.is_empty() && info.pv.len() <= info.depth as usize);
+            seen.set(info.depth);
+        })));
+        let m = ai.make_move(&mut game_state, Some(3)).unwrap();
+        assert_eq!(depths.get(), 3);
+        let pv = ai.principal_variation().to_vec();
+        assert_eq!(pv.len(), 3);
+        assert_eq!(pv[0], m);
+        // every move in the line is legal where it's played
+        for mut m in pv {
+            let legal = gen_legal_moves(&game_state, game_state.get_player_to_move());
+            assert!(
+                legal.iter().any(|l| l.from == m.from && l.to == m.to),
+                "{}",
+                m
+            );
+            game_state.make_move_mut(&mut m);
+        }
+
+        // the line stops at the mate
+        let mut game_state = fen_reader::make_game_state("k7/8/1K6/8/8/8/8/7R w - - 0 1").unwrap();
+        let m = ai.make_move_with_time(&mut game_state, Duration::from_millis(100));
+        assert_eq!(ai.principal_variation(), &[m.unwrap()]);
+        assert!(depths.get() > 1);
+    }
+
+    #[test]
+    fn test_book_move() {
+        let mut game_state = GameState::starting_game();
+        let e4 = chess_notation::san::parse_san("e4", &game_state).unwrap();
+        let book = Book::new(vec![BookEntry {
+            key: polyglot_key(&game_state),
+            raw_move: book::encode_move(&e4),
+            weight: 1,
+            learn: 0,
+        }]);
+        let book = Arc::new(book);
+
+        let mut ai = Ai::new(Color::White);
+        ai.set_book(book.clone(), 2, BookSelection::Best);
+        assert_eq!(ai.make_move(&mut game_state, Some(1)).unwrap(), e4);
+        assert!(ai.last_evaluation().is_none());
+        assert_eq!(
+            ai.make_move_with_time(&mut game_state, Duration::from_millis(10))
+                .unwrap(),
+            e4
+        );
+        // past the book depth it searches, even though the book has the position
+        ai.set_book(book, 0, BookSelection::Best);
+        ai.make_move(&mut game_state, Some(1)).unwrap();
+        assert!(ai.last_evaluation().is_some());
+    }
+
+    #[test]
+    fn test_tablebase_play() {
+        let tablebases = tablebase::test_tablebases();
+        let mut game_state = fen_reader::make_game_state("8/8/8/3k4/8/8/8/KR6 w - - 0 1").unwrap();
+        let dtm = tablebases.probe(&game_state).unwrap().dtm;
+        let mut white = Ai::new(Color::White);
+        let mut black = Ai::new(Color::Black);
+        white.set_tablebases(tablebases.clone());
+        black.set_tablebases(tablebases);
+        // both sides play perfectly so it's mate in exactly dtm plies
+        let mut plies = 0;
+        loop {
+            let ai = match game_state.get_player_to_move() {
+                Color::White => &mut white,
+                Color::Black => &mut black,
+            };
+            let mut m = match ai.make_move(&mut game_state, Some(1)) {
+                Some(m) => m,
+                None => break,
+            };
+            game_state.make_move_mut(&mut m);
+            plies += 1;
+            assert!(plies <= dtm);
+        }
+        assert_eq!(plies, dtm);
+        assert!(!generate_checks(&game_state, Color::Black).is_empty());
+        assert_eq!(
+            white.last_evaluation().unwrap().mated_player,
+            Some(Color::Black)
+        );
+    }
+
+    #[test]
+    fn test_tablebase_in_search() {
+        // no table for KRKN, but taking the knight leaves a KRK the tablebase knows is won
+        let mut game_state =
+            fen_reader::make_game_state("4k3/8/8/8/3n4/8/8/3R2K1 w - - 0 1").unwrap();
+        let mut ai = Ai::new(Color::White);
+        ai.set_tablebases(tablebase::test_tablebases());
+        let m = ai.make_move(&mut game_state, Some(2)).unwrap();
+        assert_eq!(m.captured, Some(PieceType::Knight));
+        let eval = ai.last_evaluation().unwrap();
+        assert_eq!(eval.mated_player, Some(Color::Black));
+        assert!(eval.score > MATE_SCORE - 64.0);
+    }
+
+    #[test]
+    fn test_analyse_with_tablebases() {
+        let mut game_state = fen_reader::make_game_state("8/8/8/3k4/8/8/8/KQ6 w - - 0 1").unwrap();
+        let mut ai = Ai::new(Color::Black);
+        ai.set_tablebases(tablebase::test_tablebases());
+        let (eval, m) = ai.analyse(&mut game_state).unwrap();
+        assert!(m.is_some());
+        assert_eq!(eval.mated_player, Some(Color::Black));
+        assert_eq!(ai.principal_variation(), &[m.unwrap()]);
+    }
+
+    #[test]
+    fn test_search_with_nnue() {
+        // a network that only counts pieces, the first half of the inputs are our pieces
+        let hidden_size = 2;
+        let mut feature_weights = vec![0; nnue::INPUT_SIZE * hidden_size];
+        for w in feature_weights
+            .iter_mut()
+            .take(nnue::INPUT_SIZE / 2 * hidden_size)
+        {
+            *w = 10;
+        }
+        let network = nnue::Network::new(
+            hidden_size,
+            feature_weights,
+            vec![0; hidden_size],
+            vec![64, 64, -64, -64],
+            0,
+        )
+        .unwrap();
+        let mut ai = Ai::new(Color::White);
+        ai.set_nnue(Some(Arc::new(network)));
+        // the only capture is the free rook
+        let mut game_state =
+            fen_reader::make_game_state("4k3/8/8/3r4/8/8/3Q4/4K3 w - - 0 1").unwrap();
+        let m = ai.make_move(&mut game_state, Some(1)).unwrap();
+        assert_eq!(m.captured, Some(PieceType::Rook));
+        // deeper the accumulator has to follow the moves through the hooks to find it
+        let m = ai.make_move(&mut game_state, Some(3)).unwrap();
+        assert_eq!(m.captured, Some(PieceType::Rook));
+    }
+
+    // counts how often the search calls the hooks
+    struct CountingEvaluator {
+        prepared: Rc<Cell<u32>>,
+        made: Rc<Cell<u32>>,
+        unmade: Rc<Cell<u32>>,
+    }
+
+    impl Evaluator for CountingEvaluator {
+        fn evaluate(&self, _game_state: &GameState) -> Evaluation {
+            Evaluation {
+                score: 0.0,
+                mated_player: None,
+            }
+        }
+        fn prepare(&mut self, _game_state: &mut GameState) {
+            self.prepared.set(self.prepared.get() + 1);
+        }
+        fn make_move(&mut self, _game_state: &GameState, _m: &Move) {
+            self.made.set(self.made.get() + 1);
+        }
+        fn unmake_move(&mut self, _game_state: &GameState, _m: &Move) {
+            self.unmade.set(self.unmade.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_evaluator_hooks() {
+        for search in [AiSearch::AlphaBeta, AiSearch::Minimax] {
+            let (prepared, made, unmade) = (
+                Rc::new(Cell::new(0)),
+                Rc::new(Cell::new(0)),
+                Rc::new(Cell::new(0)),
+            );
+            let evaluator = CountingEvaluator {
+                prepared: prepared.clone(),
+                made: made.clone(),
+                unmade: unmade.clone(),
+            };
+            let mut ai = Ai::new_with_evaluator(Color::White, search, Box::new(evaluator));
+            let mut game_state = GameState::starting_game();
+            ai.make_move(&mut game_state, Some(2));
+            assert_eq!(prepared.get(), 1);
+            assert!(made.get() > 20);
+            assert_eq!(made.get(), unmade.get());
+        }
+    }
+
+    #[test]
+    fn test_material_evaluator() {
+        let mut ai = Ai::new_with_evaluator(
+            Color::Black,
+            AiSearch::AlphaBeta,
+            Box::new(MaterialEvaluator::default()),
+        );
+        // black queen can take the rook or the knight
+        let mut game_state =
+            fen_reader::make_game_state("4k3/8/8/1N1q3R/8/8/8/4K3 b - - 0 1").unwrap();
+        let m = ai.make_move(&mut game_state, Some(1)).unwrap();
+        assert_eq!(m.captured, Some(PieceType::Rook));
+    }
+
     #[test]
     fn bug_unwrap() {
         // black to move
         let fen = "r3k1r1/1b1p1p2/p3pp2/B1b4p/Pp2P3/1BN2P2/1PP4P/R2K1R2 b q - 10 20";
-        let mut game_state = fen_reader::make_game_state(fen);
+        let mut game_state = fen_reader::make_game_state(fen).unwrap();
 
         let fen = "r3k1r1/1b1p1p2/p3pp2/B1b4p/Pp2P3/1BN2P2/1PP4P/R2K1R2 b q - 10 20";
-        let mut game_state = fen_reader::make_game_state(fen);
+        let mut game_state = fen_reader::make_game_state(fen).unwrap();
         let mut b_moves = move_generator::gen_legal_moves(&game_state, Color::Black);
         for b_m in b_moves.iter_mut() {
             println!("{}", b_m);
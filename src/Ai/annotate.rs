@@ -0,0 +1,486 @@
+use crate::ai::evaluator::Evaluation;
+use crate::ai::Ai;
+use crate::board::*;
+use crate::chess_notation::fen_reader::FenError;
+use crate::chess_notation::move_to_san;
+use crate::chess_notation::pgn::{Game as PgnWriter, MoveAnnotation};
+use crate::chess_notation::pgn_parser::PgnGame;
+use crate::game::describe_score;
+use crate::game_state::GameState;
+use crate::move_generator::Move;
+use std::fmt;
+
+/*
+going over a game with the ai and marking the moves, like a coach would
+
+every position in the game gets searched, at the ai's depth or movetime. a move's loss is how
+much worse things got for the side that moved, the eval of the ai's best move minus the eval
+of the move played searched to the same depth, both from the mover's side in centipawns.
+mates count as 1000 so one missed mate doesn't swamp the average
+
+    ??  blunder      lost 300 or more
+    ?   mistake      lost 100 or more
+    ?!  inaccuracy   lost 50 or more
+    !   good move    the ai's move, a one ply search goes for something else and that
+                     something else is at least 150 worse
+
+the annotated pgn has the NAG for the mark ($4 $2 $6 $1), an [%eval] after every move and for
+the bad moves a comment with the better line. the game's own comments are kept, except old
+[%eval]s, and its variations are left out
+*/
+
+const BLUNDER: f32 = 300.0;
+const MISTAKE: f32 = 100.0;
+const INACCURACY: f32 = 50.0;
+const GOOD_MOVE_GAP: f32 = 150.0;
+// evals are capped at this, mates included
+const EVAL_CAP: f32 = 1000.0;
+// how much of the better line goes in the comment
+const LINE_PLIES: usize = 6;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Judgement {
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Judgement {
+    fn from_loss(loss: f32) -> Option<Judgement> {
+        if loss >= BLUNDER {
+            Some(Judgement::Blunder)
+        } else if loss >= MISTAKE {
+            Some(Judgement::Mistake)
+        } else if loss >= INACCURACY {
+            Some(Judgement::Inaccuracy)
+        } else {
+            None
+        }
+    }
+
+    pub fn nag(&self) -> u8 {
+        match self {
+            Judgement::Good => 1,
+            Judgement::Mistake => 2,
+            Judgement::Blunder => 4,
+            Judgement::Inaccuracy => 6,
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        match self {
+            Judgement::Good => "!",
+            Judgement::Mistake => "?",
+            Judgement::Blunder => "??",
+            Judgement::Inaccuracy => "?!",
+        }
+    }
+}
+
+impl fmt::Display for Judgement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Judgement::Good => "Good move",
+            Judgement::Inaccuracy => "Inaccuracy",
+            Judgement::Mistake => "Mistake",
+            Judgement::Blunder => "Blunder",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub struct MoveReview {
+    pub color: Color,
+    // numbered san, 12. Nf3 or 12... Nf6
+    pub played: String,
+    // centipawns, 0 when the move was the ai's choice
+    pub loss: f32,
+    pub judgement: Option<Judgement>,
+    // after the move, white's point of view
+    pub evaluation: Evaluation,
+    // the ai's line from the position before the move and its eval, when it's a different move
+    pub better: Option<(String, Evaluation)>,
+}
+
+pub struct GameReview {
+    pub white: String,
+    pub black: String,
+    pub moves: Vec<MoveReview>,
+}
+
+impl GameReview {
+    pub fn average_centipawn_loss(&self, color: Color) -> f32 {
+        let losses: Vec<f32> = self
+            .moves
+            .iter()
+            .filter(|m| m.color == color)
+            .map(|m| m.loss)
+            .collect();
+        if losses.is_empty() {
+            0.0
+        } else {
+            losses.iter().sum::<f32>() / losses.len() as f32
+        }
+    }
+
+    pub fn count(&self, color: Color, judgement: Judgement) -> usize {
+        self.moves
+            .iter()
+            .filter(|m| m.color == color && m.judgement == Some(judgement))
+            .count()
+    }
+}
+
+impl fmt::Display for GameReview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let players = [(Color::White, &self.white), (Color::Black, &self.black)];
+        for (i, (color, name)) in players.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "{} ({}) average centipawn loss {:.0}, {} inaccuracies, {} mistakes, {} blunders, {} good moves",
+                color,
+                name,
+                self.average_centipawn_loss(*color),
+                self.count(*color, Judgement::Inaccuracy),
+                self.count(*color, Judgement::Mistake),
+                self.count(*color, Judgement::Blunder),
+                self.count(*color, Judgement::Good)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// white's point of view, capped
+fn centipawns(evaluation: &Evaluation) -> f32 {
+    match evaluation.mated_player {
+        Some(Color::White) => -EVAL_CAP,
+        Some(Color::Black) => EVAL_CAP,
+        None => evaluation.score.clamp(-EVAL_CAP, EVAL_CAP),
+    }
+}
+
+// 12... Nf6 13. Nc3 Be7, with check marks
+fn numbered_line(board: &GameState, line: &[Move]) -> String {
+    let mut board = board.clone_to_game_state();
+    let mut tokens = vec![];
+    for (i, m) in line.iter().enumerate() {
+        let mut m = *m;
+        match board.get_player_to_move() {
+            Color::White => tokens.push(format!("{}.", board.get_full_move_number())),
+            Color::Black if i == 0 => tokens.push(format!("{}...", board.get_full_move_number())),
+            Color::Black => {}
+        }
+        tokens.push(move_to_san(&m, &board));
+        board.make_move_mut(&mut m);
+    }
+    tokens.join(" ")
+}
+
+// a fixed depth search, whatever the ai's own settings are
+fn search_at_depth(
+    ai: &mut Ai,
+    board: &mut GameState,
+    depth: u8,
+) -> Option<(Evaluation, Option<Move>)> {
+    let (default_depth, move_time) = (ai.default_search_depth, ai.move_time());
+    ai.default_search_depth = depth;
+    ai.set_move_time(None);
+    let result = ai.analyse(board);
+    ai.default_search_depth = default_depth;
+    ai.set_move_time(move_time);
+    result
+}
+
+// the eval after playing m searched one ply less, so it lines up with the search that picked
+// the best move. otherwise whoever moves last in the search gets an extra capture
+fn eval_after(ai: &mut Ai, board: &GameState, m: &Move, depth: u8) -> Option<Evaluation> {
+    let mut board = board.clone_to_game_state();
+    let mut m = *m;
+    board.make_move_mut(&mut m);
+    search_at_depth(ai, &mut board, depth.saturating_sub(1)).map(|(evaluation, _)| evaluation)
+}
+
+// report is called with how many of the moves have been looked at and how many there are
+pub fn review(
+    pgn_game: &PgnGame,
+    ai: &mut Ai,
+    report: &mut dyn FnMut(usize, usize),
+) -> Result<GameReview, FenError> {
+    let moves = pgn_game.main_line();
+    let mut board = pgn_game.starting_position()?;
+    let mut reviews = vec![];
+    for (i, m) in moves.iter().enumerate() {
+        let color = board.get_player_to_move();
+        let played_text = numbered_line(&board, &[*m]);
+        let analysis = ai
+            .analyse(&mut board)
+            .map(|(evaluation, best)| (evaluation, best, ai.principal_variation().to_vec()));
+        let depth = ai.last_search_depth();
+        let sign = match color {
+            Color::White => 1.0,
+            Color::Black => -1.0,
+        };
+        let review = match analysis {
+            Some((best_evaluation, Some(best), _)) if best.same_move(m) => {
+                let good = match search_at_depth(ai, &mut board, 1) {
+                    Some((_, Some(shallow))) if !shallow.same_move(m) => {
+                        eval_after(ai, &board, &shallow, depth).is_some_and(|alternative| {
+                            sign * (centipawns(&best_evaluation) - centipawns(&alternative))
+                                >= GOOD_MOVE_GAP
+                        })
+                    }
+                    _ => false,
+                };
+                MoveReview {
+                    color,
+                    played: played_text,
+                    loss: 0.0,
+                    judgement: if good { Some(Judgement::Good) } else { None },
+                    evaluation: best_evaluation,
+                    better: None,
+                }
+            }
+            Some((best_evaluation, Some(best), line)) => {
+                let evaluation = eval_after(ai, &board, m, depth).unwrap_or(best_evaluation);
+                let loss =
+                    (sign * (centipawns(&best_evaluation) - centipawns(&evaluation))).max(0.0);
+                let line: Vec<Move> = if line.is_empty() {
+                    vec![best]
+                } else {
+                    line.into_iter().take(LINE_PLIES).collect()
+                };
+                MoveReview {
+                    color,
+                    played: played_text,
+                    loss,
+                    judgement: Judgement::from_loss(loss),
+                    evaluation,
+                    better: Some((numbered_line(&board, &line), best_evaluation)),
+                }
+            }
+            // nothing to compare against
+            _ => MoveReview {
+                color,
+                played: played_text,
+                loss: 0.0,
+                judgement: None,
+                evaluation: eval_after(ai, &board, m, depth).unwrap_or(Evaluation {
+                    score: 0.0,
+                    mated_player: None,
+                }),
+                better: None,
+            },
+        };
+        reviews.push(review);
+        let mut m = *m;
+        board.make_move_mut(&mut m);
+        report(i + 1, moves.len());
+    }
+    let tag = |name: &str| pgn_game.get_tag(name).unwrap_or("?").to_string();
+    Ok(GameReview {
+        white: tag("White"),
+        black: tag("Black"),
+        moves: reviews,
+    })
+}
+
+// the game again with the review's marks and comments in it
+pub fn annotated_pgn(
+    pgn_game: &PgnGame,
+    review: &GameReview,
+    annotator: &str,
+) -> Result<String, FenError> {
+    let mut board = pgn_game.starting_position()?;
+    let mut tokens: Vec<String> = pgn_game
+        .comments
+        .iter()
+        .map(|comment| format!("{{{}}}", comment))
+        .collect();
+    tokens.extend(pgn_game.nags.iter().map(|nag| format!("${}", nag)));
+    let mut after_comment = true;
+    for (i, pgn_move) in pgn_game.moves.iter().enumerate() {
+        for comment in pgn_move.comments_before.iter() {
+            tokens.push(format!("{{{}}}", comment));
+            after_comment = true;
+        }
+        let move_number = board.get_full_move_number();
+        match board.get_player_to_move() {
+            Color::White => tokens.push(format!("{}.", move_number)),
+            Color::Black if after_comment => tokens.push(format!("{}...", move_number)),
+            Color::Black => {}
+        }
+        let mut m = pgn_move.chess_move;
+        tokens.push(move_to_san(&m, &board));
+        board.make_move_mut(&mut m);
+
+        let review = review.moves.get(i);
+        let judgement = review.and_then(|review| review.judgement);
+        if let Some(judgement) = judgement {
+            tokens.push(format!("${}", judgement.nag()));
+        }
+        // the game's own ! ? marks make way for ours
+        for nag in pgn_move.nags.iter() {
+            if judgement.is_none() || *nag > 6 {
+                tokens.push(format!("${}", nag));
+            }
+        }
+        after_comment = false;
+        for comment in pgn_move.comments.iter() {
+            if !comment.contains("[%eval ") {
+                tokens.push(format!("{{{}}}", comment));
+                after_comment = true;
+            }
+        }
+        let review = match review {
+            Some(review) => review,
+            None => continue,
+        };
+        if review.evaluation.mated_player.is_none() {
+            let annotation = MoveAnnotation {
+                eval: Some(review.evaluation.score),
+                clock: None,
+            };
+            tokens.extend(annotation.to_comment());
+            after_comment = true;
+        }
+        if let (Some(judgement), Some((line, evaluation))) = (judgement, &review.better) {
+            tokens.push(format!(
+                "{{{} ({}). Better is {} ({})}}",
+                judgement,
+                describe_score(&review.evaluation),
+                line,
+                describe_score(evaluation)
+            ));
+            after_comment = true;
+        }
+    }
+    tokens.push(pgn_game.result.clone());
+
+    let tag = |name: &str| pgn_game.get_tag(name).unwrap_or("?").to_string();
+    let roster = [
+        "Event", "Site", "Date", "Round", "White", "Black", "Result", "SetUp", "FEN",
+    ];
+    let mut extra_tags: Vec<(String, String)> = pgn_game
+        .tags
+        .iter()
+        .filter(|(name, _)| !roster.contains(&name.as_str()) && name != "Annotator")
+        .cloned()
+        .collect();
+    extra_tags.push((String::from("Annotator"), String::from(annotator)));
+    let writer = PgnWriter {
+        event: tag("Event"),
+        site: tag("Site"),
+        date: tag("Date"),
+        round: tag("Round"),
+        white: tag("White"),
+        black: tag("Black"),
+        result: pgn_game.result.clone(),
+        time_control: None,
+        termination: None,
+        mode: None,
+        fen: pgn_game.get_tag("FEN").map(String::from),
+        extra_tags,
+        move_text: tokens.join(" "),
+    };
+    Ok(format!("{}\n", writer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_notation::pgn_parser::parse_pgn;
+
+    const PGN: &str = r#"[Event "Test"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 {hoping for the best} 4. Qxf7# 1-0
+"#;
+
+    #[test]
+    fn test_review() {
+        let pgn_game = parse_pgn(PGN).unwrap().remove(0);
+        let mut ai = Ai::new(Color::White);
+        ai.default_search_depth = 3;
+        let mut reported = 0;
+        let review = review(&pgn_game, &mut ai, &mut |done, total| {
+            assert_eq!(total, 7);
+            reported = done;
+        })
+        .unwrap();
+        assert_eq!(reported, 7);
+        assert_eq!(review.moves.len(), 7);
+        assert_eq!(review.moves[0].played, "1. e4");
+        assert_eq!(review.moves[5].played, "3... Nf6");
+        // Nf6 lets the queen mate, anything stopping it is a lot better
+        let nf6 = &review.moves[5];
+        assert_eq!(nf6.judgement, Some(Judgement::Blunder));
+        assert!(nf6.loss >= BLUNDER);
+        assert!(nf6.better.as_ref().unwrap().0.starts_with("3... "));
+        let mate = &review.moves[6];
+        assert_eq!(mate.loss, 0.0);
+        assert_eq!(mate.evaluation.mated_player, Some(Color::Black));
+        assert!(review.average_centipawn_loss(Color::Black) >= BLUNDER / 3.0);
+        assert_eq!(review.count(Color::Black, Judgement::Blunder), 1);
+        let summary = review.to_string();
+        assert!(summary.starts_with("white (A) average centipawn loss "));
+        assert!(summary.contains("\nblack (B) average centipawn loss "));
+
+        let pgn = annotated_pgn(&pgn_game, &review, "chess_engine depth=3").unwrap();
+        assert!(pgn.contains("[Annotator \"chess_engine depth=3\"]"));
+        // the writer wraps lines
+        let text = pgn.split_whitespace().collect::<Vec<&str>>().join(" ");
+        assert!(text.contains(
+            "3... Nf6 $4 {hoping for the best} {Blunder (black gets mated). Better is 3... "
+        ));
+        assert!(text.contains("1. e4 {[%eval "));
+        // it reads back in with the same moves
+        let again = parse_pgn(pgn.as_str()).unwrap().remove(0);
+        assert_eq!(again.moves.len(), 7);
+        assert_eq!(again.moves[5].nags, vec![4]);
+    }
+
+    #[test]
+    fn test_good_move() {
+        // Nc7+ forks the king and rook, a one ply search takes the pawn on g2 and keeps
+        // a knight against a rook
+        let pgn = r#"[Event "Test"]
+[SetUp "1"]
+[FEN "r3k3/8/8/1N6/8/8/6p1/6K1 w - - 0 1"]
+[Result "*"]
+
+1. Nc7+ Kd7 2. Nxa8 *
+"#;
+        let pgn_game = parse_pgn(pgn).unwrap().remove(0);
+        let mut ai = Ai::new(Color::White);
+        ai.default_search_depth = 3;
+        let review = review(&pgn_game, &mut ai, &mut |_, _| {}).unwrap();
+        let fork = &review.moves[0];
+        assert_eq!(fork.played, "1. Nc7+");
+        assert_eq!(fork.judgement, Some(Judgement::Good));
+        let mut board = pgn_game.starting_position().unwrap();
+        let (_, shallow) = search_at_depth(&mut ai, &mut board, 1).unwrap();
+        let shallow = shallow.unwrap();
+        assert_eq!(move_to_san(&shallow, &board), "Kxg2");
+        let alternative = eval_after(&mut ai, &board, &shallow, 3).unwrap();
+        assert!(centipawns(&fork.evaluation) - centipawns(&alternative) >= GOOD_MOVE_GAP);
+        let pgn = annotated_pgn(&pgn_game, &review, "chess_engine depth=3").unwrap();
+        assert!(pgn.contains("1. Nc7+ $1 {[%eval "));
+    }
+
+    #[test]
+    fn test_judgement() {
+        assert_eq!(Judgement::from_loss(20.0), None);
+        assert_eq!(Judgement::from_loss(50.0), Some(Judgement::Inaccuracy));
+        assert_eq!(Judgement::from_loss(150.0), Some(Judgement::Mistake));
+        assert_eq!(Judgement::from_loss(800.0), Some(Judgement::Blunder));
+        assert_eq!(Judgement::Blunder.symbol(), "??");
+        assert_eq!(Judgement::Inaccuracy.nag(), 6);
+    }
+}
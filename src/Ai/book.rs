@@ -0,0 +1,543 @@
+use crate::board::*;
+use crate::chess_notation::pgn::result_from_str;
+use crate::chess_notation::pgn_parser::{PgnError, PgnGame};
+use crate::chess_notation::pgn_reader::PgnReader;
+use crate::game_state::GameState;
+use crate::hash::polyglot::polyglot_key;
+use crate::move_generator::{gen_legal_moves, Move, MoveType};
+use rand::Rng;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/*
+Polyglot opening books
+http://hgm.nubati.net/book_format.html
+
+a .bin file is a list of 16 byte entries sorted by key, all big endian
+    key     u64  polyglot_key of the position
+    move    u16  to file, to rank, from file, from rank, promotion, 3 bits each starting at bit 0
+                 promotion is 0 none, 1 knight, 2 bishop, 3 rook, 4 queen
+                 castling is written as the king taking its own rook, e1h1 / e1a1 / e8h8 / e8a8
+    weight  u16  how good the move is, usually how often it was played / won
+    learn   u32  not used
+
+building a book from games
+replay every game up to some number of plies and for each position count how often each move
+was played and how it turned out for the side that played it. the weight is the usual polyglot
+one, 2 per win and 1 per draw, so moves that only ever lost don't make it into the book
+*/
+
+const ENTRY_SIZE: usize = 16;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    fn from_bytes(bytes: &[u8]) -> BookEntry {
+        let mut key = [0u8; 8];
+        key.copy_from_slice(&bytes[0..8]);
+        let mut learn = [0u8; 4];
+        learn.copy_from_slice(&bytes[12..16]);
+        BookEntry {
+            key: u64::from_be_bytes(key),
+            raw_move: u16::from_be_bytes([bytes[8], bytes[9]]),
+            weight: u16::from_be_bytes([bytes[10], bytes[11]]),
+            learn: u32::from_be_bytes(learn),
+        }
+    }
+
+    fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0u8; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+// how the ai picks between the book moves for a position
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BookSelection {
+    // random, but moves with more weight come up more often
+    Weighted,
+    // always the heaviest move
+    Best,
+}
+
+#[derive(Debug, Default)]
+pub struct Book {
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    // entries don't need to be sorted, that gets done here
+    pub fn new(mut entries: Vec<BookEntry>) -> Book {
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)));
+        Book { entries }
+    }
+
+    pub fn read(bytes: &[u8]) -> Result<Book, String> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(format!(
+                "book is {} bytes, should be a multiple of {}",
+                bytes.len(),
+                ENTRY_SIZE
+            ));
+        }
+        Ok(Book::new(
+            bytes
+                .chunks(ENTRY_SIZE)
+                .map(BookEntry::from_bytes)
+                .collect(),
+        ))
+    }
+
+    pub fn load_from_file(path: &Path) -> Result<Book, String> {
+        let bytes =
+            fs::read(path).map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+        Book::read(bytes.as_slice()).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.entries.iter().flat_map(|e| e.to_bytes()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[BookEntry] {
+        &self.entries
+    }
+
+    pub fn entries_for_key(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|e| e.key < key);
+        let end = self.entries.partition_point(|e| e.key <= key);
+        &self.entries[start..end]
+    }
+
+    // the legal moves the book has for this position with their weights, heaviest first
+    // entries that don't decode to a legal move are skipped
+    pub fn moves(&self, game_state: &GameState) -> Vec<(Move, u16)> {
+        let entries = self.entries_for_key(polyglot_key(game_state));
+        if entries.is_empty() {
+            return vec![];
+        }
+        let legal_moves = gen_legal_moves(game_state, game_state.player_to_move());
+        entries
+            .iter()
+            .filter_map(|entry| {
+                let m = legal_moves
+                    .iter()
+                    .find(|m| encode_move(m) == entry.raw_move)?;
+                Some((*m, entry.weight))
+            })
+            .collect()
+    }
+
+    pub fn choose_move<R: Rng>(
+        &self,
+        game_state: &GameState,
+        selection: BookSelection,
+        rng: &mut R,
+    ) -> Option<Move> {
+        let moves = self.moves(game_state);
+        match selection {
+            BookSelection::Best => moves.first().map(|(m, _)| *m),
+            BookSelection::Weighted => {
+                let total: u32 = moves.iter().map(|(_, weight)| *weight as u32).sum();
+                if total == 0 {
+                    return moves.first().map(|(m, _)| *m);
+                }
+                let mut pick = rng.gen_range(0..total);
+                for (m, weight) in moves.iter() {
+                    if pick < *weight as u32 {
+                        return Some(*m);
+                    }
+                    pick -= *weight as u32;
+                }
+                None
+            }
+        }
+    }
+}
+
+// plies played since the start of the game, 0 for white's first move
+pub fn game_ply(game_state: &GameState) -> u16 {
+    let black_to_move = game_state.player_to_move() == Color::Black;
+    (game_state.full_move_number().max(1) - 1) * 2 + black_to_move as u16
+}
+
+// how one move out of one position did, from the side of the player making the move
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct MoveStats {
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MoveStats {
+    pub fn score(&self) -> u32 {
+        2 * self.wins + self.draws
+    }
+}
+
+pub struct BookBuilder {
+    max_plies: u16,
+    // moves played in fewer games than this are left out
+    min_games: u32,
+    // both players need an elo tag at least this high
+    min_elo: Option<u32>,
+    stats: HashMap<(u64, u16), MoveStats>,
+    games_used: usize,
+}
+
+impl BookBuilder {
+    pub fn new(max_plies: u16) -> BookBuilder {
+        BookBuilder {
+            max_plies,
+            min_games: 1,
+            min_elo: None,
+            stats: HashMap::new(),
+            games_used: 0,
+        }
+    }
+
+    pub fn with_min_games(mut self, min_games: u32) -> BookBuilder {
+        self.min_games = min_games;
+        self
+    }
+
+    pub fn with_min_elo(mut self, min_elo: u32) -> BookBuilder {
+        self.min_elo = Some(min_elo);
+        self
+    }
+
+    pub fn games_used(&self) -> usize {
+        self.games_used
+    }
+
+    fn strong_enough(&self, tags: &[(String, String)]) -> bool {
+        let min_elo = match self.min_elo {
+            Some(min_elo) => min_elo,
+            None => return true,
+        };
+        ["WhiteElo", "BlackElo"].iter().all(|side| {
+            tags.iter()
+                .find(|(tag, _)| tag == side)
+                .and_then(|(_, elo)| elo.trim().parse::<u32>().ok())
+                .is_some_and(|elo| elo >= min_elo)
+        })
+    }
+
+    // returns false if the game was left out, for the elo filter, not having a result or a bad FEN tag
+    pub fn add_game(&mut self, game: &PgnGame) -> bool {
+        if !self.strong_enough(&game.tags) {
+            return false;
+        }
+        let white_score = match result_from_str(game.result.as_str()) {
+            Some(result) => result,
+            None => return false,
+        };
+        let mut game_state = match game.starting_position() {
+            Ok(game_state) => game_state,
+            Err(_) => return false,
+        };
+        for mut m in game.main_line() {
+            if game_ply(&game_state) >= self.max_plies {
+                break;
+            }
+            let score = match game_state.player_to_move() {
+                Color::White => white_score,
+                Color::Black => 1.0 - white_score,
+            };
+            let stats = self
+                .stats
+                .entry((polyglot_key(&game_state), encode_move(&m)))
+                .or_default();
+            stats.games += 1;
+            if score == 1.0 {
+                stats.wins += 1;
+            } else if score == 0.0 {
+                stats.losses += 1;
+            } else {
+                stats.draws += 1;
+            }
+            game_state.make_move_mut(&mut m);
+        }
+        self.games_used += 1;
+        true
+    }
+
+    // add every game in a pgn file, games that don't read are skipped and their errors returned
+    pub fn add_pgn<R: Read>(&mut self, reader: R) -> Vec<PgnError> {
+        let mut errors = vec![];
+        for game in PgnReader::new(reader) {
+            let game = match game {
+                Ok(game) => game,
+                Err(err) => {
+                    errors.push(err);
+                    continue;
+                }
+            };
+            // don't bother reading the moves of games we're going to leave out
+            if !self.strong_enough(&game.tags) {
+                continue;
+            }
+            match game.parse() {
+                Ok(game) => {
+                    self.add_game(&game);
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+        errors
+    }
+
+    pub fn stats(&self, game_state: &GameState, m: &Move) -> Option<MoveStats> {
+        self.stats
+            .get(&(polyglot_key(game_state), encode_move(m)))
+            .copied()
+    }
+
+    pub fn build(&self) -> Book {
+        let scores: Vec<(u64, u16, u32)> = self
+            .stats
+            .iter()
+            .filter(|(_, stats)| stats.games >= self.min_games && stats.score() > 0)
+            .map(|((key, raw_move), stats)| (*key, *raw_move, stats.score()))
+            .collect();
+        // scores are u32 and weights only u16, scale each position down so its heaviest move fits
+        let mut max_scores: HashMap<u64, u32> = HashMap::new();
+        for (key, _, score) in scores.iter() {
+            let max = max_scores.entry(*key).or_default();
+            *max = (*max).max(*score);
+        }
+        let entries = scores
+            .into_iter()
+            .map(|(key, raw_move, score)| {
+                let max = max_scores[&key] as u64;
+                let weight = if max > u16::MAX as u64 {
+                    (score as u64 * u16::MAX as u64 / max).max(1) as u16
+                } else {
+                    score as u16
+                };
+                BookEntry {
+                    key,
+                    raw_move,
+                    weight,
+                    learn: 0,
+                }
+            })
+            .collect();
+        Book::new(entries)
+    }
+}
+
+// polyglot move format, see the top of the file
+pub fn encode_move(m: &Move) -> u16 {
+    let to = match m.move_type() {
+        // king takes rook
+        MoveType::Castling { rook_from, .. } => *rook_from,
+        _ => m.to,
+    };
+    let promotion = match m.move_type() {
+        MoveType::Promotion(PieceType::Knight) => 1,
+        MoveType::Promotion(PieceType::Bishop) => 2,
+        MoveType::Promotion(PieceType::Rook) => 3,
+        MoveType::Promotion(PieceType::Queen) => 4,
+        _ => 0,
+    };
+    (to.x() as u16 - 1)
+        | (to.y() as u16 - 1) << 3
+        | (m.from.x() as u16 - 1) << 6
+        | (m.from.y() as u16 - 1) << 9
+        | promotion << 12
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_notation::fen_reader::make_game_state;
+    use crate::chess_notation::san::parse_san;
+
+    fn entry(game_state: &GameState, san: &str, weight: u16) -> BookEntry {
+        BookEntry {
+            key: polyglot_key(game_state),
+            raw_move: encode_move(&parse_san(san, game_state).unwrap()),
+            weight,
+            learn: 0,
+        }
+    }
+
+    fn small_book() -> Book {
+        let mut game_state = GameState::starting_game();
+        let mut entries = vec![
+            entry(&game_state, "e4", 30),
+            entry(&game_state, "d4", 10),
+            entry(&game_state, "Nf3", 0),
+        ];
+        let mut e4 = parse_san("e4", &game_state).unwrap();
+        game_state.make_move_mut(&mut e4);
+        entries.push(entry(&game_state, "c5", 5));
+        Book::new(entries)
+    }
+
+    #[test]
+    fn test_encode_move() {
+        let game_state = GameState::starting_game();
+        // e2e4 = to e4 (file 4, rank 3), from e2 (file 4, rank 1)
+        assert_eq!(
+            encode_move(&parse_san("e4", &game_state).unwrap()),
+            4 | 3 << 3 | 4 << 6 | 1 << 9
+        );
+        let game_state = make_game_state("r3k3/1P6/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        // e1h1
+        assert_eq!(
+            encode_move(&parse_san("O-O", &game_state).unwrap()),
+            7 | 4 << 6
+        );
+        // b7a8=N
+        assert_eq!(
+            encode_move(&parse_san("bxa8=N", &game_state).unwrap()),
+            7 << 3 | 1 << 6 | 6 << 9 | 1 << 12
+        );
+    }
+
+    #[test]
+    fn test_read_and_probe() {
+        let book = small_book();
+        let bytes = book.to_bytes();
+        assert_eq!(bytes.len(), 4 * 16);
+        // start position key first
+        assert_eq!(&bytes[0..8], &0x463b96181691fc9cu64.to_be_bytes());
+        let book = Book::read(bytes.as_slice()).unwrap();
+        assert_eq!(book.len(), 4);
+        assert!(Book::read(&bytes[1..]).is_err());
+
+        let mut game_state = GameState::starting_game();
+        let moves = book.moves(&game_state);
+        let weights: Vec<u16> = moves.iter().map(|(_, weight)| *weight).collect();
+        assert_eq!(weights, vec![30, 10, 0]);
+        assert_eq!(moves[0].0.to, Coordinate::new(5, 4));
+
+        let mut rng = rand::thread_rng();
+        let best = book.choose_move(&game_state, BookSelection::Best, &mut rng);
+        assert_eq!(best.unwrap().to, Coordinate::new(5, 4));
+        for _ in 0..20 {
+            // Nf3 has no weight so it never gets picked
+            let m = book
+                .choose_move(&game_state, BookSelection::Weighted, &mut rng)
+                .unwrap();
+            assert_ne!(m.piece, PieceType::Knight);
+        }
+
+        let mut d4 = parse_san("d4", &game_state).unwrap();
+        game_state.make_move_mut(&mut d4);
+        assert!(book.moves(&game_state).is_empty());
+        assert!(book
+            .choose_move(&game_state, BookSelection::Best, &mut rng)
+            .is_none());
+    }
+
+    const GAMES: &str = r#"[White "a"]
+[Black "b"]
+[WhiteElo "2500"]
+[BlackElo "2400"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 Nc6 1-0
+
+[WhiteElo "2500"]
+[BlackElo "2450"]
+[Result "1/2-1/2"]
+
+1. e4 c5 2. Nf3 1/2-1/2
+
+[WhiteElo "2600"]
+[BlackElo "2600"]
+[Result "0-1"]
+
+1. d4 d5 0-1
+
+[WhiteElo "1200"]
+[BlackElo "2600"]
+[Result "1-0"]
+
+1. e4 e5 1-0
+
+[Result "*"]
+
+1. c4 *
+
+[Result "1-0"]
+
+1. e4 Qxe4 1-0
+"#;
+
+    #[test]
+    fn test_build_book() {
+        let mut builder = BookBuilder::new(3).with_min_elo(2000);
+        let errors = builder.add_pgn(GAMES.as_bytes());
+        assert_eq!(errors.len(), 0);
+        // the weak game, the game without elo tags and the unfinished game are left out
+        assert_eq!(builder.games_used(), 3);
+
+        let game_state = GameState::starting_game();
+        let e4 = parse_san("e4", &game_state).unwrap();
+        assert_eq!(
+            builder.stats(&game_state, &e4),
+            Some(MoveStats {
+                games: 2,
+                wins: 1,
+                draws: 1,
+                losses: 0
+            })
+        );
+
+        let book = builder.build();
+        let moves = book.moves(&game_state);
+        // d4 lost its only game so it isn't in the book
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0], (e4, 3));
+
+        // black's moves after 1. e4, e5 lost and c5 drew
+        let mut after_e4 = GameState::starting_game();
+        after_e4.make_move_mut(&mut e4.clone());
+        let moves = book.moves(&after_e4);
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].0.to, Coordinate::new(3, 5));
+        assert_eq!(moves[0].1, 1);
+
+        // e4, c5, d5 and the two Nf3s, nothing past 3 plies so no 2... Nc6
+        assert_eq!(book.len(), 5);
+        let book = Book::read(book.to_bytes().as_slice()).unwrap();
+        assert_eq!(book.len(), 5);
+
+        // only 1. e4 was played in two games
+        let mut builder = BookBuilder::new(3).with_min_games(2);
+        builder.add_pgn(GAMES.as_bytes());
+        assert_eq!(builder.build().len(), 1);
+    }
+
+    #[test]
+    fn test_build_book_reports_bad_games() {
+        let mut builder = BookBuilder::new(10);
+        let errors = builder.add_pgn("[Result \"1-0\"]\n\n1. e5 1-0\n".as_bytes());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(builder.games_used(), 0);
+    }
+}
@@ -0,0 +1,429 @@
+use crate::bit_board::BitBoard;
+use crate::board::*;
+use crate::game_state::GameState;
+
+/*
+specialised endgame evaluation
+
+the normal evaluation doesn't know how to make progress in KQ vs K and friends (it's happy
+as long as it's up a queen) and it can't tell that a lot of endings a pawn up are dead draws
+so once we're down to a known material signature we let one of these adjust the score
+
+keys are the pieces of the stronger side then the weaker side, each starting with the king
+and in the order K Q R B N P, ex. KBNK, KPK, KRKP
+*/
+
+// the normal evaluation from white's point of view, returns the adjusted one
+type EndgameFn = fn(&GameState, &MaterialSignature, Color, f32) -> f32;
+
+pub struct Endgame {
+    pub name: &'static str,
+    eval: EndgameFn,
+}
+
+const KPK: Endgame = Endgame {
+    name: "KPK",
+    eval: kpk,
+};
+const KBNK: Endgame = Endgame {
+    name: "KBNK",
+    eval: kbnk,
+};
+const INSUFFICIENT_MATERIAL: Endgame = Endgame {
+    name: "insufficient material",
+    eval: draw,
+};
+// these are rules that cover a whole family of signatures
+const KXK: Endgame = Endgame {
+    name: "KXK",
+    eval: kxk,
+};
+const OPPOSITE_BISHOPS: Endgame = Endgame {
+    name: "opposite coloured bishops",
+    eval: opposite_bishops,
+};
+const WRONG_ROOK_PAWN: Endgame = Endgame {
+    name: "wrong rook pawn",
+    eval: wrong_rook_pawn,
+};
+
+// exact signatures, looked up before the rules
+fn exact_endgame(key: &str) -> Option<&'static Endgame> {
+    match key {
+        "KPK" => Some(&KPK),
+        "KBNK" => Some(&KBNK),
+        "KK" | "KBK" | "KNK" | "KNNK" => Some(&INSUFFICIENT_MATERIAL),
+        _ => None,
+    }
+}
+
+const PIECE_ORDER: [PieceType; 6] = [
+    PieceType::King,
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Pawn,
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MaterialSignature {
+    white: [u8; 6], // in PIECE_ORDER
+    black: [u8; 6],
+}
+
+impl MaterialSignature {
+    pub fn new(board: &BitBoard) -> MaterialSignature {
+        let mut white = [0; 6];
+        let mut black = [0; 6];
+        for (i, &piece_type) in PIECE_ORDER.iter().enumerate() {
+            white[i] = board.get_piece_type_count(piece_type, Color::White);
+            black[i] = board.get_piece_type_count(piece_type, Color::Black);
+        }
+        MaterialSignature { white, black }
+    }
+
+    pub fn count(&self, piece_type: PieceType, color: Color) -> u8 {
+        let i = PIECE_ORDER.iter().position(|&p| p == piece_type).unwrap();
+        match color {
+            Color::White => self.white[i],
+            Color::Black => self.black[i],
+        }
+    }
+
+    fn side_key(&self, color: Color) -> String {
+        let mut key = String::new();
+        for &piece_type in PIECE_ORDER.iter() {
+            for _ in 0..self.count(piece_type, color) {
+                key.push_str(piece_type.to().to_uppercase().as_str());
+            }
+        }
+        key
+    }
+
+    // ex. KRK with strong side white for white king + rook vs black king
+    pub fn key(&self, strong_side: Color) -> String {
+        format!(
+            "{}{}",
+            self.side_key(strong_side),
+            self.side_key(strong_side.opposite())
+        )
+    }
+
+    fn non_pawn_pieces(&self, color: Color) -> u8 {
+        self.count(PieceType::Queen, color)
+            + self.count(PieceType::Rook, color)
+            + self.count(PieceType::Bishop, color)
+            + self.count(PieceType::Knight, color)
+    }
+
+    fn is_bare_king(&self, color: Color) -> bool {
+        self.non_pawn_pieces(color) == 0 && self.count(PieceType::Pawn, color) == 0
+    }
+
+    // can force mate against a bare king without help from pawns
+    fn has_mating_material(&self, color: Color) -> bool {
+        let bishops = self.count(PieceType::Bishop, color);
+        let knights = self.count(PieceType::Knight, color);
+        self.count(PieceType::Queen, color) > 0
+            || self.count(PieceType::Rook, color) > 0
+            || bishops >= 2
+            || (bishops >= 1 && knights >= 1)
+    }
+}
+
+// finds the endgame for this position and which side is the stronger one
+pub fn find_endgame(signature: &MaterialSignature) -> Option<(&'static Endgame, Color)> {
+    for &strong_side in [Color::White, Color::Black].iter() {
+        if let Some(endgame) = exact_endgame(signature.key(strong_side).as_str()) {
+            return Some((endgame, strong_side));
+        }
+    }
+    for &strong_side in [Color::White, Color::Black].iter() {
+        let weak_side = strong_side.opposite();
+        if signature.is_bare_king(weak_side) && signature.has_mating_material(strong_side) {
+            return Some((&KXK, strong_side));
+        }
+        if signature.is_bare_king(weak_side)
+            && signature.non_pawn_pieces(strong_side) == 1
+            && signature.count(PieceType::Bishop, strong_side) == 1
+            && signature.count(PieceType::Pawn, strong_side) > 0
+        {
+            return Some((&WRONG_ROOK_PAWN, strong_side));
+        }
+    }
+    let only_a_bishop = |color: Color| {
+        signature.non_pawn_pieces(color) == 1 && signature.count(PieceType::Bishop, color) == 1
+    };
+    if only_a_bishop(Color::White) && only_a_bishop(Color::Black) {
+        // no strong side really, so the bishops ending is always looked at from white
+        return Some((&OPPOSITE_BISHOPS, Color::White));
+    }
+    None
+}
+
+// score is the normal evaluation, from white's point of view
+// returns the adjusted score and the name of the endgame if there was one
+pub fn evaluate(game_state: &GameState, score: f32) -> (f32, Option<&'static str>) {
+    let board = game_state.get_board_ref();
+    // nothing in here cares about positions with this many pieces
+    if board.get_piece_count() > 12 {
+        return (score, None);
+    }
+    let signature = MaterialSignature::new(board);
+    match find_endgame(&signature) {
+        None => (score, None),
+        Some((endgame, strong_side)) => (
+            (endgame.eval)(game_state, &signature, strong_side, score),
+            Some(endgame.name),
+        ),
+    }
+}
+
+// file and rank of a single piece bit board, 0 - 7
+fn square_of(bits: u64) -> (i32, i32) {
+    let idx = bits.trailing_zeros() as i32;
+    (idx % 8, idx / 8)
+}
+
+fn king_square(board: &BitBoard, color: Color) -> (i32, i32) {
+    square_of(board.get_kings_board() & color_board(board, color))
+}
+
+fn color_board(board: &BitBoard, color: Color) -> u64 {
+    match color {
+        Color::White => board.get_white_pieces_board(),
+        Color::Black => board.get_black_pieces_board(),
+    }
+}
+
+// number of king moves between two squares
+fn distance(a: (i32, i32), b: (i32, i32)) -> i32 {
+    (a.0 - b.0).abs().max((a.1 - b.1).abs())
+}
+
+fn manhattan_distance(a: (i32, i32), b: (i32, i32)) -> i32 {
+    (a.0 - b.0).abs() + (a.1 - b.1).abs()
+}
+
+// 0 in the middle of the board, 6 in the corners
+fn center_distance(square: (i32, i32)) -> i32 {
+    let file = if square.0 < 4 {
+        3 - square.0
+    } else {
+        square.0 - 4
+    };
+    let rank = if square.1 < 4 {
+        3 - square.1
+    } else {
+        square.1 - 4
+    };
+    file + rank
+}
+
+fn for_strong_side(strong_side: Color, value: f32) -> f32 {
+    match strong_side {
+        Color::White => value,
+        Color::Black => -value,
+    }
+}
+
+fn draw(_: &GameState, _: &MaterialSignature, _: Color, _: f32) -> f32 {
+    0.0
+}
+
+// mop-up, push the lone king to the edge and bring our king closer to help
+// https://www.chessprogramming.org/Mop-up_Evaluation
+fn kxk(game_state: &GameState, _: &MaterialSignature, strong_side: Color, score: f32) -> f32 {
+    let board = game_state.get_board_ref();
+    let strong_king = king_square(board, strong_side);
+    let weak_king = king_square(board, strong_side.opposite());
+    let mop_up =
+        10 * center_distance(weak_king) + 4 * (14 - manhattan_distance(strong_king, weak_king));
+    score + for_strong_side(strong_side, mop_up as f32)
+}
+
+// like KXK but only the two corners the bishop can cover are any good for mating
+fn kbnk(game_state: &GameState, _: &MaterialSignature, strong_side: Color, score: f32) -> f32 {
+    let board = game_state.get_board_ref();
+    let strong_king = king_square(board, strong_side);
+    let weak_king = king_square(board, strong_side.opposite());
+    let bishop = board.get_bishops_board() & color_board(board, strong_side);
+    let bishop_color = BitBoard::get_square_color(bishop);
+    let corners = [(0, 0), (7, 0), (0, 7), (7, 7)];
+    let corner_distance = corners
+        .iter()
+        .filter(|&&(file, rank)| BitBoard::get_square_color(1 << (rank * 8 + file)) == bishop_color)
+        .map(|&corner| manhattan_distance(weak_king, corner))
+        .min()
+        .unwrap();
+    let mop_up =
+        20 * (14 - corner_distance) + 4 * (14 - manhattan_distance(strong_king, weak_king));
+    score + for_strong_side(strong_side, mop_up as f32)
+}
+
+/*
+rule of the square, if the lone king can't get into the square of the pawn it's never catching it
+if it can, it's only still winning when our king is already in front of the pawn, and never
+with a rook pawn
+*/
+fn kpk(game_state: &GameState, _: &MaterialSignature, strong_side: Color, score: f32) -> f32 {
+    let board = game_state.get_board_ref();
+    let weak_side = strong_side.opposite();
+    let strong_king = king_square(board, strong_side);
+    let weak_king = king_square(board, weak_side);
+    let pawn = square_of(board.get_pawns_board() & color_board(board, strong_side));
+    let (promotion_rank, forward) = match strong_side {
+        Color::White => (7, 1),
+        Color::Black => (0, -1),
+    };
+    // a pawn on its starting rank can jump a square
+    let start_rank = promotion_rank - 6 * forward;
+    let pawn_rank = if pawn.1 == start_rank {
+        pawn.1 + forward
+    } else {
+        pawn.1
+    };
+    let pawn_distance = (promotion_rank - pawn_rank).abs();
+    let mut king_distance = distance(weak_king, (pawn.0, promotion_rank));
+    if game_state.get_player_to_move() == weak_side {
+        king_distance -= 1;
+    }
+    if king_distance > pawn_distance {
+        // it's going to be a queen
+        return score + for_strong_side(strong_side, 500.0);
+    }
+    let is_rook_pawn = pawn.0 == 0 || pawn.0 == 7;
+    let king_in_front =
+        (strong_king.0 - pawn.0).abs() <= 1 && (strong_king.1 - pawn.1) * forward > 0;
+    if is_rook_pawn {
+        0.0
+    } else if king_in_front {
+        score
+    } else {
+        score / 4.0
+    }
+}
+
+// with only pawns besides the bishops, bishops on different colours are very drawish
+fn opposite_bishops(game_state: &GameState, _: &MaterialSignature, _: Color, score: f32) -> f32 {
+    let board = game_state.get_board_ref();
+    let white_bishop = board.get_bishops_board() & board.get_white_pieces_board();
+    let black_bishop = board.get_bishops_board() & board.get_black_pieces_board();
+    if BitBoard::get_square_color(white_bishop) != BitBoard::get_square_color(black_bishop) {
+        score / 2.0
+    } else {
+        score
+    }
+}
+
+// bishop and rook pawns where the bishop can't cover the queening square, the lone king just
+// sits in the corner
+fn wrong_rook_pawn(
+    game_state: &GameState,
+    _: &MaterialSignature,
+    strong_side: Color,
+    score: f32,
+) -> f32 {
+    let board = game_state.get_board_ref();
+    let pawns = board.get_pawns_board() & color_board(board, strong_side);
+    let a_file = BitBoard::get_file_for_bit(1);
+    let h_file = BitBoard::get_file_for_bit(1 << 7);
+    let file = if pawns & !a_file == 0 {
+        0
+    } else if pawns & !h_file == 0 {
+        7
+    } else {
+        return score;
+    };
+    let promotion_rank = match strong_side {
+        Color::White => 7,
+        Color::Black => 0,
+    };
+    let bishop = board.get_bishops_board() & color_board(board, strong_side);
+    let promotion_square = 1u64 << (promotion_rank * 8 + file);
+    if BitBoard::get_square_color(bishop) == BitBoard::get_square_color(promotion_square) {
+        return score;
+    }
+    let weak_king = king_square(board, strong_side.opposite());
+    if distance(weak_king, (file, promotion_rank)) <= 1 {
+        0.0
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_notation::fen_reader;
+
+    fn eval(fen: &str, score: f32) -> (f32, Option<&'static str>) {
+        evaluate(&fen_reader::make_game_state(fen).unwrap(), score)
+    }
+
+    #[test]
+    fn test_material_signature() {
+        let game_state = fen_reader::make_game_state("8/8/8/4k3/8/8/8/KBN5 w - - 0 1").unwrap();
+        let signature = MaterialSignature::new(game_state.get_board_ref());
+        assert_eq!(signature.key(Color::White), "KBNK");
+        assert_eq!(signature.key(Color::Black), "KKBN");
+        let game_state = fen_reader::make_game_state("8/8/1p6/4k3/8/8/2R5/K7 w - - 0 1").unwrap();
+        let signature = MaterialSignature::new(game_state.get_board_ref());
+        assert_eq!(signature.key(Color::White), "KRKP");
+        // starting position is nowhere near an endgame
+        assert_eq!(evaluate(&GameState::starting_game(), 10.0), (10.0, None));
+    }
+
+    #[test]
+    fn test_kxk() {
+        // lone black king in the corner is better for white than in the middle
+        let (corner, name) = eval("k7/8/2K5/8/8/8/7Q/8 w - - 0 1", 900.0);
+        assert_eq!(name, Some("KXK"));
+        let (center, _) = eval("8/8/2K5/8/4k3/8/7Q/8 w - - 0 1", 900.0);
+        assert!(corner > center);
+        // same thing for black
+        let (corner, _) = eval("7K/8/8/8/8/2k5/8/r7 b - - 0 1", -500.0);
+        let (center, _) = eval("8/8/8/4K3/8/2k5/8/r7 b - - 0 1", -500.0);
+        assert!(corner < center);
+        // kbnk wants the corner the bishop can reach, a1 is dark
+        let (right_corner, name) = eval("8/8/8/8/8/1K6/8/k1B1N3 w - - 0 1", 585.0);
+        assert_eq!(name, Some("KBNK"));
+        let (wrong_corner, _) = eval("8/8/8/8/8/6K1/8/2B1N2k w - - 0 1", 585.0);
+        assert!(right_corner > wrong_corner);
+    }
+
+    #[test]
+    fn test_kpk() {
+        // black king is one step outside the square of the pawn
+        let (score, name) = eval("8/8/8/8/4P3/k7/8/K7 w - - 0 1", 100.0);
+        assert_eq!(name, Some("KPK"));
+        assert_eq!(score, 600.0);
+        // but gets there if it's black's move, white's king is too far away to help
+        assert_eq!(eval("8/8/8/8/4P3/k7/8/K7 b - - 0 1", 100.0).0, 25.0);
+        // unless it's already in front of the pawn
+        assert_eq!(eval("8/8/8/3K4/4P3/k7/8/8 b - - 0 1", 100.0).0, 100.0);
+        // rook pawn with the king in front of it is a draw
+        assert_eq!(eval("k7/8/8/8/P7/8/8/7K w - - 0 1", 100.0).0, 0.0);
+        // and it works for black too
+        assert_eq!(eval("7K/k7/8/4p3/8/8/8/8 w - - 0 1", -100.0).0, -600.0);
+    }
+
+    #[test]
+    fn test_draw_scaling() {
+        // knight alone can't win
+        assert_eq!(eval("8/8/8/4k3/8/8/8/KN6 w - - 0 1", 285.0).0, 0.0);
+        // opposite coloured bishops, a pawn up
+        let (score, name) = eval("8/5k2/8/3b4/8/4B3/4P3/4K3 w - - 0 1", 100.0);
+        assert_eq!(name, Some("opposite coloured bishops"));
+        assert_eq!(score, 50.0);
+        // same coloured bishops are left alone
+        assert_eq!(eval("8/5k2/8/4b3/8/4B3/4P3/4K3 w - - 0 1", 100.0).0, 100.0);
+        // light squared bishop can't help the h pawn queen on h8 (dark)
+        let (score, name) = eval("7k/8/8/8/7P/8/8/3BK3 w - - 0 1", 400.0);
+        assert_eq!(name, Some("wrong rook pawn"));
+        assert_eq!(score, 0.0);
+        // dark squared bishop can
+        assert_eq!(eval("7k/8/8/8/7P/8/8/2B1K3 w - - 0 1", 400.0).0, 400.0);
+    }
+}
@@ -1,7 +1,14 @@
+use crate::ai::endgame;
+use crate::bit_board::BitBoard;
 use crate::board::*;
 use crate::game_state::GameState;
 use crate::move_generator::Move;
 use crate::move_generator::{self, plmg};
+use std::fmt;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::Path;
 
 /*
 previously with Board
@@ -165,6 +172,137 @@ impl Evaluation {
     }
 }
 
+// weights used by the evaluation function, these can be tuned against game results
+// see tuner.rs
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EvalParams {
+    pub pawn: f32,
+    pub knight: f32,
+    pub bishop: f32,
+    pub rook: f32,
+    pub queen: f32,
+    pub doubled_pawn: f32,
+    pub isolated_pawn: f32,
+    pub mobility: f32,
+    pub piece_square: f32,
+    pub king_shield: f32,
+}
+
+impl Default for EvalParams {
+    fn default() -> EvalParams {
+        EvalParams {
+            pawn: 100.0,
+            knight: 285.0,
+            bishop: 300.0,
+            rook: 500.0,
+            queen: 900.0,
+            doubled_pawn: 1.0,
+            isolated_pawn: 1.0,
+            mobility: 1.0,
+            piece_square: 1.0,
+            king_shield: 10.0,
+        }
+    }
+}
+
+impl EvalParams {
+    pub const NAMES: [&'static str; 10] = [
+        "pawn",
+        "knight",
+        "bishop",
+        "rook",
+        "queen",
+        "doubled_pawn",
+        "isolated_pawn",
+        "mobility",
+        "piece_square",
+        "king_shield",
+    ];
+
+    pub fn values(&self) -> Vec<f32> {
+        vec![
+            self.pawn,
+            self.knight,
+            self.bishop,
+            self.rook,
+            self.queen,
+            self.doubled_pawn,
+            self.isolated_pawn,
+            self.mobility,
+            self.piece_square,
+            self.king_shield,
+        ]
+    }
+
+    pub fn from_values(values: &[f32]) -> EvalParams {
+        let mut params = EvalParams::default();
+        for (name, &value) in EvalParams::NAMES.iter().zip(values.iter()) {
+            params.set(name, value);
+        }
+        params
+    }
+
+    // returns false if there's no param with this name
+    pub fn set(&mut self, name: &str, value: f32) -> bool {
+        let param = match name {
+            "pawn" => &mut self.pawn,
+            "knight" => &mut self.knight,
+            "bishop" => &mut self.bishop,
+            "rook" => &mut self.rook,
+            "queen" => &mut self.queen,
+            "doubled_pawn" => &mut self.doubled_pawn,
+            "isolated_pawn" => &mut self.isolated_pawn,
+            "mobility" => &mut self.mobility,
+            "piece_square" => &mut self.piece_square,
+            "king_shield" => &mut self.king_shield,
+            _ => return false,
+        };
+        *param = value;
+        true
+    }
+
+    // one "name value" pair per line, lines starting with # are ignored
+    pub fn read(str: &str) -> Result<EvalParams, String> {
+        let mut params = EvalParams::default();
+        for (i, line) in str.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() != 2 {
+                return Err(format!("line {}: expected \"name value\"", i + 1));
+            }
+            let value = parts[1]
+                .parse::<f32>()
+                .map_err(|_| format!("line {}: {} is not a number", i + 1, parts[1]))?;
+            if !params.set(parts[0], value) {
+                return Err(format!("line {}: unknown param {}", i + 1, parts[0]));
+            }
+        }
+        Ok(params)
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<EvalParams, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+        EvalParams::read(contents.as_str())
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for EvalParams {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (name, value) in EvalParams::NAMES.iter().zip(self.values()) {
+            writeln!(f, "{} {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
 // Basic evaluation algorithm
 //     f(p) = 200(K-K')
 //        + 9(Q-Q')
@@ -177,47 +315,195 @@ impl Evaluation {
 // KQRBNP = number of kings, queens, rooks, bishops, knights and pawns
 // D,S,I = doubled, blocked and isolated pawns
 // M = Mobility (the number of legal moves)
+//
+// on top of that there's a piece square table bonus and a bonus for pawns sheltering the king
+// and known endgames get their score adjusted, see endgame.rs
 
 pub fn evaluate(
     game_state: &GameState,
     white_moves_ref: Option<&Vec<Move>>,
     black_moves_ref: Option<&Vec<Move>>,
 ) -> Evaluation {
+    evaluate_with_params(game_state, &EvalParams::default())
+}
+
+/*
+anything Ai can use to score a position, scores are from white's point of view
+the hooks are for evaluators that keep some state of their own up to date as the search moves
+through the tree, the search calls
+    prepare once on the board it's about to search
+    make_move after every make_move_mut, unmake_move after every unmake_move_mut
+*/
+pub trait Evaluator {
+    fn evaluate(&self, game_state: &GameState) -> Evaluation;
+
+    fn prepare(&mut self, _game_state: &mut GameState) {}
+    fn make_move(&mut self, _game_state: &GameState, _m: &Move) {}
+    fn unmake_move(&mut self, _game_state: &GameState, _m: &Move) {}
+}
+
+// the hand written evaluation below
+#[derive(Debug, Default)]
+pub struct ClassicEvaluator {
+    pub params: EvalParams,
+}
+
+impl ClassicEvaluator {
+    pub fn new(params: EvalParams) -> ClassicEvaluator {
+        ClassicEvaluator { params }
+    }
+}
+
+impl Evaluator for ClassicEvaluator {
+    fn evaluate(&self, game_state: &GameState) -> Evaluation {
+        evaluate_with_params(game_state, &self.params)
+    }
+}
+
+// just counts material, handy for testing search without the positional stuff getting in the way
+#[derive(Debug, Default)]
+pub struct MaterialEvaluator {
+    pub params: EvalParams,
+}
+
+impl Evaluator for MaterialEvaluator {
+    fn evaluate(&self, game_state: &GameState) -> Evaluation {
+        let (white, black) = count_material(&PieceCount::new(game_state), &self.params);
+        Evaluation {
+            score: white - black,
+            mated_player: None,
+        }
+    }
+}
+
+pub fn evaluate_with_params(game_state: &GameState, params: &EvalParams) -> Evaluation {
+    let (terms, mated_player) = eval_terms(game_state, params);
+    let (score, _endgame) =
+        endgame::evaluate(game_state, terms.iter().map(|term| term.net()).sum());
+    Evaluation {
+        score,
+        mated_player,
+    }
+}
+
+// same thing as evaluate_with_params but keeps every term around so we can see where the
+// score came from, see --eval in main
+pub fn evaluate_with_trace(game_state: &GameState, params: &EvalParams) -> EvalTrace {
+    let (terms, mated_player) = eval_terms(game_state, params);
+    let (score, endgame) = endgame::evaluate(game_state, terms.iter().map(|term| term.net()).sum());
+    EvalTrace {
+        terms,
+        endgame,
+        evaluation: Evaluation {
+            score,
+            mated_player,
+        },
+    }
+}
+
+// one line of the eval breakdown, each side's contribution is from its own point of view
+// (penalties are negative) and net is white - black, same as the score
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EvalTerm {
+    pub name: &'static str,
+    pub white: f32,
+    pub black: f32,
+}
+
+impl EvalTerm {
+    pub fn net(&self) -> f32 {
+        self.white - self.black
+    }
+}
+
+pub const TERM_COUNT: usize = 6;
+
+#[derive(Debug, Copy, Clone)]
+pub struct EvalTrace {
+    pub terms: [EvalTerm; TERM_COUNT],
+    // set when the score came from one of the specialised endgame evaluations, see endgame.rs
+    pub endgame: Option<&'static str>,
+    pub evaluation: Evaluation,
+}
+
+impl EvalTrace {
+    pub fn get_term(&self, name: &str) -> Option<&EvalTerm> {
+        self.terms.iter().find(|term| term.name == name)
+    }
+}
+
+impl fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let line = "------------------------+----------+----------+----------";
+        writeln!(
+            f,
+            "{:<23} | {:>8} | {:>8} | {:>8}",
+            " Term", "White", "Black", "Net"
+        )?;
+        writeln!(f, "{}", line)?;
+        for term in self.terms.iter() {
+            // + 0.0 so penalties of zero don't print as -0.0
+            writeln!(
+                f,
+                " {:<22} | {:>8.1} | {:>8.1} | {:>8.1}",
+                term.name,
+                term.white + 0.0,
+                term.black + 0.0,
+                term.net() + 0.0
+            )?;
+        }
+        writeln!(f, "{}", line)?;
+        let white: f32 = self.terms.iter().map(|term| term.white).sum();
+        let black: f32 = self.terms.iter().map(|term| term.black).sum();
+        writeln!(
+            f,
+            " {:<22} | {:>8.1} | {:>8.1} | {:>8.1}",
+            "Total", white, black, self.evaluation.score
+        )?;
+        if let Some(endgame) = self.endgame {
+            writeln!(f, " Endgame: {}, the total is adjusted for it", endgame)?;
+        }
+        if let Some(color) = self.evaluation.mated_player {
+            writeln!(f, " {:?} is checkmated", color)?;
+        }
+        Ok(())
+    }
+}
+
+fn count_material(c: &PieceCount, params: &EvalParams) -> (f32, f32) {
+    let white = params.queen * c.white_queen as f32
+        + params.rook * c.white_rook as f32
+        + params.bishop * c.white_bishop as f32
+        + params.knight * c.white_knight as f32
+        + params.pawn * c.white_pawn as f32;
+    let black = params.queen * c.black_queen as f32
+        + params.rook * c.black_rook as f32
+        + params.bishop * c.black_bishop as f32
+        + params.knight * c.black_knight as f32
+        + params.pawn * c.black_pawn as f32;
+    (white, black)
+}
+
+fn eval_terms(
+    game_state: &GameState,
+    params: &EvalParams,
+) -> ([EvalTerm; TERM_COUNT], Option<Color>) {
     let board = game_state.get_board_ref();
     let c = PieceCount::new(game_state);
     // let k: i32 = 200 * (c.white_king as i32 - c.black_king as i32);
-    let queen_val: i32 = 900 * (c.white_queen as i32 - c.black_queen as i32);
-    let rook_val: i32 = 500 * (c.white_rook as i32 - c.black_rook as i32);
-    let bishop_val: i32 = 300 * (c.white_bishop as i32 - c.black_bishop as i32);
-    let knight_val: i32 = 285 * (c.white_knight as i32 - c.black_knight as i32);
-    let pawn_val: i32 = 100 * (c.white_pawn as i32 - c.black_pawn as i32);
-    let material_value =  (queen_val + rook_val + bishop_val + pawn_val) as f32;
+    let (white_material, black_material) = count_material(&c, params);
 
     // pawn structure evaluation
     let (white_pawn_file, black_pawn_file) = make_pawn_count_by_file(game_state);
     let (white_doubled_pawns, black_doubled_pawns) =
         count_doubled_pawns(&white_pawn_file, &black_pawn_file);
-    let doubled: i32 = white_doubled_pawns as i32 - black_doubled_pawns as i32;
     let (white_isolated_pawns, black_isolated_pawns) =
         count_isolated_pawns(&white_pawn_file, &black_pawn_file);
-    let isolated: i32 = white_isolated_pawns as i32 - black_isolated_pawns as i32;
-    let pawn_structure:f32 = -1.0 * (doubled + isolated) as f32;
 
     // mobility
     let white_move_count: i32 = plmg::get_attack_mobility_count(board, Color::White) as i32;
     let black_move_count: i32 = plmg::get_attack_mobility_count(board, Color::Black) as i32;
 
-    // let white_move_count: i32 = white_moves_ref.map_or_else(
-    //     || plmg::get_attack_mobility_count(board, Color::White) as i32,
-    //     // || 0,
-    //     |moves| moves.len() as i32,
-    // );
-    // let black_move_count: i32 = black_moves_ref.map_or_else(
-    //     || plmg::get_attack_mobility_count(board, Color::Black) as i32,
-    //     // || 0,
-    //     |moves| moves.len() as i32,
-    // );
-
     // checkmate
     let mated_player = if game_state.player_to_move() == Color::White && white_move_count == 0 {
         Some(Color::White)
@@ -227,13 +513,187 @@ pub fn evaluate(
         None
     };
 
-    let mobility:f32 = 1.0 * (white_move_count - black_move_count) as f32;
-    // let mobility: f32 = 0.0;
+    let terms = [
+        EvalTerm {
+            name: "Material",
+            white: white_material,
+            black: black_material,
+        },
+        EvalTerm {
+            name: "Piece-square",
+            white: params.piece_square * piece_square_total(board, Color::White) as f32,
+            black: params.piece_square * piece_square_total(board, Color::Black) as f32,
+        },
+        EvalTerm {
+            name: "Pawns: doubled",
+            white: -params.doubled_pawn * white_doubled_pawns as f32,
+            black: -params.doubled_pawn * black_doubled_pawns as f32,
+        },
+        EvalTerm {
+            name: "Pawns: isolated",
+            white: -params.isolated_pawn * white_isolated_pawns as f32,
+            black: -params.isolated_pawn * black_isolated_pawns as f32,
+        },
+        EvalTerm {
+            name: "Mobility",
+            white: params.mobility * white_move_count as f32,
+            black: params.mobility * black_move_count as f32,
+        },
+        EvalTerm {
+            name: "King safety",
+            white: params.king_shield * count_king_shield_pawns(board, Color::White) as f32,
+            black: params.king_shield * count_king_shield_pawns(board, Color::Black) as f32,
+        },
+    ];
+    (terms, mated_player)
+}
 
-    Evaluation {
-        score: material_value + mobility + pawn_structure,
-        mated_player: mated_player,
+/*
+piece square tables, from the simplified evaluation function
+https://www.chessprogramming.org/Simplified_Evaluation_Function
+laid out the way you'd look at the board as white, a8 first and h1 last
+black uses the same tables flipped vertically
+*/
+#[rustfmt::skip]
+const PAWN_TABLE: [i8; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i8; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+#[rustfmt::skip]
+const BISHOP_TABLE: [i8; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+#[rustfmt::skip]
+const ROOK_TABLE: [i8; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+#[rustfmt::skip]
+const QUEEN_TABLE: [i8; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+// middle game king, stay home behind the pawns
+#[rustfmt::skip]
+const KING_TABLE: [i8; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+fn piece_square_table(piece_type: PieceType) -> &'static [i8; 64] {
+    match piece_type {
+        PieceType::King => &KING_TABLE,
+        PieceType::Queen => &QUEEN_TABLE,
+        PieceType::Bishop => &BISHOP_TABLE,
+        PieceType::Knight => &KNIGHT_TABLE,
+        PieceType::Rook => &ROOK_TABLE,
+        PieceType::Pawn => &PAWN_TABLE,
+    }
+}
+
+// bit n of a bit board is file n % 8, rank n / 8 (a1 is bit 0)
+fn piece_square_value(piece_type: PieceType, color: Color, bit_idx: u32) -> i32 {
+    let file = (bit_idx % 8) as usize;
+    let rank = (bit_idx / 8) as usize;
+    let row = match color {
+        Color::White => 7 - rank,
+        Color::Black => rank,
+    };
+    piece_square_table(piece_type)[row * 8 + file] as i32
+}
+
+fn piece_square_total(board: &BitBoard, color: Color) -> i32 {
+    let color_board = match color {
+        Color::White => board.get_white_pieces_board(),
+        Color::Black => board.get_black_pieces_board(),
+    };
+    let piece_boards = [
+        (PieceType::King, board.get_kings_board()),
+        (PieceType::Queen, board.get_queens_board()),
+        (PieceType::Bishop, board.get_bishops_board()),
+        (PieceType::Knight, board.get_knights_board()),
+        (PieceType::Rook, board.get_rooks_board()),
+        (PieceType::Pawn, board.get_pawns_board()),
+    ];
+    let mut total = 0;
+    for &(piece_type, piece_board) in piece_boards.iter() {
+        let mut bits = piece_board & color_board;
+        while bits != 0 {
+            total += piece_square_value(piece_type, color, bits.trailing_zeros());
+            bits &= bits - 1;
+        }
     }
+    total
+}
+
+// friendly pawns on the three files around the king, one or two ranks in front of it
+fn count_king_shield_pawns(board: &BitBoard, color: Color) -> u32 {
+    let color_board = match color {
+        Color::White => board.get_white_pieces_board(),
+        Color::Black => board.get_black_pieces_board(),
+    };
+    let king = board.get_kings_board() & color_board;
+    if king == 0 {
+        return 0;
+    }
+    let king_idx = king.trailing_zeros() as i32;
+    let (file, rank) = (king_idx % 8, king_idx / 8);
+    let forward = match color {
+        Color::White => 1,
+        Color::Black => -1,
+    };
+    let mut shield: u64 = 0;
+    for df in -1..=1 {
+        for distance in 1..=2 {
+            let (f, r) = (file + df, rank + forward * distance);
+            if (0..8).contains(&f) && (0..8).contains(&r) {
+                shield |= 1 << (r * 8 + f);
+            }
+        }
+    }
+    (shield & board.get_pawns_board() & color_board).count_ones()
 }
 
 #[cfg(test)]
@@ -255,7 +715,7 @@ mod tests {
         }
         // position 2
         let fen = "3rkr2/pp3p1p/4b3/3PP2n/1P1q3p/3R4/P1P3P1/2R1K3 b - - 0 19";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let (w, b) = make_pawn_count_by_file(&game_state);
         assert_eq!(w.files[0], 1);
         assert_eq!(w.files[1], 1);
@@ -277,7 +737,7 @@ mod tests {
 
         // pos 3
         let fen = "rnbqkbnr/3pppp1/2pP2p1/p6P/p1P5/8/P1PP1P1P/RNBQKBNR w KQkq - 0 1";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let (w, b) = make_pawn_count_by_file(&game_state);
         assert_eq!(w.files[0], 1);
         assert_eq!(w.files[1], 0);
@@ -310,7 +770,7 @@ mod tests {
 
         // pos 2
         let fen2 = "3rkr2/pp3p1p/4b3/3PP2n/1P1q3p/3R4/P1P3P1/2R1K3 b - - 0 19";
-        let game_state2 = fen_reader::make_game_state(fen2);
+        let game_state2 = fen_reader::make_game_state(fen2).unwrap();
         let (w, b) = make_pawn_count_by_file(&game_state2);
         let (w, b) = count_doubled_pawns(&w, &b);
         assert_eq!(w, 0);
@@ -318,7 +778,7 @@ mod tests {
 
         // pos 3
         let fen3 = "rnbqkbnr/3pppp1/2pP2p1/p6P/p1P5/8/P1PP1P1P/RNBQKBNR w KQkq - 0 1";
-        let game_state3 = fen_reader::make_game_state(fen3);
+        let game_state3 = fen_reader::make_game_state(fen3).unwrap();
         let (w, b) = make_pawn_count_by_file(&game_state3);
         let (w, b) = count_doubled_pawns(&w, &b);
         assert_eq!(w, 3);
@@ -335,7 +795,7 @@ mod tests {
 
         // pos 2
         let fen = "2r2rk1/1b4bp/1q1pp1p1/2p1np2/1p2P3/pNnPBPP1/PPPQN1BP/3R1RK1 w - - 0 1";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let (wf, bf) = make_pawn_count_by_file(&game_state);
         let (w, b) = count_isolated_pawns(&wf, &bf);
         assert_eq!(w, 0);
@@ -343,13 +803,68 @@ mod tests {
 
         // pos 3, edge pawns
         let fen = "2r2rk1/1b4bp/1q1pp1p1/4n3/1p2P3/1NnPBP2/P1PQN1BP/3R1RK1 w - - 0 1";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let (wf, bf) = make_pawn_count_by_file(&game_state);
         let (w, b) = count_isolated_pawns(&wf, &bf);
         assert_eq!(w, 2);
         assert_eq!(b, 1);
     }
 
+    #[test]
+    fn test_evaluate_with_trace() {
+        let params = EvalParams::default();
+        // everything cancels out in the starting position
+        let trace = evaluate_with_trace(&GameState::starting_game(), &params);
+        for term in trace.terms.iter() {
+            assert_eq!(term.white, term.black, "{}", term.name);
+        }
+        assert_eq!(trace.evaluation.score, 0.0);
+        assert_eq!(
+            trace.get_term("King safety").unwrap().white,
+            3.0 * params.king_shield
+        );
+
+        // the terms add up to the same score evaluate gives
+        let fen = "3rkr2/pp3p1p/4b3/3PP2n/1P1q3p/3R4/P1P3P1/2R1K3 b - - 0 19";
+        let game_state = fen_reader::make_game_state(fen).unwrap();
+        let trace = evaluate_with_trace(&game_state, &params);
+        let net: f32 = trace.terms.iter().map(|term| term.net()).sum();
+        assert_eq!(
+            trace.evaluation.score,
+            evaluate_with_params(&game_state, &params).score
+        );
+        assert_eq!(trace.evaluation.score, net);
+        let material = trace.get_term("Material").unwrap();
+        assert_eq!(material.white, 2.0 * params.rook + 6.0 * params.pawn);
+        assert_eq!(
+            material.net(),
+            params.pawn - (params.queen + params.bishop + params.knight)
+        );
+        assert_eq!(
+            trace.get_term("Pawns: doubled").unwrap().black,
+            -params.doubled_pawn
+        );
+        assert!(trace.to_string().contains("Material"));
+    }
+
+    #[test]
+    fn test_piece_square_and_king_shield() {
+        // castled king behind its pawns vs a king out in the open
+        let fen = "8/8/8/8/3k4/8/5PPP/6K1 w - - 0 1";
+        let game_state = fen_reader::make_game_state(fen).unwrap();
+        let board = game_state.get_board_ref();
+        assert_eq!(count_king_shield_pawns(board, Color::White), 3);
+        assert_eq!(count_king_shield_pawns(board, Color::Black), 0);
+        // g1 king is worth 30, a black king on d4 is scored like a white king on d5
+        assert_eq!(piece_square_value(PieceType::King, Color::White, 6), 30);
+        assert_eq!(piece_square_value(PieceType::King, Color::Black, 27), -50);
+        // a pawn on e4 for white is worth the same as a pawn on e5 for black
+        assert_eq!(
+            piece_square_value(PieceType::Pawn, Color::White, 28),
+            piece_square_value(PieceType::Pawn, Color::Black, 36)
+        );
+    }
+
     #[test]
     fn test_piece_count() {
         // starting position
@@ -372,7 +887,7 @@ mod tests {
 
         // position 2
         let fen = "3rkr2/pp3p1p/4b3/3PP2n/1P1q3p/3R4/P1P3P1/2R1K3 b - - 0 19";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let piece_count = PieceCount::new(&game_state);
         // black pieces
         assert_eq!(piece_count.black_rook, 2);
@@ -393,7 +908,7 @@ mod tests {
     #[test]
     fn test_pawn_count() {
         let fen = "rnb1kr2/pp1p1p1p/1qB2n2/7Q/1P1pPP1p/b4N1R/P1P3P1/RNB1K3 b Qq - 4 10";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let (w_count, b_count) = make_pawn_count_by_file(&game_state);
         let white_file: [u8; 8] = [1, 1, 1, 0, 1, 1, 1, 0];
         let black_file: [u8; 8] = [1, 1, 0, 2, 0, 1, 0, 2];
@@ -404,7 +919,7 @@ mod tests {
     #[test]
     fn test_count_pawn_structure() {
         let fen = "rnb1kr2/pp1p1p1p/1qB2n2/7Q/1P1pPP1p/b4N1R/P1P3P1/RNB1K3 b Qq - 4 10";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         // let (w, b) = count_blocked_pawns(&game_state);
         // assert_eq!(3, b);
         // assert_eq!(1, w);
@@ -422,7 +937,7 @@ mod tests {
     #[bench]
     fn bench_evaluate_board(b: &mut Bencher) {
         let fen = "rnb1kr2/pp1p1p1p/1qB2n2/7Q/1P1pPP1p/b4N1R/P1P3P1/RNB1K3 b Qq - 4 10";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         b.iter(|| {
             for i in 0..100 {
                 black_box({
@@ -435,7 +950,7 @@ mod tests {
     #[bench]
     fn bench_make_pawn_count_by_file(b: &mut Bencher) {
         let fen = "3rkr2/pp3p1p/4b3/3PP2n/1P1q3p/3R4/P1P3P1/2R1K3 b - - 0 19";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         b.iter(|| {
             for i in 0..1000 {
                 black_box({
@@ -447,7 +962,7 @@ mod tests {
     #[bench]
     fn bench_make_pawn_count_isolated_pawns(b: &mut Bencher) {
         let fen = "2r2rk1/1b4bp/1q1pp1p1/4n3/1p2P3/1NnPBP2/P1PQN1BP/3R1RK1 w - - 0 1";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
 
         b.iter(|| {
             let (w, b) = make_pawn_count_by_file(&game_state);
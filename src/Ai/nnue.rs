@@ -0,0 +1,543 @@
+use crate::ai::evaluator::{Evaluation, Evaluator};
+use crate::board::*;
+use crate::game_state::GameState;
+use crate::move_generator::{Move, MoveType};
+use std::fmt;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/*
+efficiently updatable neural network evaluation
+https://www.chessprogramming.org/NNUE
+
+768 inputs, one for every (side, piece type, square), from the point of view of each player
+so there are two accumulators, one where white's pieces are "ours" and one where black's are
+(black's view is flipped vertically so both players see their own pieces at the bottom)
+
+    768 -> hidden (x2 perspectives) -> 1
+
+the first layer is just a sum of the weights of the active features, so when a move is made we
+only have to subtract the weights of the features that went away and add the ones that showed
+up instead of redoing the whole thing, see NnueEvaluator's move hooks
+
+quantisation
+    feature weights and biases are i16, scaled by QA
+    output weights are i8, scaled by QB
+    hidden activation is clipped relu, clamp(x, 0, QA)
+*/
+
+pub const INPUT_SIZE: usize = 768;
+const QA: i32 = 255;
+const QB: i32 = 64;
+// network output of 1.0 is this many centipawns
+const SCALE: i32 = 400;
+
+const MAGIC: &[u8; 4] = b"CENN";
+const VERSION: u32 = 1;
+
+#[derive(PartialEq, Eq)]
+pub struct Network {
+    hidden_size: usize,
+    feature_weights: Vec<i16>, // INPUT_SIZE * hidden_size, all the weights for one feature together
+    feature_bias: Vec<i16>,    // hidden_size
+    output_weights: Vec<i8>,   // 2 * hidden_size, side to move first
+    output_bias: i32,
+}
+
+// don't print all the weights
+impl fmt::Debug for Network {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Network {{ hidden_size: {} }}", self.hidden_size)
+    }
+}
+
+/*
+weights file layout, everything little endian
+
+    "CENN"
+    u32 version
+    u32 hidden size
+    i16 feature weights [768 * hidden size]
+    i16 feature bias [hidden size]
+    i8 output weights [2 * hidden size]
+    i32 output bias
+*/
+impl Network {
+    pub fn new(
+        hidden_size: usize,
+        feature_weights: Vec<i16>,
+        feature_bias: Vec<i16>,
+        output_weights: Vec<i8>,
+        output_bias: i32,
+    ) -> Result<Network, String> {
+        if hidden_size == 0 {
+            return Err(String::from("hidden size must be at least 1"));
+        }
+        if feature_weights.len() != INPUT_SIZE * hidden_size {
+            return Err(format!(
+                "expected {} feature weights, found {}",
+                INPUT_SIZE * hidden_size,
+                feature_weights.len()
+            ));
+        }
+        if feature_bias.len() != hidden_size {
+            return Err(format!(
+                "expected {} feature biases, found {}",
+                hidden_size,
+                feature_bias.len()
+            ));
+        }
+        if output_weights.len() != 2 * hidden_size {
+            return Err(format!(
+                "expected {} output weights, found {}",
+                2 * hidden_size,
+                output_weights.len()
+            ));
+        }
+        Ok(Network {
+            hidden_size,
+            feature_weights,
+            feature_bias,
+            output_weights,
+            output_bias,
+        })
+    }
+
+    pub fn hidden_size(&self) -> usize {
+        self.hidden_size
+    }
+
+    pub fn read(bytes: &[u8]) -> Result<Network, String> {
+        let mut reader = ByteReader { bytes, at: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err(String::from("not a network file, bad magic"));
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(format!("unsupported network version {}", version));
+        }
+        let hidden_size = reader.u32()? as usize;
+        if hidden_size == 0 || hidden_size > 1 << 16 {
+            return Err(format!("bad hidden size {}", hidden_size));
+        }
+        let mut feature_weights = Vec::with_capacity(INPUT_SIZE * hidden_size);
+        for _ in 0..INPUT_SIZE * hidden_size {
+            feature_weights.push(reader.i16()?);
+        }
+        let mut feature_bias = Vec::with_capacity(hidden_size);
+        for _ in 0..hidden_size {
+            feature_bias.push(reader.i16()?);
+        }
+        let output_weights: Vec<i8> = reader
+            .take(2 * hidden_size)?
+            .iter()
+            .map(|&b| b as i8)
+            .collect();
+        let output_bias = reader.u32()? as i32;
+        if reader.at != bytes.len() {
+            return Err(format!(
+                "{} extra bytes at the end of the network file",
+                bytes.len() - reader.at
+            ));
+        }
+        Network::new(
+            hidden_size,
+            feature_weights,
+            feature_bias,
+            output_weights,
+            output_bias,
+        )
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(16 + 2 * self.feature_weights.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.hidden_size as u32).to_le_bytes());
+        for w in self.feature_weights.iter() {
+            bytes.extend_from_slice(&w.to_le_bytes());
+        }
+        for b in self.feature_bias.iter() {
+            bytes.extend_from_slice(&b.to_le_bytes());
+        }
+        bytes.extend(self.output_weights.iter().map(|&w| w as u8));
+        bytes.extend_from_slice(&self.output_bias.to_le_bytes());
+        bytes
+    }
+
+    pub fn load_from_file(path: &Path) -> Result<Network, String> {
+        let bytes =
+            fs::read(path).map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+        Network::read(&bytes).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    fn feature_weights(&self, feature: usize) -> &[i16] {
+        let start = feature * self.hidden_size;
+        &self.feature_weights[start..start + self.hidden_size]
+    }
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.at + n > self.bytes.len() {
+            return Err(String::from("network file is truncated"));
+        }
+        let taken = &self.bytes[self.at..self.at + n];
+        self.at += n;
+        Ok(taken)
+    }
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+    fn i16(&mut self) -> Result<i16, String> {
+        let b = self.take(2)?;
+        Ok(i16::from_le_bytes([b[0], b[1]]))
+    }
+}
+
+fn piece_type_idx(piece_type: PieceType) -> usize {
+    match piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    }
+}
+
+// input index of a piece as seen by perspective
+pub fn feature_index(
+    perspective: Color,
+    color: Color,
+    piece_type: PieceType,
+    at: &Coordinate,
+) -> usize {
+    let side = if color == perspective { 0 } else { 1 };
+    let file = (at.x() - 1) as usize;
+    let rank = match perspective {
+        Color::White => (at.y() - 1) as usize,
+        Color::Black => (8 - at.y()) as usize,
+    };
+    (side * 6 + piece_type_idx(piece_type)) * 64 + rank * 8 + file
+}
+
+// the first layer output for both perspectives
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Accumulator {
+    network: Arc<Network>,
+    white: Vec<i16>,
+    black: Vec<i16>,
+}
+
+impl Accumulator {
+    // build it from scratch for this position
+    pub fn new(network: Arc<Network>, game_state: &GameState) -> Accumulator {
+        let mut accumulator = Accumulator {
+            white: network.feature_bias.clone(),
+            black: network.feature_bias.clone(),
+            network,
+        };
+        for piece in game_state.get_piece_list() {
+            if let Some(at) = piece.at() {
+                accumulator.add(piece.color, piece.piece_type, at);
+            }
+        }
+        accumulator
+    }
+
+    pub fn network(&self) -> &Arc<Network> {
+        &self.network
+    }
+
+    // wrapping so that any add can always be undone exactly by a remove
+    pub fn add(&mut self, color: Color, piece_type: PieceType, at: &Coordinate) {
+        let network = &self.network;
+        let white = network.feature_weights(feature_index(Color::White, color, piece_type, at));
+        for (a, w) in self.white.iter_mut().zip(white) {
+            *a = a.wrapping_add(*w);
+        }
+        let black = network.feature_weights(feature_index(Color::Black, color, piece_type, at));
+        for (a, w) in self.black.iter_mut().zip(black) {
+            *a = a.wrapping_add(*w);
+        }
+    }
+
+    pub fn remove(&mut self, color: Color, piece_type: PieceType, at: &Coordinate) {
+        let network = &self.network;
+        let white = network.feature_weights(feature_index(Color::White, color, piece_type, at));
+        for (a, w) in self.white.iter_mut().zip(white) {
+            *a = a.wrapping_sub(*w);
+        }
+        let black = network.feature_weights(feature_index(Color::Black, color, piece_type, at));
+        for (a, w) in self.black.iter_mut().zip(black) {
+            *a = a.wrapping_sub(*w);
+        }
+    }
+
+    // color is the player making the move
+    pub fn make_move(&mut self, m: &Move, color: Color) {
+        self.apply_move(m, color, false);
+    }
+
+    pub fn unmake_move(&mut self, m: &Move, color: Color) {
+        self.apply_move(m, color, true);
+    }
+
+    fn apply_move(&mut self, m: &Move, color: Color, undo: bool) {
+        // everything removed by the move, then everything added, swapped when undoing
+        let mut removed: [Option<(Color, PieceType, Coordinate)>; 3] = [None; 3];
+        let mut added: [Option<(Color, PieceType, Coordinate)>; 2] = [None; 2];
+        removed[0] = Some((color, m.piece, m.from));
+        let moved_to = match m.move_type() {
+            MoveType::Promotion(promoted_to) => *promoted_to,
+            _ => m.piece,
+        };
+        added[0] = Some((color, moved_to, m.to));
+        if let Some(captured) = m.captured {
+            let captured_at = match m.move_type() {
+                MoveType::EnPassant => Coordinate::new(m.to.x(), m.from.y()),
+                _ => m.to,
+            };
+            removed[1] = Some((color.opposite(), captured, captured_at));
+        }
+        if let MoveType::Castling { rook_from, rook_to } = m.move_type() {
+            removed[2] = Some((color, PieceType::Rook, *rook_from));
+            added[1] = Some((color, PieceType::Rook, *rook_to));
+        }
+
+        for &(c, piece_type, at) in removed.iter().flatten() {
+            if undo {
+                self.add(c, piece_type, &at);
+            } else {
+                self.remove(c, piece_type, &at);
+            }
+        }
+        for &(c, piece_type, at) in added.iter().flatten() {
+            if undo {
+                self.remove(c, piece_type, &at);
+            } else {
+                self.add(c, piece_type, &at);
+            }
+        }
+    }
+
+    // score in centipawns from the side to move's point of view
+    pub fn forward(&self, side_to_move: Color) -> i32 {
+        let (us, them) = match side_to_move {
+            Color::White => (&self.white, &self.black),
+            Color::Black => (&self.black, &self.white),
+        };
+        let hidden_size = self.network.hidden_size;
+        let weights = &self.network.output_weights;
+        // i64, a full size hidden layer can add up past what an i32 holds
+        let mut sum: i64 = 0;
+        for (i, &a) in us.iter().enumerate() {
+            sum += ((a as i32).clamp(0, QA) * weights[i] as i32) as i64;
+        }
+        for (i, &a) in them.iter().enumerate() {
+            sum += ((a as i32).clamp(0, QA) * weights[hidden_size + i] as i32) as i64;
+        }
+        let score = (sum + self.network.output_bias as i64) * SCALE as i64 / (QA * QB) as i64;
+        score.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+}
+
+// score from white's point of view like evaluator::evaluate
+pub fn evaluate(accumulator: &Accumulator, side_to_move: Color) -> f32 {
+    let score = accumulator.forward(side_to_move) as f32;
+    match side_to_move {
+        Color::White => score,
+        Color::Black => -score,
+    }
+}
+
+// lets Ai search with a network, see evaluator::Evaluator
+// the accumulator is built when the search starts and the move hooks keep it in step with the board
+pub struct NnueEvaluator {
+    network: Arc<Network>,
+    accumulator: Option<Accumulator>,
+}
+
+impl NnueEvaluator {
+    pub fn new(network: Arc<Network>) -> NnueEvaluator {
+        NnueEvaluator {
+            network,
+            accumulator: None,
+        }
+    }
+
+    pub fn accumulator(&self) -> Option<&Accumulator> {
+        self.accumulator.as_ref()
+    }
+}
+
+impl Evaluator for NnueEvaluator {
+    fn evaluate(&self, game_state: &GameState) -> Evaluation {
+        let side_to_move = game_state.get_player_to_move();
+        // before any search there's nothing to keep up to date, build one just for this
+        let score = match &self.accumulator {
+            Some(accumulator) => evaluate(accumulator, side_to_move),
+            None => evaluate(
+                &Accumulator::new(self.network.clone(), game_state),
+                side_to_move,
+            ),
+        };
+        Evaluation {
+            score,
+            mated_player: None,
+        }
+    }
+
+    fn prepare(&mut self, game_state: &mut GameState) {
+        self.accumulator = Some(Accumulator::new(self.network.clone(), game_state));
+    }
+
+    // the move has been made so the player to move is the one that didn't make it
+    fn make_move(&mut self, game_state: &GameState, m: &Move) {
+        if let Some(accumulator) = self.accumulator.as_mut() {
+            accumulator.make_move(m, game_state.get_player_to_move().opposite());
+        }
+    }
+
+    fn unmake_move(&mut self, game_state: &GameState, m: &Move) {
+        if let Some(accumulator) = self.accumulator.as_mut() {
+            accumulator.unmake_move(m, game_state.get_player_to_move());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_notation::fen_reader;
+    use crate::move_generator::gen_legal_moves;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_network(hidden_size: usize) -> Network {
+        let mut rng = StdRng::seed_from_u64(7);
+        Network::new(
+            hidden_size,
+            (0..INPUT_SIZE * hidden_size)
+                .map(|_| rng.gen_range(-60..60))
+                .collect(),
+            (0..hidden_size).map(|_| rng.gen_range(0..100)).collect(),
+            (0..2 * hidden_size)
+                .map(|_| rng.gen_range(-64..64))
+                .collect(),
+            rng.gen_range(-1000..1000),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_feature_index() {
+        let e2 = Coordinate::new(5, 2);
+        let e7 = Coordinate::new(5, 7);
+        // a white pawn on e2 looks the same to white as a black pawn on e7 looks to black
+        assert_eq!(
+            feature_index(Color::White, Color::White, PieceType::Pawn, &e2),
+            feature_index(Color::Black, Color::Black, PieceType::Pawn, &e7)
+        );
+        assert_eq!(
+            feature_index(
+                Color::White,
+                Color::White,
+                PieceType::Pawn,
+                &Coordinate::new(1, 1)
+            ),
+            0
+        );
+        assert_eq!(
+            feature_index(
+                Color::White,
+                Color::Black,
+                PieceType::King,
+                &Coordinate::new(8, 8)
+            ),
+            INPUT_SIZE - 1
+        );
+    }
+
+    #[test]
+    fn test_read_write_network() {
+        let network = random_network(4);
+        let bytes = network.to_bytes();
+        assert_eq!(Network::read(&bytes).unwrap(), network);
+        assert!(Network::read(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Network::read(b"nope").is_err());
+    }
+
+    // play through a bunch of moves, the incremental accumulator should always match one
+    // built from scratch and should end up back where it started after unmaking everything
+    #[test]
+    fn test_incremental_accumulator() {
+        let network = Arc::new(random_network(8));
+        let fens = [
+            // castling both ways
+            "r3k2r/pppq1ppp/2npbn2/2b1p3/2B1P3/2NPBN2/PPPQ1PPP/R3K2R w KQkq - 0 1",
+            // en passant
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+            // promotion with and without capture
+            "1r2k3/P7/8/8/8/8/6p1/4K2R b K - 0 1",
+        ];
+        for fen in fens.iter() {
+            let mut game_state = fen_reader::make_game_state(fen).unwrap();
+            let mut evaluator = NnueEvaluator::new(network.clone());
+            evaluator.prepare(&mut game_state);
+            let start = evaluator.accumulator().unwrap().clone();
+            let color = game_state.get_player_to_move();
+            for m in gen_legal_moves(&game_state, color).iter_mut() {
+                game_state.make_move_mut(m);
+                evaluator.make_move(&game_state, m);
+                let expected = Accumulator::new(network.clone(), &game_state);
+                assert_eq!(evaluator.accumulator(), Some(&expected), "{}", m);
+                for reply in gen_legal_moves(&game_state, color.opposite()).iter_mut() {
+                    game_state.make_move_mut(reply);
+                    evaluator.make_move(&game_state, reply);
+                    let expected = Accumulator::new(network.clone(), &game_state);
+                    assert_eq!(evaluator.accumulator(), Some(&expected));
+                    game_state.unmake_move_mut(reply);
+                    evaluator.unmake_move(&game_state, reply);
+                }
+                game_state.unmake_move_mut(m);
+                evaluator.unmake_move(&game_state, m);
+                assert_eq!(evaluator.accumulator(), Some(&start));
+            }
+        }
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let network = Arc::new(random_network(8));
+        let mut game_state = GameState::starting_game();
+        let mut evaluator = NnueEvaluator::new(network);
+        // without a search going it builds an accumulator just for the board it's given
+        let unprepared = evaluator.evaluate(&game_state).score;
+        evaluator.prepare(&mut game_state);
+        let white_to_move = evaluator.evaluate(&game_state).score;
+        assert_eq!(unprepared, white_to_move);
+        // the starting position looks the same to both players
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1";
+        let black_to_move = fen_reader::make_game_state(fen).unwrap();
+        let accumulator = evaluator.accumulator().unwrap();
+        assert_eq!(
+            evaluate(accumulator, black_to_move.get_player_to_move()),
+            -white_to_move
+        );
+    }
+}
@@ -0,0 +1,994 @@
+use crate::bit_board::BitBoard;
+use crate::board::*;
+use crate::game_state::GameState;
+use crate::move_generator::{
+    gen_legal_moves, gen_pseudo_legal_moves, generate_checks, Move, MoveType,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/*
+endgame tablebases for up to 4 pieces, kings included
+we can't download the syzygy / nalimov files so we build our own with retrograde analysis
+
+every table is one material signature named like the endgame keys, ex. KQK, KRKP, with white
+as the stronger side. positions where black is the stronger side are looked up with the colors
+swapped and the board flipped
+
+indexing
+    side to move, white king square, then 64 squares for every other piece
+the white king gets moved into a1-d1-d4 by mirroring / flipping the board, or just into files
+a-d when there are pawns since those can't be flipped top to bottom. indexes that aren't the
+canonical form of their position, or aren't a legal position, never get a value
+
+values, one byte per position, from the side to move's point of view
+    0       draw, or not a legal position
+    n > 0   mate in n - 1 plies, odd plies are wins for the side to move and even plies are
+            losses, so 1 is checkmated right now
+
+generating
+1. go over every position. checkmates are lost in 0 plies. moves that capture or promote leave
+   the table so their result gets looked up in the smaller tables, every other move gets counted
+2. resolve positions one ply at a time. a position that's lost in n makes everything that can
+   move into it won in n + 1. a position that's won in n takes one off the count of everything
+   that can move into it, once that's down to 0 every move loses so it's lost too
+3. anything that never got resolved is a draw
+
+the positions that can move into a position come from unmoving the side that just moved.
+pieces other than pawns move the same way backwards so those are the pseudo legal moves, pawns
+get stepped back by hand. the tables don't know about en passant or castling, so positions where
+either one can still happen aren't probed
+
+file format, <name>.tb
+    "CETB", u8 name length, name, one byte per index
+*/
+
+pub const MAX_PIECES: u64 = 4;
+const MAGIC: &[u8; 4] = b"CETB";
+// move counts for indexes that aren't legal positions
+const INVALID: u8 = u8::MAX;
+
+// non king pieces, same order as the endgame keys
+const PIECE_ORDER: [PieceType; 5] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Pawn,
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Wdl {
+    Win,
+    Draw,
+    Loss,
+}
+
+// from the side to move's point of view, dtm is plies until mate and 0 for draws
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TbResult {
+    pub wdl: Wdl,
+    pub dtm: u16,
+}
+
+impl TbResult {
+    fn from_value(value: u8) -> TbResult {
+        if value == 0 {
+            return TbResult {
+                wdl: Wdl::Draw,
+                dtm: 0,
+            };
+        }
+        let dtm = value as u16 - 1;
+        TbResult {
+            wdl: if dtm % 2 == 1 { Wdl::Win } else { Wdl::Loss },
+            dtm,
+        }
+    }
+
+    // the result for the player that moved into this position
+    fn before_move(self) -> TbResult {
+        match self.wdl {
+            Wdl::Draw => self,
+            Wdl::Win => TbResult {
+                wdl: Wdl::Loss,
+                dtm: self.dtm + 1,
+            },
+            Wdl::Loss => TbResult {
+                wdl: Wdl::Win,
+                dtm: self.dtm + 1,
+            },
+        }
+    }
+
+    // bigger is better, quick wins beat slow ones and slow losses beat quick ones
+    fn rank(&self) -> (u8, i32) {
+        match self.wdl {
+            Wdl::Win => (2, -(self.dtm as i32)),
+            Wdl::Draw => (1, 0),
+            Wdl::Loss => (0, self.dtm as i32),
+        }
+    }
+}
+
+fn file(square: u8) -> u8 {
+    square % 8
+}
+
+fn rank(square: u8) -> u8 {
+    square / 8
+}
+
+fn coordinate(square: u8) -> Coordinate {
+    Coordinate::new(file(square) + 1, rank(square) + 1)
+}
+
+fn square_of(coordinate: &Coordinate) -> u8 {
+    (coordinate.y() - 1) * 8 + coordinate.x() - 1
+}
+
+fn piece_value(piece_type: PieceType) -> u32 {
+    match piece_type {
+        PieceType::Queen => 9,
+        PieceType::Rook => 5,
+        PieceType::Bishop | PieceType::Knight => 3,
+        PieceType::Pawn => 1,
+        PieceType::King => 0,
+    }
+}
+
+fn type_board(board: &BitBoard, piece_type: PieceType) -> u64 {
+    match piece_type {
+        PieceType::King => board.get_kings_board(),
+        PieceType::Queen => board.get_queens_board(),
+        PieceType::Rook => board.get_rooks_board(),
+        PieceType::Bishop => board.get_bishops_board(),
+        PieceType::Knight => board.get_knights_board(),
+        PieceType::Pawn => board.get_pawns_board(),
+    }
+}
+
+fn color_board(board: &BitBoard, color: Color) -> u64 {
+    match color {
+        Color::White => board.get_white_pieces_board(),
+        Color::Black => board.get_black_pieces_board(),
+    }
+}
+
+// non king pieces of one side, in PIECE_ORDER
+fn side_pieces(board: &BitBoard, color: Color) -> Vec<PieceType> {
+    let mut pieces = vec![];
+    for &piece_type in PIECE_ORDER.iter() {
+        for _ in 0..board.get_piece_type_count(piece_type, color) {
+            pieces.push(piece_type);
+        }
+    }
+    pieces
+}
+
+fn side_name(pieces: &[PieceType]) -> String {
+    let mut name = String::from("K");
+    for piece_type in pieces {
+        name.push_str(piece_type.to().to_uppercase().as_str());
+    }
+    name
+}
+
+// name of the table for this material with the stronger side first, and whether the
+// stronger side is black so the board has to be flipped to look it up
+fn material_name(white: &[PieceType], black: &[PieceType]) -> (String, bool) {
+    let strength = |pieces: &[PieceType]| {
+        let counts: Vec<usize> = PIECE_ORDER
+            .iter()
+            .map(|p| pieces.iter().filter(|&q| q == p).count())
+            .collect();
+        (pieces.iter().map(|&p| piece_value(p)).sum::<u32>(), counts)
+    };
+    if strength(black) > strength(white) {
+        (format!("{}{}", side_name(black), side_name(white)), true)
+    } else {
+        (format!("{}{}", side_name(white), side_name(black)), false)
+    }
+}
+
+// "KRKP" => ([Rook], [Pawn])
+fn parse_name(name: &str) -> Result<(Vec<PieceType>, Vec<PieceType>), String> {
+    let name = name.trim().to_uppercase();
+    let second_king = name[1.min(name.len())..].find('K').map(|i| i + 1);
+    let second_king = match second_king {
+        Some(i) if name.starts_with('K') => i,
+        _ => return Err(format!("{} should look like KQK or KRKP", name)),
+    };
+    let side = |letters: &str| -> Result<Vec<PieceType>, String> {
+        let mut pieces = vec![];
+        for letter in letters.chars() {
+            match PieceType::from(letter.to_lowercase().to_string().as_str()) {
+                Some(PieceType::King) | None => {
+                    return Err(format!("{} isn't a piece in {}", letter, name))
+                }
+                Some(piece_type) => pieces.push(piece_type),
+            }
+        }
+        pieces.sort_by_key(|p| PIECE_ORDER.iter().position(|q| q == p));
+        Ok(pieces)
+    };
+    let white = side(&name[1..second_king])?;
+    let black = side(&name[second_king + 1..])?;
+    let total = 2 + white.len() + black.len();
+    if total < 3 || total > MAX_PIECES as usize {
+        return Err(format!(
+            "{} has {} pieces, tables are 3 to {} pieces",
+            name, total, MAX_PIECES
+        ));
+    }
+    Ok((white, black))
+}
+
+// every way one side's pieces can change, one of them captured or one pawn promoted
+fn fewer_or_promoted(pieces: &[PieceType]) -> Vec<Vec<PieceType>> {
+    let mut changed = vec![];
+    for (i, &piece_type) in pieces.iter().enumerate() {
+        let mut fewer = pieces.to_vec();
+        fewer.remove(i);
+        changed.push(fewer);
+        if piece_type == PieceType::Pawn {
+            for &promotion in PIECE_ORDER[..4].iter() {
+                let mut promoted = pieces.to_vec();
+                promoted[i] = promotion;
+                promoted.sort_by_key(|p| PIECE_ORDER.iter().position(|q| q == p));
+                changed.push(promoted);
+            }
+        }
+    }
+    changed
+}
+
+// the materials a table's captures and promotions lead to
+fn smaller_materials(
+    white: &[PieceType],
+    black: &[PieceType],
+) -> Vec<(Vec<PieceType>, Vec<PieceType>)> {
+    let mut materials: Vec<(Vec<PieceType>, Vec<PieceType>)> = fewer_or_promoted(white)
+        .into_iter()
+        .map(|white| (white, black.to_vec()))
+        .collect();
+    materials.extend(
+        fewer_or_promoted(black)
+            .into_iter()
+            .map(|black| (white.to_vec(), black)),
+    );
+    materials
+}
+
+pub struct Table {
+    name: String,
+    // kings first, then white's other pieces and then black's, each in PIECE_ORDER
+    pieces: Vec<(PieceType, Color)>,
+    // where the white king can be after the symmetries
+    king_squares: Vec<u8>,
+    values: Vec<u8>,
+}
+
+impl Table {
+    fn empty(name: &str) -> Result<Table, String> {
+        let (white, black) = parse_name(name)?;
+        let mut pieces = vec![
+            (PieceType::King, Color::White),
+            (PieceType::King, Color::Black),
+        ];
+        pieces.extend(white.iter().map(|&p| (p, Color::White)));
+        pieces.extend(black.iter().map(|&p| (p, Color::Black)));
+        let has_pawns = pieces.iter().any(|(p, _)| *p == PieceType::Pawn);
+        let king_squares: Vec<u8> = (0..64)
+            .filter(|&square| {
+                file(square) < 4
+                    && (has_pawns || (rank(square) < 4 && rank(square) <= file(square)))
+            })
+            .collect();
+        let size = 2 * king_squares.len() * 64usize.pow(pieces.len() as u32 - 1);
+        Ok(Table {
+            name: material_name(&white, &black).0,
+            pieces,
+            king_squares,
+            values: vec![0; size],
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    fn has_pawns(&self) -> bool {
+        self.pieces.iter().any(|(p, _)| *p == PieceType::Pawn)
+    }
+
+    // move the white king into king_squares and put pieces of the same kind in square order,
+    // so every position has one index
+    fn canonical(&self, squares: &mut [u8]) {
+        let transpose = |s: u8| file(s) * 8 + rank(s);
+        if file(squares[0]) > 3 {
+            squares.iter_mut().for_each(|s| *s ^= 7);
+        }
+        if !self.has_pawns() {
+            if rank(squares[0]) > 3 {
+                squares.iter_mut().for_each(|s| *s ^= 56);
+            }
+            if rank(squares[0]) > file(squares[0]) {
+                squares.iter_mut().for_each(|s| *s = transpose(*s));
+            }
+        }
+        self.sort_same_pieces(squares);
+        // a king on the diagonal can still be flipped across it, go with whichever comes first
+        if !self.has_pawns() && rank(squares[0]) == file(squares[0]) {
+            let mut flipped: Vec<u8> = squares.iter().map(|&s| transpose(s)).collect();
+            self.sort_same_pieces(&mut flipped);
+            if flipped.as_slice() < &*squares {
+                squares.copy_from_slice(&flipped);
+            }
+        }
+    }
+
+    fn sort_same_pieces(&self, squares: &mut [u8]) {
+        let mut start = 1;
+        while start < squares.len() {
+            let mut end = start + 1;
+            while end < squares.len() && self.pieces[end] == self.pieces[start] {
+                end += 1;
+            }
+            squares[start..end].sort_unstable();
+            start = end;
+        }
+    }
+
+    // squares have to be canonical
+    fn index(&self, squares: &[u8], side_to_move: Color) -> usize {
+        let king = self
+            .king_squares
+            .iter()
+            .position(|&s| s == squares[0])
+            .expect("white king isn't on a canonical square");
+        let side = match side_to_move {
+            Color::White => 0,
+            Color::Black => 1,
+        };
+        let mut index = side * self.king_squares.len() + king;
+        for &square in squares[1..].iter() {
+            index = index * 64 + square as usize;
+        }
+        index
+    }
+
+    fn decode(&self, mut index: usize) -> (Vec<u8>, Color) {
+        let mut squares = vec![0u8; self.pieces.len()];
+        for i in (1..squares.len()).rev() {
+            squares[i] = (index % 64) as u8;
+            index /= 64;
+        }
+        squares[0] = self.king_squares[index % self.king_squares.len()];
+        let side = if index / self.king_squares.len() == 0 {
+            Color::White
+        } else {
+            Color::Black
+        };
+        (squares, side)
+    }
+
+    // flipped reads black's pieces as white's on the mirrored board
+    fn squares_from_board(&self, board: &BitBoard, flipped: bool) -> Vec<u8> {
+        let mut taken = 0u64;
+        self.pieces
+            .iter()
+            .map(|&(piece_type, color)| {
+                let color = if flipped { color.opposite() } else { color };
+                let bits = type_board(board, piece_type) & color_board(board, color) & !taken;
+                let bit = bits & bits.wrapping_neg();
+                taken |= bit;
+                let square = bit.trailing_zeros() as u8;
+                if flipped {
+                    square ^ 56
+                } else {
+                    square
+                }
+            })
+            .collect()
+    }
+
+    fn game_state(&self, squares: &[u8], side_to_move: Color) -> GameState {
+        let mut board = BitBoard::init_from_pieces(vec![]);
+        for (&(piece_type, color), &square) in self.pieces.iter().zip(squares) {
+            board.set_piece(piece_type, color, coordinate(square));
+        }
+        GameState::make_game_state(side_to_move, false, false, false, false, None, 0, 1, board)
+    }
+
+    // None if the index is a position that can't happen or isn't the canonical one
+    fn legal_position(&self, index: usize) -> Option<(Vec<u8>, Color, GameState)> {
+        let (squares, side) = self.decode(index);
+        let mut occupied = 0u64;
+        for (&(piece_type, _), &square) in self.pieces.iter().zip(squares.iter()) {
+            if occupied & (1 << square) != 0 {
+                return None;
+            }
+            occupied |= 1 << square;
+            if piece_type == PieceType::Pawn && (rank(square) == 0 || rank(square) == 7) {
+                return None;
+            }
+        }
+        let mut canonical = squares.clone();
+        self.canonical(&mut canonical);
+        if self.index(&canonical, side) != index {
+            return None;
+        }
+        let (white_king, black_king) = (squares[0], squares[1]);
+        if file(white_king).abs_diff(file(black_king)) <= 1
+            && rank(white_king).abs_diff(rank(black_king)) <= 1
+        {
+            return None;
+        }
+        let game_state = self.game_state(&squares, side);
+        if !generate_checks(&game_state, side.opposite()).is_empty() {
+            return None;
+        }
+        Some((squares, side, game_state))
+    }
+
+    // index of the position after a move that stays in the table
+    fn index_after(&self, squares: &[u8], m: &Move, side_to_move: Color) -> usize {
+        let from = square_of(&m.from);
+        let mut after = squares.to_vec();
+        let moved = after.iter().position(|&s| s == from).unwrap();
+        after[moved] = square_of(&m.to);
+        self.canonical(&mut after);
+        self.index(&after, side_to_move.opposite())
+    }
+
+    // every index that has a move into this position without a capture or promotion
+    fn predecessors(
+        &self,
+        squares: &[u8],
+        side_to_move: Color,
+        game_state: &GameState,
+    ) -> Vec<usize> {
+        let moved = side_to_move.opposite();
+        let occupied: u64 = squares.iter().fold(0, |bits, &s| bits | 1 << s);
+        let mut before: Vec<Vec<u8>> = vec![];
+        for m in gen_pseudo_legal_moves(game_state, moved) {
+            if m.piece == PieceType::Pawn
+                || m.captured.is_some()
+                || m.move_type() != &MoveType::Move
+            {
+                continue;
+            }
+            let mut unmoved = squares.to_vec();
+            let i = unmoved
+                .iter()
+                .position(|&s| s == square_of(&m.from))
+                .unwrap();
+            unmoved[i] = square_of(&m.to);
+            before.push(unmoved);
+        }
+        for (i, &(piece_type, color)) in self.pieces.iter().enumerate() {
+            if piece_type != PieceType::Pawn || color != moved {
+                continue;
+            }
+            // which way is backwards and the rank it would've double pushed to
+            let (back, double_push_rank): (i8, u8) = match color {
+                Color::White => (-8, 3),
+                Color::Black => (8, 4),
+            };
+            let square = squares[i];
+            let one_back = (square as i8 + back) as u8;
+            if rank(one_back) == 0 || rank(one_back) == 7 || occupied & (1 << one_back) != 0 {
+                continue;
+            }
+            let mut unmoved = squares.to_vec();
+            unmoved[i] = one_back;
+            before.push(unmoved);
+            let two_back = (one_back as i8 + back) as u8;
+            if rank(square) == double_push_rank && occupied & (1 << two_back) == 0 {
+                let mut unmoved = squares.to_vec();
+                unmoved[i] = two_back;
+                before.push(unmoved);
+            }
+        }
+        let mut indexes: Vec<usize> = before
+            .into_iter()
+            .map(|mut unmoved| {
+                self.canonical(&mut unmoved);
+                self.index(&unmoved, moved)
+            })
+            .collect();
+        indexes.sort_unstable();
+        indexes.dedup();
+        indexes
+    }
+
+    // from the side to move's point of view, flipped if black is the stronger side on the board
+    fn probe_board(&self, board: &BitBoard, side_to_move: Color, flipped: bool) -> TbResult {
+        let mut squares = self.squares_from_board(board, flipped);
+        let side_to_move = if flipped {
+            side_to_move.opposite()
+        } else {
+            side_to_move
+        };
+        self.canonical(&mut squares);
+        TbResult::from_value(self.values[self.index(&squares, side_to_move)])
+    }
+
+    // the longest forced mate in the table, in plies from the winning side's move
+    pub fn longest_mate(&self) -> u16 {
+        self.values
+            .iter()
+            .map(|&value| TbResult::from_value(value))
+            .filter(|result| result.wdl == Wdl::Win)
+            .map(|result| result.dtm)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(self.name.len() as u8);
+        bytes.extend_from_slice(self.name.as_bytes());
+        bytes.extend_from_slice(&self.values);
+        bytes
+    }
+
+    pub fn read(bytes: &[u8]) -> Result<Table, String> {
+        if bytes.len() < 5 || &bytes[0..4] != MAGIC {
+            return Err(String::from("not a tablebase file"));
+        }
+        let name_end = 5 + bytes[4] as usize;
+        let name = bytes
+            .get(5..name_end)
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .ok_or_else(|| String::from("tablebase name is cut off"))?;
+        let mut table = Table::empty(name)?;
+        if bytes.len() - name_end != table.values.len() {
+            return Err(format!(
+                "{} should have {} positions, found {}",
+                table.name,
+                table.values.len(),
+                bytes.len() - name_end
+            ));
+        }
+        table.values.copy_from_slice(&bytes[name_end..]);
+        Ok(table)
+    }
+}
+
+#[derive(Default)]
+pub struct Tablebases {
+    tables: HashMap<String, Table>,
+}
+
+impl Tablebases {
+    pub fn new() -> Tablebases {
+        Tablebases::default()
+    }
+
+    // every .tb file in the directory
+    pub fn load_dir(dir: &Path) -> Result<Tablebases, String> {
+        let entries =
+            fs::read_dir(dir).map_err(|err| format!("couldn't read {}: {}", dir.display(), err))?;
+        let mut tablebases = Tablebases::new();
+        for entry in entries {
+            let path = entry.map_err(|err| err.to_string())?.path();
+            if path.extension().is_none_or(|e| e != "tb") {
+                continue;
+            }
+            let bytes = fs::read(&path)
+                .map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+            let table = Table::read(bytes.as_slice())
+                .map_err(|err| format!("{}: {}", path.display(), err))?;
+            tablebases.insert(table);
+        }
+        Ok(tablebases)
+    }
+
+    pub fn save_dir(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir)
+            .map_err(|err| format!("couldn't create {}: {}", dir.display(), err))?;
+        for table in self.tables.values() {
+            let path = dir.join(format!("{}.tb", table.name));
+            fs::write(&path, table.to_bytes())
+                .map_err(|err| format!("couldn't write {}: {}", path.display(), err))?;
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, table: Table) {
+        self.tables.insert(table.name.clone(), table);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Table> {
+        self.tables.get(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.tables.keys().map(|name| name.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    // None if there's no table for the position
+    pub fn probe(&self, game_state: &GameState) -> Option<TbResult> {
+        let board = game_state.get_board_ref();
+        let piece_count = board.get_piece_count();
+        if piece_count > MAX_PIECES
+            || game_state.get_castling_rights(Color::White).some()
+            || game_state.get_castling_rights(Color::Black).some()
+        {
+            return None;
+        }
+        let color = game_state.get_player_to_move();
+        if game_state.en_passant_target().is_some()
+            && gen_legal_moves(game_state, color)
+                .iter()
+                .any(|m| m.move_type() == &MoveType::EnPassant)
+        {
+            return None;
+        }
+        if piece_count == 2 {
+            return Some(TbResult {
+                wdl: Wdl::Draw,
+                dtm: 0,
+            });
+        }
+        let (name, flipped) = material_name(
+            &side_pieces(board, Color::White),
+            &side_pieces(board, Color::Black),
+        );
+        let table = self.tables.get(&name)?;
+        Some(table.probe_board(board, color, flipped))
+    }
+
+    // the move that keeps the best result, the quickest mate when winning and the longest
+    // one when losing
+    pub fn best_move(&self, game_state: &GameState) -> Option<(Move, TbResult)> {
+        self.probe(game_state)?;
+        let mut best: Option<(Move, TbResult)> = None;
+        for mut m in gen_legal_moves(game_state, game_state.get_player_to_move()) {
+            let mut after = game_state.clone_to_game_state();
+            after.make_move_mut(&mut m);
+            let result = self.probe(&after)?.before_move();
+            if best.is_none_or(|(_, best)| result.rank() > best.rank()) {
+                best = Some((m, result));
+            }
+        }
+        best
+    }
+
+    // builds the table and any smaller ones it needs that we don't have yet, the names of
+    // everything built get returned in the order they were built
+    pub fn generate(&mut self, name: &str) -> Result<Vec<String>, String> {
+        let (white, black) = parse_name(name)?;
+        self.generate_material(&white, &black)
+    }
+
+    fn generate_material(
+        &mut self,
+        white: &[PieceType],
+        black: &[PieceType],
+    ) -> Result<Vec<String>, String> {
+        let (name, _) = material_name(white, black);
+        if self.tables.contains_key(&name) {
+            return Ok(vec![]);
+        }
+        let mut generated = vec![];
+        for (white, black) in smaller_materials(white, black) {
+            if white.len() + black.len() > 0 {
+                generated.extend(self.generate_material(&white, &black)?);
+            }
+        }
+        let table = self.solve(Table::empty(name.as_str())?)?;
+        self.insert(table);
+        generated.push(name);
+        Ok(generated)
+    }
+
+    fn solve(&self, mut table: Table) -> Result<Table, String> {
+        let size = table.len();
+        // moves that stay in the table and aren't known to lose yet
+        let mut counts = vec![INVALID; size];
+        // what the moves out of the table do, a capture or promotion that wins or draws
+        let mut can_win = vec![false; size];
+        let mut can_draw = vec![false; size];
+        // the longest loss through a move out of the table
+        let mut longest_loss = vec![0u16; size];
+        // positions to resolve at each ply, even plies are losses and odd are wins
+        let mut plies: Vec<Vec<u32>> = vec![];
+        let push = |plies: &mut Vec<Vec<u32>>, ply: usize, index: usize| {
+            if plies.len() <= ply {
+                plies.resize(ply + 1, vec![]);
+            }
+            plies[ply].push(index as u32);
+        };
+
+        for index in 0..size {
+            let (squares, side, game_state) = match table.legal_position(index) {
+                Some(position) => position,
+                None => continue,
+            };
+            let moves = gen_legal_moves(&game_state, side);
+            if moves.is_empty() {
+                if generate_checks(&game_state, side).is_empty() {
+                    can_draw[index] = true;
+                } else {
+                    push(&mut plies, 0, index);
+                }
+            }
+            let mut in_table = vec![];
+            for mut m in moves.iter().copied() {
+                let promotes = matches!(m.move_type(), MoveType::Promotion(_));
+                if m.captured.is_none() && !promotes {
+                    in_table.push(table.index_after(&squares, &m, side));
+                    continue;
+                }
+                let mut after = game_state.clone_to_game_state();
+                after.make_move_mut(&mut m);
+                let result = self
+                    .probe(&after)
+                    .ok_or_else(|| format!("{} needs a table for {}", table.name, m))?
+                    .before_move();
+                match result.wdl {
+                    Wdl::Win => {
+                        can_win[index] = true;
+                        push(&mut plies, result.dtm as usize, index);
+                    }
+                    Wdl::Draw => can_draw[index] = true,
+                    Wdl::Loss => longest_loss[index] = longest_loss[index].max(result.dtm),
+                }
+            }
+            in_table.sort_unstable();
+            in_table.dedup();
+            counts[index] = in_table.len() as u8;
+            if in_table.is_empty() && !moves.is_empty() && !can_win[index] && !can_draw[index] {
+                push(&mut plies, longest_loss[index] as usize, index);
+            }
+        }
+
+        let mut ply = 0;
+        while ply < plies.len() {
+            let resolving = std::mem::take(&mut plies[ply]);
+            for index in resolving {
+                let index = index as usize;
+                if table.values[index] != 0 {
+                    continue;
+                }
+                if ply >= u8::MAX as usize {
+                    return Err(format!(
+                        "{} has a mate longer than {} plies",
+                        table.name, ply
+                    ));
+                }
+                table.values[index] = ply as u8 + 1;
+                let (squares, side) = table.decode(index);
+                let game_state = table.game_state(&squares, side);
+                for before in table.predecessors(&squares, side, &game_state) {
+                    if counts[before] == INVALID || table.values[before] != 0 {
+                        continue;
+                    }
+                    if ply % 2 == 0 {
+                        // lost here, so moving here wins
+                        push(&mut plies, ply + 1, before);
+                        continue;
+                    }
+                    counts[before] -= 1;
+                    if counts[before] == 0 && !can_win[before] && !can_draw[before] {
+                        let dtm = (ply + 1).max(longest_loss[before] as usize);
+                        push(&mut plies, dtm, before);
+                    }
+                }
+            }
+            ply += 1;
+        }
+        Ok(table)
+    }
+}
+
+// built once and shared by every test that needs them, KPK pulls in KQK, KRK, KBK and KNK
+#[cfg(test)]
+pub(crate) fn test_tablebases() -> std::sync::Arc<Tablebases> {
+    static TABLEBASES: std::sync::OnceLock<std::sync::Arc<Tablebases>> = std::sync::OnceLock::new();
+    TABLEBASES
+        .get_or_init(|| {
+            let mut tablebases = Tablebases::new();
+            tablebases.generate("KPK").unwrap();
+            std::sync::Arc::new(tablebases)
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_notation::fen_reader::make_game_state;
+
+    fn probe(fen: &str) -> TbResult {
+        test_tablebases()
+            .probe(&make_game_state(fen).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(
+            parse_name("krkp"),
+            Ok((vec![PieceType::Rook], vec![PieceType::Pawn]))
+        );
+        let (white, black) = parse_name("KPKR").unwrap();
+        assert_eq!(material_name(&white, &black), (String::from("KRKP"), true));
+        let (white, black) = parse_name("KKQ").unwrap();
+        assert_eq!(material_name(&white, &black), (String::from("KQK"), true));
+        let (white, black) = parse_name("KNKB").unwrap();
+        assert_eq!(material_name(&white, &black), (String::from("KBKN"), true));
+        assert!(parse_name("KQ").is_err());
+        assert!(parse_name("QKK").is_err());
+        assert!(parse_name("KK").is_err());
+        assert!(parse_name("KQRKR").is_err());
+        assert!(parse_name("KXK").is_err());
+    }
+
+    #[test]
+    fn test_longest_mates() {
+        let tablebases = test_tablebases();
+        assert_eq!(tablebases.names(), vec!["KBK", "KNK", "KPK", "KQK", "KRK"]);
+        // mate in 10 and mate in 16 moves
+        assert_eq!(tablebases.get("KQK").unwrap().longest_mate(), 19);
+        assert_eq!(tablebases.get("KRK").unwrap().longest_mate(), 31);
+        assert_eq!(tablebases.get("KBK").unwrap().longest_mate(), 0);
+        assert_eq!(tablebases.get("KNK").unwrap().longest_mate(), 0);
+    }
+
+    #[test]
+    fn test_probe() {
+        let mated = probe("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1");
+        assert_eq!(
+            mated,
+            TbResult {
+                wdl: Wdl::Loss,
+                dtm: 0
+            }
+        );
+        let mate_in_one = probe("k7/7Q/1K6/8/8/8/8/8 w - - 0 1");
+        assert_eq!(
+            mate_in_one,
+            TbResult {
+                wdl: Wdl::Win,
+                dtm: 1
+            }
+        );
+        // the queen hangs
+        assert_eq!(probe("8/8/8/8/8/1k6/2Q5/K7 b - - 0 1").wdl, Wdl::Draw);
+        // stalemate
+        assert_eq!(probe("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1").wdl, Wdl::Draw);
+
+        // king on the 6th in front of the pawn wins whoever is to move
+        assert_eq!(probe("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1").wdl, Wdl::Win);
+        assert_eq!(probe("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1").wdl, Wdl::Loss);
+        // same thing for black, looked up with the board flipped
+        assert_eq!(probe("8/8/8/8/4p3/4k3/8/4K3 b - - 0 1").wdl, Wdl::Win);
+        assert_eq!(probe("8/8/8/8/4p3/4k3/8/4K3 w - - 0 1").wdl, Wdl::Loss);
+        // the rook pawn can't get the king out of the corner
+        assert_eq!(probe("k7/8/K7/P7/8/8/8/8 w - - 0 1").wdl, Wdl::Draw);
+        // opposition decides it
+        assert_eq!(probe("4k3/8/8/4K3/4P3/8/8/8 w - - 0 1").wdl, Wdl::Win);
+        assert_eq!(probe("4k3/8/4P3/4K3/8/8/8/8 w - - 0 1").wdl, Wdl::Draw);
+
+        // too many pieces, no table, castling rights
+        let tablebases = test_tablebases();
+        assert!(tablebases.probe(&GameState::starting_game()).is_none());
+        assert!(tablebases
+            .probe(&make_game_state("k7/8/8/8/8/8/8/KQR5 w - - 0 1").unwrap())
+            .is_none());
+        assert!(tablebases
+            .probe(&make_game_state("k7/8/8/8/8/8/8/4K2R w K - 0 1").unwrap())
+            .is_none());
+
+        // exd6 en passant is there to be played, the table doesn't know that
+        let mut tablebases = Tablebases::new();
+        tablebases.insert(Table::empty("KPKP").unwrap());
+        let fen = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        assert!(tablebases.probe(&make_game_state(fen).unwrap()).is_none());
+        let fen = "4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1";
+        assert!(tablebases.probe(&make_game_state(fen).unwrap()).is_some());
+        // there's an en passant square but no pawn next to it to take
+        let fen = "4k3/8/8/3p4/8/8/4P3/4K3 w - d6 0 1";
+        assert!(tablebases.probe(&make_game_state(fen).unwrap()).is_some());
+    }
+
+    #[test]
+    fn test_best_move() {
+        let tablebases = test_tablebases();
+        let game_state = make_game_state("k7/7Q/1K6/8/8/8/8/8 w - - 0 1").unwrap();
+        let (m, result) = tablebases.best_move(&game_state).unwrap();
+        assert_eq!(
+            result,
+            TbResult {
+                wdl: Wdl::Win,
+                dtm: 1
+            }
+        );
+        let mut after = game_state.clone_to_game_state();
+        after.make_move_mut(&mut m.clone());
+        assert_eq!(
+            tablebases.probe(&after),
+            Some(TbResult {
+                wdl: Wdl::Loss,
+                dtm: 0
+            })
+        );
+        // a rook still wins, just slower than a queen
+        let game_state = make_game_state("8/4P3/8/8/8/8/k7/4K3 w - - 0 1").unwrap();
+        let promote = |piece_type: PieceType| {
+            let mut m = gen_legal_moves(&game_state, Color::White)
+                .into_iter()
+                .find(|m| m.move_type() == &MoveType::Promotion(piece_type))
+                .unwrap();
+            let mut after = game_state.clone_to_game_state();
+            after.make_move_mut(&mut m);
+            tablebases.probe(&after).unwrap().before_move()
+        };
+        let (queen, rook) = (promote(PieceType::Queen), promote(PieceType::Rook));
+        assert_eq!((queen.wdl, rook.wdl), (Wdl::Win, Wdl::Win));
+        assert!(queen.dtm < rook.dtm);
+        assert_eq!(promote(PieceType::Knight).wdl, Wdl::Draw);
+        let (_, result) = tablebases.best_move(&game_state).unwrap();
+        assert!(result.dtm <= queen.dtm);
+        // black is lost so it holds out as long as it can
+        let game_state = make_game_state("8/8/8/3k4/8/8/8/KQ6 b - - 0 1").unwrap();
+        let (m, result) = tablebases.best_move(&game_state).unwrap();
+        assert_eq!(result.wdl, Wdl::Loss);
+        let mut after = game_state.clone_to_game_state();
+        after.make_move_mut(&mut m.clone());
+        assert_eq!(tablebases.probe(&after).unwrap().dtm + 1, result.dtm);
+    }
+
+    // every position's value has to be what its best move leads to
+    fn check_table(name: &str, step: usize) {
+        let tablebases = test_tablebases();
+        let table = tablebases.get(name).unwrap();
+        for index in (0..table.len()).step_by(step) {
+            let (_, side, game_state) = match table.legal_position(index) {
+                Some(position) => position,
+                None => continue,
+            };
+            let result = TbResult::from_value(table.values[index]);
+            match tablebases.best_move(&game_state) {
+                Some((_, best)) => assert_eq!(best, result, "{} index {}", name, index),
+                None => {
+                    let mated = !generate_checks(&game_state, side).is_empty();
+                    assert_eq!(result.wdl, if mated { Wdl::Loss } else { Wdl::Draw });
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tables_are_consistent() {
+        check_table("KRK", 1);
+        check_table("KPK", 7);
+    }
+
+    #[test]
+    fn test_read_write() {
+        let tablebases = test_tablebases();
+        let table = tablebases.get("KQK").unwrap();
+        let bytes = table.to_bytes();
+        let read = Table::read(bytes.as_slice()).unwrap();
+        assert_eq!(read.name(), "KQK");
+        assert_eq!(read.values, table.values);
+        assert!(Table::read(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Table::read(b"nope").is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::ai::Ai;
+use crate::board::*;
+use crate::chess_notation::epd::Epd;
+use crate::chess_notation::{move_to_san, san};
+use crate::game_state::GameState;
+use crate::move_generator::Move;
+use std::fmt;
+use std::time::Duration;
+
+/*
+run the ai over a test suite of epd positions (wac, sts, ...) and see how many it gets right
+
+a position is solved when the ai plays one of the bm moves, stays away from the am moves, or
+for dm only positions finds a mate
+
+STS (strategic test suite) positions give partial credit, their c0 looks like
+    c0 "f5=10, Be5+=2, Bf2=3, Bg4=2"
+so the best move is worth 10 and some of the others get a few points. only a bm move solves
+one of these, the others just get their points. positions without the points get 10 for
+solving it and 0 otherwise
+*/
+
+const POINTS_PER_POSITION: u32 = 10;
+
+pub struct PositionResult {
+    pub id: String,
+    pub played: Option<String>,
+    pub expected: String,
+    pub solved: bool,
+    pub points: u32,
+    pub max_points: u32,
+    pub depth: u8,
+}
+
+impl fmt::Display for PositionResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} played {} expected {} depth {} points {}/{}",
+            self.id,
+            if self.solved { "solved" } else { "failed" },
+            self.played.as_deref().unwrap_or("nothing"),
+            self.expected,
+            self.depth,
+            self.points,
+            self.max_points
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct SuiteResult {
+    pub positions: Vec<PositionResult>,
+}
+
+impl SuiteResult {
+    pub fn solved(&self) -> usize {
+        self.positions.iter().filter(|p| p.solved).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.positions.len() - self.solved()
+    }
+
+    pub fn points(&self) -> u32 {
+        self.positions.iter().map(|p| p.points).sum()
+    }
+
+    pub fn max_points(&self) -> u32 {
+        self.positions.iter().map(|p| p.max_points).sum()
+    }
+}
+
+impl fmt::Display for SuiteResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |part: f32, total: f32| {
+            if total > 0.0 {
+                100.0 * part / total
+            } else {
+                0.0
+            }
+        };
+        writeln!(
+            f,
+            "solved {} / {} ({:.1}%), failed {}",
+            self.solved(),
+            self.positions.len(),
+            percent(self.solved() as f32, self.positions.len() as f32),
+            self.failed()
+        )?;
+        write!(
+            f,
+            "score {} / {} ({:.1}%)",
+            self.points(),
+            self.max_points(),
+            percent(self.points() as f32, self.max_points() as f32)
+        )
+    }
+}
+
+// "f5=10, Be5+=2, Bf2=3, Bg4=2" => [(f5, 10), (Be5, 2), ...], None if it isn't in that format
+pub fn sts_points(comment: &str, game_state: &GameState) -> Option<Vec<(Move, u32)>> {
+    comment
+        .split(',')
+        .map(|part| {
+            let (m, points) = part.trim().rsplit_once('=')?;
+            let m = san::parse_san(m, game_state).ok()?;
+            Some((m, points.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+// the ai searching this position has to be the side to move
+pub fn run_position(epd: &Epd, ai: &mut Ai, move_time: Duration) -> Result<PositionResult, String> {
+    let mut game_state = epd.game_state();
+    let best = epd.best_moves().map_err(|err| err.to_string())?;
+    let avoid = epd.avoid_moves().map_err(|err| err.to_string())?;
+    let mate_in = epd.direct_mate();
+    if best.is_empty() && avoid.is_empty() && mate_in.is_none() {
+        return Err(String::from("position has no bm, am or dm"));
+    }
+    let points = epd
+        .comment()
+        .and_then(|comment| sts_points(comment, &game_state));
+
+    let played = ai.make_move_with_time(&mut game_state, move_time);
+    let solved = match played {
+        None => false,
+        Some(played) if points.is_some() => best.iter().any(|m| m.same_move(&played)),
+        Some(played) => {
+            if !best.is_empty() || !avoid.is_empty() {
+                (best.is_empty() || best.iter().any(|m| m.same_move(&played)))
+                    && !avoid.iter().any(|m| m.same_move(&played))
+            } else {
+                let opponent = game_state.get_player_to_move().opposite();
+                ai.last_evaluation()
+                    .is_some_and(|eval| eval.mated_player == Some(opponent))
+            }
+        }
+    };
+    let (points, max_points) = match &points {
+        Some(points) => (
+            played
+                .and_then(|played| points.iter().find(|(m, _)| m.same_move(&played)))
+                .map_or(0, |(_, points)| *points),
+            points.iter().map(|(_, points)| *points).max().unwrap_or(0),
+        ),
+        None => (
+            if solved { POINTS_PER_POSITION } else { 0 },
+            POINTS_PER_POSITION,
+        ),
+    };
+
+    let mut expected = vec![];
+    for (opcode, moves) in [("bm", &best), ("am", &avoid)].iter() {
+        if !moves.is_empty() {
+            let moves: Vec<String> = moves.iter().map(|m| move_to_san(m, &game_state)).collect();
+            expected.push(format!("{} {}", opcode, moves.join(" ")));
+        }
+    }
+    if let Some(mate_in) = mate_in {
+        expected.push(format!("dm {}", mate_in));
+    }
+
+    Ok(PositionResult {
+        id: epd.id().map_or_else(|| epd.position.clone(), String::from),
+        played: played.map(|m| move_to_san(&m, &game_state)),
+        expected: expected.join(", "),
+        solved,
+        points,
+        max_points,
+        depth: ai.last_search_depth(),
+    })
+}
+
+// new_ai gets the color of the side to move in each position
+// report is called with each position as it finishes, positions that can't be run are skipped
+pub fn run_suite(
+    epds: &[Epd],
+    move_time: Duration,
+    new_ai: &dyn Fn(Color) -> Ai,
+    report: &mut dyn FnMut(usize, Result<&PositionResult, String>),
+) -> SuiteResult {
+    let mut result = SuiteResult::default();
+    for (i, epd) in epds.iter().enumerate() {
+        let side_to_move = epd.game_state().get_player_to_move();
+        let mut ai = new_ai(side_to_move);
+        match run_position(epd, &mut ai, move_time) {
+            Err(err) => report(i, Err(err)),
+            Ok(position) => {
+                report(i, Ok(&position));
+                result.positions.push(position);
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_notation::epd::{parse_epd, read_epd_file};
+
+    #[test]
+    fn test_sts_points() {
+        let epd = parse_epd(
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; c0 \"Qg6=10, Qh3=3, Kh1+=1\";",
+        )
+        .unwrap();
+        let game_state = epd.game_state();
+        // Kh1+ isn't even check but the suffix doesn't matter
+        let points = sts_points(epd.comment().unwrap(), &game_state).unwrap();
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[0].1, 10);
+        assert_eq!(points[2].0.to, Coordinate::new(8, 1));
+        assert!(sts_points("just a comment", &game_state).is_none());
+    }
+
+    #[test]
+    fn test_run_mate_in_one() {
+        let epds = read_epd_file(
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; id \"back rank\";\n\
+             6k1/5ppp/8/8/8/8/8/R5K1 w - - dm 1; id \"dm only\";\n\
+             6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra7; c0 \"Ra7=10, Ra8=2\"; id \"sts\";\n\
+             6k1/5ppp/8/8/8/8/8/R5K1 w - - id \"nothing to solve\";\n",
+        )
+        .unwrap();
+        let mut reported = vec![];
+        let result = run_suite(
+            &epds,
+            Duration::from_millis(100),
+            &|color| Ai::new(color),
+            &mut |i, position| reported.push((i, position.is_ok())),
+        );
+        assert_eq!(reported, vec![(0, true), (1, true), (2, true), (3, false)]);
+        assert_eq!(result.positions.len(), 3);
+        let solved: Vec<bool> = result.positions.iter().map(|p| p.solved).collect();
+        assert_eq!(solved, vec![true, true, false]);
+        assert_eq!(result.positions[0].played.as_deref(), Some("Ra8#"));
+        // mating is worth less than the bm here, it gets its points but doesn't solve it
+        assert_eq!(result.positions[2].points, 2);
+        assert_eq!(result.points(), 22);
+        assert_eq!(result.max_points(), 30);
+        assert_eq!(
+            result.to_string(),
+            "solved 2 / 3 (66.7%), failed 1\nscore 22 / 30 (73.3%)"
+        );
+    }
+}
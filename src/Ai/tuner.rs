@@ -0,0 +1,392 @@
+use crate::ai::evaluator::{evaluate_with_params, EvalParams};
+use crate::board::*;
+use crate::chess_notation::fen_reader::{self, FenError};
+use crate::chess_notation::pgn::result_from_str;
+use crate::chess_notation::pgn_reader::PgnReader;
+use crate::game_state::GameState;
+use crate::move_generator::{gen_legal_moves, Move};
+
+/*
+Texel tuning
+https://www.chessprogramming.org/Texel%27s_Tuning_Method
+
+take a bunch of positions from real games, label each with the result of the game
+(1.0 white won, 0.5 draw, 0.0 black won) and then nudge the eval params around until
+sigmoid(eval) is as close to the results as we can get it
+
+    E = 1/N * sum((R - sigmoid(q))^2)
+    sigmoid(s) = 1 / (1 + 10^(-K * s / 400))
+
+q is the quiescence score of the position, so we're not tuning on positions in the middle
+of a capture sequence
+*/
+
+// how deep the quiescence search goes before we just take the static eval
+const MAX_QUIESCENCE_DEPTH: u8 = 8;
+// skip the first few plies of every game, they're all book moves anyway
+const SKIP_OPENING_PLIES: usize = 8;
+
+pub struct TuningPosition {
+    pub fen: String,
+    pub result: f32,
+}
+
+pub fn sigmoid(score: f32, k: f32) -> f32 {
+    1.0 / (1.0 + 10f32.powf(-k * score / 400.0))
+}
+
+// read every game in the pgn text and label each position with the game result
+// games without a result and games that don't read are left out
+pub fn positions_from_pgn(pgn_text: &str) -> Vec<TuningPosition> {
+    let mut positions = vec![];
+    for game in PgnReader::new(pgn_text.as_bytes()) {
+        let game = match game.and_then(|game| game.parse()) {
+            Ok(game) => game,
+            Err(_) => continue,
+        };
+        let result = match result_from_str(game.result.as_str()) {
+            Some(result) => result,
+            None => continue,
+        };
+        // games from a [FEN] tag start there
+        let mut game_state = match game.starting_position() {
+            Ok(game_state) => game_state,
+            Err(_) => continue,
+        };
+        for (ply, mut m) in game.main_line().into_iter().enumerate() {
+            game_state.make_move_mut(&mut m);
+            if ply + 1 < SKIP_OPENING_PLIES {
+                continue;
+            }
+            positions.push(TuningPosition {
+                fen: fen_reader::make_fen(&game_state),
+                result,
+            });
+        }
+    }
+    positions
+}
+
+// one position per line, "<fen>;<result>"
+pub fn read_dataset(str: &str) -> Vec<TuningPosition> {
+    str.lines()
+        .filter_map(|line| {
+            let mut parts = line.split(';');
+            let fen = parts.next()?.trim();
+            let result = parts.next()?.trim().parse::<f32>().ok()?;
+            if fen.is_empty() {
+                return None;
+            }
+            Some(TuningPosition {
+                fen: String::from(fen),
+                result,
+            })
+        })
+        .collect()
+}
+
+pub fn write_dataset(positions: &[TuningPosition]) -> String {
+    positions
+        .iter()
+        .map(|p| format!("{};{}\n", p.fen, p.result))
+        .collect()
+}
+
+// capture only search, white maximizes and black minimizes just like alpha_beta in Ai
+// returns the score and the capture sequence that leads to it
+pub fn quiescence(
+    game_state: &mut GameState,
+    params: &EvalParams,
+    mut lower_bound: f32,
+    mut upper_bound: f32,
+    depth_to_go: u8,
+) -> (f32, Vec<Move>) {
+    let player_moving = game_state.get_player_to_move();
+    let stand_pat = evaluate_with_params(game_state, params).score;
+    if depth_to_go == 0 {
+        return (stand_pat, vec![]);
+    }
+    // the player moving can always decline to capture
+    match player_moving {
+        Color::White => {
+            if stand_pat >= upper_bound {
+                return (stand_pat, vec![]);
+            }
+            lower_bound = lower_bound.max(stand_pat);
+        }
+        Color::Black => {
+            if stand_pat <= lower_bound {
+                return (stand_pat, vec![]);
+            }
+            upper_bound = upper_bound.min(stand_pat);
+        }
+    }
+
+    let mut best = (stand_pat, vec![]);
+    let mut captures: Vec<Move> = gen_legal_moves(game_state, player_moving)
+        .into_iter()
+        .filter(|m| m.captured.is_some())
+        .collect();
+    for capture in captures.iter_mut() {
+        game_state.make_move_mut(capture);
+        let (score, mut line) = quiescence(
+            game_state,
+            params,
+            lower_bound,
+            upper_bound,
+            depth_to_go - 1,
+        );
+        game_state.unmake_move_mut(capture);
+
+        let improves = match player_moving {
+            Color::White => score > best.0,
+            Color::Black => score < best.0,
+        };
+        if improves {
+            line.insert(0, *capture);
+            best = (score, line);
+        }
+        match player_moving {
+            Color::White => {
+                if score >= upper_bound {
+                    return best;
+                }
+                lower_bound = lower_bound.max(score);
+            }
+            Color::Black => {
+                if score <= lower_bound {
+                    return best;
+                }
+                upper_bound = upper_bound.min(score);
+            }
+        }
+    }
+    best
+}
+
+// play out the capture sequence found by quiescence search so we're left with a quiet position
+// the static eval of the quiet position is the quiescence score, which means we don't have to
+// rerun the search every time a param changes
+pub fn resolve_position(fen: &str, params: &EvalParams) -> Result<GameState, FenError> {
+    let mut game_state = fen_reader::make_game_state(fen)?;
+    let (_score, mut line) = quiescence(
+        &mut game_state,
+        params,
+        f32::NEG_INFINITY,
+        f32::INFINITY,
+        MAX_QUIESCENCE_DEPTH,
+    );
+    for m in line.iter_mut() {
+        game_state.make_move_mut(m);
+    }
+    Ok(game_state)
+}
+
+pub fn mean_squared_error(positions: &[(GameState, f32)], params: &EvalParams, k: f32) -> f64 {
+    if positions.is_empty() {
+        return 0.0;
+    }
+    let total: f64 = positions
+        .iter()
+        .map(|(game_state, result)| {
+            let score = evaluate_with_params(game_state, params).score;
+            let error = (result - sigmoid(score, k)) as f64;
+            error * error
+        })
+        .sum();
+    total / positions.len() as f64
+}
+
+// the positions that read, resolved to quiet positions and paired with their results
+fn resolve_positions(positions: &[TuningPosition], params: &EvalParams) -> Vec<(GameState, f32)> {
+    positions
+        .iter()
+        .filter_map(|p| Some((resolve_position(p.fen.as_str(), params).ok()?, p.result)))
+        .collect()
+}
+
+// find the sigmoid scale that best fits the dataset with these params, so tuning starts from
+// an error that reflects how the eval predicts results and not how it happens to be scaled
+// line search around the best k so far, with a ten times finer step each pass
+pub fn fit_k(positions: &[TuningPosition], params: &EvalParams) -> f32 {
+    let resolved = resolve_positions(positions, params);
+    let mut best_k = 1.0f32;
+    let mut best_error = mean_squared_error(&resolved, params, best_k);
+    let mut step = 0.5f32;
+    for _ in 0..4 {
+        let center = best_k;
+        for i in -10..=10 {
+            let k = center + i as f32 * step;
+            if k <= 0.0 || i == 0 {
+                continue;
+            }
+            let error = mean_squared_error(&resolved, params, k);
+            if error < best_error {
+                best_error = error;
+                best_k = k;
+            }
+        }
+        step /= 10.0;
+    }
+    best_k
+}
+
+// local search, try moving each param up and down a step and keep whatever lowers the error
+// stops when a full pass over the params doesn't improve anything or we run out of iterations
+// report is called with the iteration and the error after it, 0 for the starting error
+pub fn tune(
+    positions: &[TuningPosition],
+    start: &EvalParams,
+    k: f32,
+    max_iterations: u32,
+    report: &mut dyn FnMut(u32, f64),
+) -> EvalParams {
+    // positions that don't read are left out
+    let resolved = resolve_positions(positions, start);
+
+    let mut values = start.values();
+    // bigger steps for the piece values, small steps for the positional terms
+    let steps: Vec<f32> = values.iter().map(|v| (v.abs() / 20.0).max(0.5)).collect();
+    let mut best_error = mean_squared_error(&resolved, start, k);
+    report(0, best_error);
+
+    for iteration in 0..max_iterations {
+        let mut improved = false;
+        for i in 0..values.len() {
+            for direction in [1.0f32, -1.0f32].iter() {
+                let mut candidate = values.clone();
+                candidate[i] += direction * steps[i];
+                let error = mean_squared_error(&resolved, &EvalParams::from_values(&candidate), k);
+                if error < best_error {
+                    best_error = error;
+                    values = candidate;
+                    improved = true;
+                    break;
+                }
+            }
+        }
+        report(iteration + 1, best_error);
+        if !improved {
+            break;
+        }
+    }
+    EvalParams::from_values(&values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sigmoid() {
+        assert_eq!(sigmoid(0.0, 1.0), 0.5);
+        assert!(sigmoid(400.0, 1.0) > 0.9);
+        assert!(sigmoid(-400.0, 1.0) < 0.1);
+    }
+
+    const TWO_GAMES: &str = r#"[Event "one"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. O-O Nf6 5. d3 O-O 6. Bg5 h6 7. Bh4 d6
+8. c3 Bg4 1-0
+
+[Event "two"]
+[Result "0-1"]
+
+1. d4 d5 2. c4 e6 3. Nc3 Nf6 4. Bg5 Be7 5. e3 O-O 0-1
+
+[Event "unfinished"]
+[Result "*"]
+
+1. e4 e5 *
+
+[Event "from a fen"]
+[Result "1/2-1/2"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+
+1. e4 Kd7 2. e5 Ke6 3. Ke2 Kxe5 4. Kf3 Kf5 5. Kg3 Kg5 1/2-1/2
+"#;
+
+    #[test]
+    fn test_positions_from_pgn() {
+        let positions = positions_from_pgn(TWO_GAMES);
+        // 16, 10 and 10 plies minus the opening, the unfinished game is skipped
+        let per_game = |plies: usize| plies - SKIP_OPENING_PLIES + 1;
+        assert_eq!(positions.len(), per_game(16) + per_game(10) * 2);
+        assert_eq!(positions[0].result, 1.0);
+        assert_eq!(positions[per_game(16)].result, 0.0);
+        // the last game is played out from its fen
+        let from_fen = &positions[per_game(16) + per_game(10)];
+        assert_eq!(from_fen.fen, "8/8/8/5k2/8/5K2/8/8 w - - 2 5");
+        assert_eq!(from_fen.result, 0.5);
+
+        let dataset = write_dataset(&positions);
+        let read = read_dataset(dataset.as_str());
+        assert_eq!(read.len(), positions.len());
+        assert_eq!(read[0].fen, positions[0].fen);
+    }
+
+    #[test]
+    fn test_quiescence_resolves_captures() {
+        // white queen can take an undefended rook
+        let fen = "4k3/8/8/3r4/8/8/3Q4/4K3 w - - 0 1";
+        let params = EvalParams::default();
+        let mut game_state = fen_reader::make_game_state(fen).unwrap();
+        let (score, line) = quiescence(
+            &mut game_state,
+            &params,
+            f32::NEG_INFINITY,
+            f32::INFINITY,
+            MAX_QUIESCENCE_DEPTH,
+        );
+        assert_eq!(line.len(), 1);
+        assert_eq!(line[0].captured, Some(PieceType::Rook));
+        assert!(score > 0.0);
+    }
+
+    #[test]
+    fn test_tune_lowers_error() {
+        let positions = positions_from_pgn(TWO_GAMES);
+        let start = EvalParams::default();
+        let mut errors = vec![];
+        let tuned = tune(&positions, &start, 1.0, 3, &mut |iteration, error| {
+            errors.push((iteration, error))
+        });
+        assert_eq!(errors[0].0, 0);
+        assert!(errors.len() >= 2 && errors.len() <= 4);
+        assert!(errors.windows(2).all(|pair| pair[1].1 <= pair[0].1));
+        let resolved: Vec<(GameState, f32)> = positions
+            .iter()
+            .map(|p| (resolve_position(p.fen.as_str(), &start).unwrap(), p.result))
+            .collect();
+        assert!(
+            mean_squared_error(&resolved, &tuned, 1.0)
+                <= mean_squared_error(&resolved, &start, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_fit_k() {
+        let positions = positions_from_pgn(TWO_GAMES);
+        let params = EvalParams::default();
+        let k = fit_k(&positions, &params);
+        assert!(k > 0.0);
+        let resolved = resolve_positions(&positions, &params);
+        assert!(
+            mean_squared_error(&resolved, &params, k)
+                <= mean_squared_error(&resolved, &params, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_params_round_trip() {
+        let params = EvalParams {
+            knight: 310.5,
+            ..EvalParams::default()
+        };
+        let read = EvalParams::read(params.to_string().as_str()).unwrap();
+        assert_eq!(read, params);
+        assert!(EvalParams::read("not_a_param 1").is_err());
+    }
+}
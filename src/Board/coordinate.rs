@@ -70,24 +70,21 @@ impl Coordinate {
         str
     }
 
-    pub fn from(str: &str) -> Coordinate {
-        if str.len() < 2 {
-            panic!("{} is not a valid coordinate", str);
-        }
+    // "e4" => (5, 4), None if it isn't a square on the board
+    pub fn from(str: &str) -> Option<Coordinate> {
         let c: Vec<char> = str.chars().collect();
-        let x = match c.get(0).unwrap() {
-            'a' => 1,
-            'b' => 2,
-            'c' => 3,
-            'd' => 4,
-            'e' => 5,
-            'f' => 6,
-            'g' => 7,
-            'h' => 8,
-            _ => panic!("{} not valid coordinate", str),
+        if c.len() != 2 {
+            return None;
+        }
+        let x = match c[0] {
+            'a'..='h' => c[0] as u8 - b'a' + 1,
+            _ => return None,
+        };
+        let y = match c[1] {
+            '1'..='8' => c[1] as u8 - b'0',
+            _ => return None,
         };
-        let y = c.get(1).unwrap().to_string().parse::<u8>().unwrap();
-        Coordinate::new(x, y)
+        Some(Coordinate::new(x, y))
     }
 }
 
@@ -103,13 +100,17 @@ mod tests {
 
     #[test]
     fn from_coordinate_test() {
-        assert_eq!(Coordinate::from("a1"), Coordinate::new(1, 1));
-        assert_eq!(Coordinate::from("h3"), Coordinate::new(8, 3));
-        assert_eq!(Coordinate::from("b7"), Coordinate::new(2, 7));
-        assert_eq!(Coordinate::from("d5"), Coordinate::new(4, 5));
-        assert_eq!(Coordinate::from("a8"), Coordinate::new(1, 8));
-        assert_eq!(Coordinate::from("e4"), Coordinate::new(5, 4));
-        assert_eq!(Coordinate::from("e5"), Coordinate::new(5, 5));
+        assert_eq!(Coordinate::from("a1"), Some(Coordinate::new(1, 1)));
+        assert_eq!(Coordinate::from("h3"), Some(Coordinate::new(8, 3)));
+        assert_eq!(Coordinate::from("b7"), Some(Coordinate::new(2, 7)));
+        assert_eq!(Coordinate::from("d5"), Some(Coordinate::new(4, 5)));
+        assert_eq!(Coordinate::from("a8"), Some(Coordinate::new(1, 8)));
+        assert_eq!(Coordinate::from("e4"), Some(Coordinate::new(5, 4)));
+        assert_eq!(Coordinate::from("e5"), Some(Coordinate::new(5, 5)));
+        assert_eq!(Coordinate::from("i1"), None);
+        assert_eq!(Coordinate::from("a9"), None);
+        assert_eq!(Coordinate::from("a"), None);
+        assert_eq!(Coordinate::from("a10"), None);
     }
 
     #[test]
@@ -1,90 +1,274 @@
 use crate::bit_board::BitBoard;
-use crate::board::{BoardTrait, Color, Coordinate, PieceType};
-use matrix_display::Format;
-use matrix_display::*;
+use crate::board::{BoardTrait, Color, Coordinate, Piece, PieceType};
+use console::Style;
 
-//@note : something about the square colors is backwards here btw..
-//@bug : to fix
-pub fn print_board(board: &dyn BoardTrait) {
-    let mut board_cells = vec![];
-    for row_idx in (1..=8).rev() {
-        for col_idx in (1..=8) {
-            let c = Coordinate::new(col_idx, row_idx);
-            let piece_opt = board.get_piece_at(&c);
-            let color = BitBoard::get_square_color_at(c);
-            // ansi 8 bit color scheme
-            let mut foreground = 0;
-            let mut value = ' ';
-
-            // if there's a piece
-            if piece_opt.is_some() {
-                let piece = piece_opt.unwrap();
-                foreground = match piece.color {
-                    Color::Black => 1, // red
-                    Color::White => 5, // purple
-                };
-                value = match piece.piece_type {
-                    PieceType::King => 'K',
-                    PieceType::Queen => 'Q',
-                    PieceType::Bishop => 'B',
-                    PieceType::Knight => 'N',
-                    PieceType::Rook => 'R',
-                    PieceType::Pawn => 'P',
-                }
-            }
-            let ansi_bg = match color {
-                Color::White => 0,
-                Color::Black => 7,
+/*
+drawing boards as text
+
+    BoardRenderer::new()
+        .with_unicode(true)              figurines instead of letters
+        .with_orientation(Color::Black)  black's side at the bottom
+        .with_labels(false)              leave off the rank numbers and file letters
+        .with_highlights(vec![e2, e4])   yellow squares, for the last move
+        .with_check(Some(e8))            a red square, for the king in check
+        .with_ansi(false)                no escape codes, for logs and tests
+
+without ansi there's no colour, so empty squares are dots, black's pieces are lower case
+(or black figurines), highlighted squares are in [ ] and the king in check is in ( )
+*/
+
+#[derive(Debug, Clone)]
+pub struct BoardRenderer {
+    unicode: bool,
+    orientation: Color,
+    labels: bool,
+    highlights: Vec<Coordinate>,
+    check: Option<Coordinate>,
+    ansi: bool,
+}
+
+impl Default for BoardRenderer {
+    fn default() -> Self {
+        BoardRenderer::new()
+    }
+}
+
+impl BoardRenderer {
+    // letters, white at the bottom, labels on, colours when stdout is a terminal
+    pub fn new() -> BoardRenderer {
+        BoardRenderer {
+            unicode: false,
+            orientation: Color::White,
+            labels: true,
+            highlights: vec![],
+            check: None,
+            ansi: console::colors_enabled(),
+        }
+    }
+
+    pub fn with_unicode(mut self, unicode: bool) -> BoardRenderer {
+        self.unicode = unicode;
+        self
+    }
+
+    // the side at the bottom
+    pub fn with_orientation(mut self, bottom: Color) -> BoardRenderer {
+        self.orientation = bottom;
+        self
+    }
+
+    pub fn with_labels(mut self, labels: bool) -> BoardRenderer {
+        self.labels = labels;
+        self
+    }
+
+    pub fn with_highlights(mut self, highlights: Vec<Coordinate>) -> BoardRenderer {
+        self.highlights = highlights;
+        self
+    }
+
+    pub fn with_check(mut self, check: Option<Coordinate>) -> BoardRenderer {
+        self.check = check;
+        self
+    }
+
+    pub fn with_ansi(mut self, ansi: bool) -> BoardRenderer {
+        self.ansi = ansi;
+        self
+    }
+
+    pub fn orientation(&self) -> Color {
+        self.orientation
+    }
+
+    // the rank at the top of the board goes first, then the file labels if there are any
+    pub fn lines(&self, board: &dyn BoardTrait) -> Vec<String> {
+        self.lines_of(|at| board.get_piece_at(at).copied())
+    }
+
+    pub fn bit_board_lines(&self, bit_board: &BitBoard) -> Vec<String> {
+        self.lines_of(|at| bit_board.get_piece_at(at))
+    }
+
+    pub fn render(&self, board: &dyn BoardTrait) -> String {
+        self.lines(board).join("\n")
+    }
+
+    pub fn print(&self, board: &dyn BoardTrait) {
+        println!("{}", self.render(board));
+    }
+
+    fn lines_of(&self, piece_at: impl Fn(&Coordinate) -> Option<Piece>) -> Vec<String> {
+        let mut lines = vec![];
+        for row in 0..8 {
+            let y = match self.orientation {
+                Color::White => 8 - row,
+                Color::Black => row + 1,
+            };
+            let mut line = if self.labels {
+                format!(" {} ", y)
+            } else {
+                String::new()
             };
-            board_cells.push(cell::Cell::new(value, foreground, ansi_bg));
+            for column in 0..8 {
+                let at = Coordinate::new(self.file(column), y);
+                line.push_str(self.square(at, piece_at(&at)).as_str());
+            }
+            lines.push(line);
+        }
+        if self.labels {
+            let files: String = (0..8)
+                .map(|column| format!(" {} ", (b'a' + self.file(column) - 1) as char))
+                .collect();
+            lines.push(format!("   {}", files.trim_end()));
         }
+        lines
     }
-    let format = Format::new(7, 3);
-    let mut data = matrix::Matrix::new(8, board_cells);
-    let display = MatrixDisplay::new(&format, &mut data);
-    display.print(&mut std::io::stdout(), &style::BordersStyle::None);
-}
 
-pub fn print_bit_board(bit_board: &BitBoard) {
-    let mut board_cells = vec![];
-    // loop over rows in reverse 
-    for row_idx in (1..=8).rev() {
-        for col_idx in (1..=8) {
-            let c = Coordinate::new(col_idx, row_idx);
-            // ansi 8 bit color scheme
-            let mut foreground = 0;
-            let mut value = ' ';
-            // get square color 
-            let ansi_bg = match BitBoard::get_square_color_at(c) {
-                Color::White => 7,
-                Color::Black => 0,
+    // the file shown in this column, counting from the left
+    fn file(&self, column: u8) -> u8 {
+        match self.orientation {
+            Color::White => column + 1,
+            Color::Black => 8 - column,
+        }
+    }
+
+    fn symbol(&self, piece: &Piece) -> char {
+        if self.unicode {
+            return match (piece.color, piece.piece_type) {
+                (Color::White, PieceType::King) => '♔',
+                (Color::White, PieceType::Queen) => '♕',
+                (Color::White, PieceType::Rook) => '♖',
+                (Color::White, PieceType::Bishop) => '♗',
+                (Color::White, PieceType::Knight) => '♘',
+                (Color::White, PieceType::Pawn) => '♙',
+                (Color::Black, PieceType::King) => '♚',
+                (Color::Black, PieceType::Queen) => '♛',
+                (Color::Black, PieceType::Rook) => '♜',
+                (Color::Black, PieceType::Bishop) => '♝',
+                (Color::Black, PieceType::Knight) => '♞',
+                (Color::Black, PieceType::Pawn) => '♟',
             };
+        }
+        let letter = match piece.piece_type {
+            PieceType::King => 'K',
+            PieceType::Queen => 'Q',
+            PieceType::Bishop => 'B',
+            PieceType::Knight => 'N',
+            PieceType::Rook => 'R',
+            PieceType::Pawn => 'P',
+        };
+        match piece.color {
+            Color::White => letter,
+            Color::Black => letter.to_ascii_lowercase(),
+        }
+    }
 
-            let piece_opt = bit_board.get_piece_at(&c);
-            if piece_opt.is_some() {
-                let piece = piece_opt.unwrap();
-                foreground = match piece.color {
-                    Color::Black => 1, // red
-                    Color::White => 5, // purple
+    // three characters wide, the piece in the middle
+    fn square(&self, at: Coordinate, piece: Option<Piece>) -> String {
+        let checked = self.check == Some(at);
+        let highlighted = self.highlights.contains(&at);
+        if !self.ansi {
+            let symbol = piece.map_or('.', |piece| self.symbol(&piece));
+            return if checked {
+                format!("({})", symbol)
+            } else if highlighted {
+                format!("[{}]", symbol)
+            } else {
+                format!(" {} ", symbol)
+            };
+        }
+        let style = if checked {
+            Style::new().on_red()
+        } else if highlighted {
+            Style::new().on_yellow()
+        } else {
+            match BitBoard::get_square_color_at(at) {
+                Color::White => Style::new().on_white(),
+                Color::Black => Style::new().on_green(),
+            }
+        };
+        let cell = match piece {
+            Some(piece) => {
+                let style = match piece.color {
+                    Color::White => style.magenta().bold(),
+                    Color::Black => style.red().bold(),
                 };
-                value = match piece.piece_type {
-                    PieceType::King => 'K',
-                    PieceType::Queen => 'Q',
-                    PieceType::Bishop => 'B',
-                    PieceType::Knight => 'N',
-                    PieceType::Rook => 'R',
-                    PieceType::Pawn => 'P',
-                }
+                style
+                    .force_styling(true)
+                    .apply_to(format!(" {} ", self.symbol(&piece)))
             }
+            None => style.force_styling(true).apply_to(String::from("   ")),
+        };
+        cell.to_string()
+    }
+}
 
+pub fn print_board(board: &dyn BoardTrait) {
+    BoardRenderer::new().print(board);
+}
 
-            board_cells.push(cell::Cell::new(value, foreground, ansi_bg));
-        }
+pub fn print_bit_board(bit_board: &BitBoard) {
+    println!(
+        "{}",
+        BoardRenderer::new().bit_board_lines(bit_board).join("\n")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_notation::fen_reader;
+    use console::strip_ansi_codes;
+
+    #[test]
+    fn test_lines() {
+        let board = fen_reader::make_initial_board();
+        let lines = BoardRenderer::new().with_ansi(false).lines(&board);
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], " 8  r  n  b  q  k  b  n  r ");
+        assert_eq!(lines[4], " 4  .  .  .  .  .  .  .  . ");
+        assert_eq!(lines[7], " 1  R  N  B  Q  K  B  N  R ");
+        assert_eq!(lines[8], "    a  b  c  d  e  f  g  h");
+
+        let lines = BoardRenderer::new()
+            .with_ansi(false)
+            .with_orientation(Color::Black)
+            .with_labels(false)
+            .with_unicode(true)
+            .lines(&board);
+        assert_eq!(lines.len(), 8);
+        assert_eq!(lines[0], " ♖  ♘  ♗  ♔  ♕  ♗  ♘  ♖ ");
+        assert_eq!(lines[7], " ♜  ♞  ♝  ♚  ♛  ♝  ♞  ♜ ");
     }
 
+    #[test]
+    fn test_highlights() {
+        let board = fen_reader::make_game_state(
+            "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+        )
+        .unwrap();
+        let lines = BoardRenderer::new()
+            .with_ansi(false)
+            .with_highlights(vec![Coordinate::new(4, 8), Coordinate::new(8, 4)])
+            .with_check(Some(Coordinate::new(5, 1)))
+            .lines(&board);
+        assert_eq!(lines[0], " 8  r  n  b [.] k  b  n  r ");
+        assert_eq!(lines[4], " 4  .  .  .  .  .  .  P [q]");
+        assert_eq!(lines[7], " 1  R  N  B  Q (K) B  N  R ");
 
-    let format = Format::new(7, 3);
-    let mut data = matrix::Matrix::new(8, board_cells);
-    let display = MatrixDisplay::new(&format, &mut data);
-    display.print(&mut std::io::stdout(), &style::BordersStyle::None);
+        // a1 is a dark square and h1 a light one
+        let renderer = BoardRenderer::new().with_ansi(true);
+        assert!(renderer
+            .square(Coordinate::new(1, 1), None)
+            .contains("\u{1b}[42m"));
+        assert!(renderer
+            .square(Coordinate::new(8, 1), None)
+            .contains("\u{1b}[47m"));
+        let lines = renderer.with_orientation(Color::Black).lines(&board);
+        assert_eq!(
+            strip_ansi_codes(lines[0].as_str()),
+            " 1  R  N  B  K  Q  B  N  R "
+        );
+    }
 }
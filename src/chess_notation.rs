@@ -1,7 +1,9 @@
+pub mod epd;
 pub mod fen_reader;
 pub mod pgn;
-
-use regex::Regex;
+pub mod pgn_parser;
+pub mod pgn_reader;
+pub mod san;
 
 use crate::board::*;
 use crate::chess_notation::fen_reader::make_fen;
@@ -26,6 +28,7 @@ identify the piece—which occurs only in rare cases where a player has three or
 pieces able to reach the same square, as a result of one or more pawns having promoted).
 **/
 
+// + and # come from the move's is_check and is_checkmate, see set_check_flags
 pub fn print_move(m: &Move, board: &GameState) -> String {
     if m.is_king_side_castle() {
         return String::from("O-O"); // O not 0
@@ -34,7 +37,12 @@ pub fn print_move(m: &Move, board: &GameState) -> String {
         return String::from("O-O-O"); // O not 0
     }
 
-    let piece_specifier = get_piece_specifier(m, board);
+    // pawns already say what file they're coming from when they capture
+    let piece_specifier = if m.piece == PieceType::Pawn {
+        String::new()
+    } else {
+        get_piece_specifier(m, board)
+    };
 
     // =Q or =B etc
     let mut pawn_promotion = String::new();
@@ -42,13 +50,13 @@ pub fn print_move(m: &Move, board: &GameState) -> String {
         pawn_promotion = format!("={}", promoted_to.to().to_uppercase());
     }
 
-    let mut check = "";
-    if m.is_check {
-        check = "+";
-    }
-    if m.is_checkmate {
-        check = "#";
-    }
+    let check = if m.is_checkmate {
+        "#"
+    } else if m.is_check {
+        "+"
+    } else {
+        ""
+    };
 
     let piece = if m.piece == PieceType::Pawn {
         if m.captured.is_none() {
@@ -68,15 +76,55 @@ pub fn print_move(m: &Move, board: &GameState) -> String {
     )
 }
 
-// change this to result error ?
+// play the move on a copy of the board to see if it checks or mates the other side
+// returns (is_check, is_checkmate)
+pub fn find_check_flags(m: &Move, board: &GameState) -> (bool, bool) {
+    let mover = match board.get_piece_at(&m.from) {
+        Some(piece) => piece.color,
+        None => board.get_player_to_move(),
+    };
+    let opponent = mover.opposite();
+    let mut after = board.clone_to_game_state();
+    let mut m = *m;
+    after.make_move_mut(&mut m);
+    if after.get_king(opponent).is_none() {
+        return (false, false);
+    }
+    let is_check = !generate_checks(&after, opponent).is_empty();
+    let is_checkmate = is_check && gen_legal_moves(&after, opponent).is_empty();
+    (is_check, is_checkmate)
+}
+
+// fills in is_check and is_checkmate, do this before the move is made on the board
+pub fn set_check_flags(m: &mut Move, board: &GameState) {
+    let (is_check, is_checkmate) = find_check_flags(m, board);
+    m.is_check = is_check;
+    m.is_checkmate = is_checkmate;
+}
+
+// the move in san with its + or #, before it's made on the board
+pub fn move_to_san(m: &Move, board: &GameState) -> String {
+    let mut m = *m;
+    set_check_flags(&mut m, board);
+    print_move(&m, board)
+}
+
+// "12" is white's 12th move and "12..." black's
+pub fn parse_move_number(text: &str) -> Result<(u16, Color), String> {
+    let (number, color) = match text.strip_suffix("...") {
+        Some(number) => (number, Color::Black),
+        None => (text.trim_end_matches('.'), Color::White),
+    };
+    match number.parse::<u16>() {
+        Ok(number) if number > 0 => Ok((number, color)),
+        _ => Err(format!("{} isn't a move number, try 12 or 12...", text)),
+    }
+}
+
 // doesn't return illegal moves, return None if not possible
+// use san::parse_san_for if you want to know why it didn't work
 pub fn parse_move(str: &str, board: &GameState, color: Color) -> Option<Move> {
-    // find what piece they're talking about by looking through the possible moves
-    println!("{}", str);
-    let their_move = String::from(str);
-    gen_legal_moves(board, color)
-        .into_iter()
-        .find(|m| their_move == print_move(m, board))
+    san::parse_san_for(str, board, color).ok()
 }
 
 // for a given move and board state, return the piece identifier 
@@ -141,112 +189,6 @@ fn get_piece_specifier(m: &Move, board: &GameState) -> String {
     piece_specifier
 }
 
-// algebraic moves and move generator moves are different because they're board dependent
-// bools are for short and long castles
-#[derive(Debug, Eq, PartialEq)]
-pub enum ParsedMoveType {
-    Move,
-    ShortCastles,
-    LongCastles,
-    Promotion,
-}
-
-fn read_piece(str: &str) -> Option<PieceType> {
-    let piece_identifier_matcher = Regex::new(r"^\s?[R,K,Q,N,B]").unwrap();
-    if piece_identifier_matcher.is_match(str) {
-        let _p = piece_identifier_matcher.find(str).unwrap().as_str();
-        let mut piece = PieceType::Rook;
-        if _p == "K" {
-            piece = PieceType::King;
-        } else if _p == "Q" {
-            piece = PieceType::Queen;
-        } else if _p == "N" {
-            piece = PieceType::Knight;
-        } else if _p == "B" {
-            piece = PieceType::Bishop;
-        }
-        return Some(piece);
-    } else {
-        return None;
-    }
-}
-
-// @todo : row and column specifiers
-pub fn read_move(
-    str: &str,
-) -> Option<(
-    PieceType,
-    Option<Coordinate>,
-    ParsedMoveType,
-    Option<PieceType>,
-)> {
-    // println!("reading {}", str);
-    // is $1, $2 blah whatevers
-    let crap_matcher = Regex::new(r"\$\d").unwrap();
-    if crap_matcher.is_match(str) {
-        println!("found crap");
-        return None;
-    }
-    //is castles ?
-    let long_castles_matcher = Regex::new(r"^\s?O\-O\-O").unwrap();
-    if long_castles_matcher.is_match(str) {
-        return Some((PieceType::King, None, ParsedMoveType::LongCastles, None));
-    }
-    let short_castles_matcher = Regex::new(r"^\s?O\-O").unwrap();
-    if short_castles_matcher.is_match(str) {
-        return Some((PieceType::King, None, ParsedMoveType::ShortCastles, None));
-    }
-
-    // pawn promotion todo::
-    let end_with_piece_matcher = Regex::new(r"^\s[R,K,Q,N,B]$").unwrap();
-    let mut pawn_promotion: Option<PieceType> = None;
-    if end_with_piece_matcher.is_match(str) {
-        pawn_promotion = read_piece(str);
-    }
-    let is_capture_specifier = Regex::new(r"[x]").unwrap();
-    let piece_identifier_matcher = Regex::new(r"^\s?[R,K,Q,N,B]").unwrap();
-    let coordinate_matcher = Regex::new(r"[a-h][1-8]").unwrap();
-    let is_only_coordinate_matcher = Regex::new(r"^\s?[a-h][1-8]\s?$").unwrap();
-
-    // pawn captures , no promotion
-    if is_capture_specifier.is_match(str) && !piece_identifier_matcher.is_match(str) {
-        let c = Coordinate::from(coordinate_matcher.find(str).unwrap().as_str());
-        return Some((
-            PieceType::Pawn,
-            Some(c),
-            ParsedMoveType::Move,
-            pawn_promotion,
-        ));
-    }
-
-    // regular non-capture pawn moves
-    if is_only_coordinate_matcher.is_match(str) {
-        let c = Coordinate::from(str);
-        return Some((PieceType::Pawn, Some(c), ParsedMoveType::Move, None));
-    }
-
-    // if coordinate_matcher.is_match(str) {
-    //     let c = coordinate_matcher.find(str).unwrap().as_str();
-    // }
-
-    // can't be a pawn move , can have column & row specified & will have a target square
-    if piece_identifier_matcher.is_match(str) {
-        let piece = read_piece(str).unwrap();
-        let c = Coordinate::from(coordinate_matcher.find(str).unwrap().as_str());
-        return Some((piece, Some(c), ParsedMoveType::Move, None));
-    }
-    // "fxe8N"
-    // don't forget pawn capture specifiers
-
-    //remove any captures
-    //remove any checks or checkmates
-    //is just coordinate ?
-    // else
-    //is piece identifier
-    //is row / column included
-    return None;
-}
-
 #[cfg(test)]
 mod print_move_test {
     use super::*;
@@ -287,7 +229,7 @@ mod print_move_test {
 
         // file specified
         let fen = "rnbqkbnr/1ppppppp/8/8/8/p1N1N3/PPPPPPPP/R1BQKB1R b KQkq - 1 5";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let m = Move::new(
             Coordinate::new(3, 3),
             Coordinate::new(4, 5),
@@ -322,7 +264,7 @@ mod print_move_test {
 
         //rank specified
         let fen = "rnbqkbnr/1ppppppp/8/3N4/8/1nP5/P1QPPPPP/2BNKB1R w Kkq - 5 10";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let m = Move::new(
             Coordinate::new(4, 1),
             Coordinate::new(5, 3),
@@ -356,10 +298,49 @@ mod print_move_test {
         );
     }
 
+    #[test]
+    fn checks_and_mates() {
+        // fool's mate
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq g3 0 2";
+        let game_state = fen_reader::make_game_state(fen).unwrap();
+        let mut m = parse_move("Qh4", &game_state, Color::Black).unwrap();
+        set_check_flags(&mut m, &game_state);
+        assert!(m.is_check && m.is_checkmate);
+        assert_eq!(print_move(&m, &game_state), "Qh4#");
+
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1";
+        let game_state = fen_reader::make_game_state(fen).unwrap();
+        let mut m = parse_move("Ra8", &game_state, Color::White).unwrap();
+        assert_eq!(print_move(&m, &game_state), "Ra8");
+        set_check_flags(&mut m, &game_state);
+        assert!(m.is_check && !m.is_checkmate);
+        assert_eq!(print_move(&m, &game_state), "Ra8+");
+        let m = parse_move("O-O-O", &game_state, Color::White).unwrap();
+        assert_eq!(print_move(&m, &game_state), "O-O-O");
+
+        // promotion with check
+        let fen = "8/3P1k2/8/8/8/8/8/4K3 w - - 0 1";
+        let game_state = fen_reader::make_game_state(fen).unwrap();
+        let mut m = parse_move("d8=N", &game_state, Color::White).unwrap();
+        set_check_flags(&mut m, &game_state);
+        assert_eq!(print_move(&m, &game_state), "d8=N+");
+    }
+
+    #[test]
+    fn pawn_captures_dont_get_specifiers() {
+        // c and e pawns can both take on d5
+        let fen = "4k3/8/8/3p4/2P1P3/8/8/4K3 w - - 0 1";
+        let game_state = fen_reader::make_game_state(fen).unwrap();
+        let m = parse_move("exd5", &game_state, Color::White).unwrap();
+        assert_eq!(print_move(&m, &game_state), "exd5");
+        let m = parse_move("cxd5", &game_state, Color::White).unwrap();
+        assert_eq!(print_move(&m, &game_state), "cxd5");
+    }
+
     #[test]
     fn captures() {
         let fen = "rnbqkbnr/1ppppppp/8/3N4/8/1nP5/P1QPPPPP/2BNKB1R w Kkq - 5 10";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let m = Move::new(
             Coordinate::new(1, 2),
             Coordinate::new(2, 3),
@@ -376,7 +357,7 @@ mod print_move_test {
     #[test]
     fn pawn_promotion() {
         let fen = "rnbqkbnr/1ppppppp/8/8/2N5/2N5/PpPPPPPP/R1BQKB1R b KQkq - 1 6";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
 
         // pawn promotion with capture moves
         let m = Move::new(
@@ -450,7 +431,7 @@ mod print_move_test {
     #[test]
     fn castling() {
         let fen = "rnbqkbnr/1pp4p/4pp2/3p2p1/3P4/2NQN1PB/PBP1PP1P/R3K2R b KQkq - 1 10";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let m = Move::new(
             Coordinate::new(5, 1),
             Coordinate::new(7, 1),
@@ -486,7 +467,7 @@ mod print_move_test {
     fn en_passant() {
         // unimplemented!("");
         let fen = "rnbqkbnr/1pp4p/4pp2/3p4/3P1Pp1/2NQN1PB/PBP1P2P/R3K2R b KQkq f3 0 11";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let m = Move::new(
             Coordinate::new(7, 4),
             Coordinate::new(6, 3),
@@ -504,7 +485,7 @@ mod print_move_test {
     fn test_make_move_log() {
         // double capture
         let double_capture = "rnbqkbnr/ppppp1pp/8/5p2/4P1P1/8/PPPP1P1P/RNBQKBNR b KQkq g3 0 2";
-        let game_state = fen_reader::make_game_state(double_capture);
+        let game_state = fen_reader::make_game_state(double_capture).unwrap();
         // let m = Move::new()
     }
 }
@@ -515,17 +496,6 @@ mod tests {
     use crate::board::CastlingRights;
     use crate::chess_notation::fen_reader::*;
 
-    #[test]
-    fn read_move_test1() {
-        let test_strs_pass: Vec<&str> = Vec::from([
-            "e4", "e5", "Nf3", "d6", "d4", "Bg4", "dxe5", "Bxf3", "Qxf3", "dxe5", "Bc4", "Nf6",
-            "Qb3", "Qe7", "Nc3", "c6", "Bg5", "b5", "Nxb5", "$1", "cxb5", "Bxb5+", "Nbd7", "O-O-O",
-            "Rd8", "Rxd7", "$1", "Rxd7", "Rd1", "Qe6", "Bxd7+", "Nxd7", "Qb8+", "$3", "Nxb8",
-            "Rd8#", "1-0",
-        ]);
-        let test_strs_fail: Vec<&str> = Vec::from(["$1", "$1", "$3", "1-0"]);
-    }
-
     #[test]
     fn read_move_test() {
         let game_state = GameState::starting_game();
@@ -533,9 +503,9 @@ mod tests {
         let s2 = "a4";
         let m = parse_move(s, &game_state, Color::White);
         let m2 = parse_move(s2, &game_state, Color::White).unwrap();
-        let a1 = Coordinate::from("a1");
-        let a2 = Coordinate::from("a2");
-        let a4 = Coordinate::from("a4");
+        let a1 = Coordinate::from("a1").unwrap();
+        let a2 = Coordinate::from("a2").unwrap();
+        let a4 = Coordinate::from("a4").unwrap();
         let rook = Piece::new(Color::White, PieceType::Rook, Some(a1.clone()));
         let pawn = Piece::new(Color::White, PieceType::Pawn, Some(a2.clone()));
         assert!(m.is_none());
@@ -0,0 +1,377 @@
+use crate::board::BoardTrait;
+use crate::chess_notation::fen_reader;
+use crate::chess_notation::move_to_san;
+use crate::chess_notation::san::{self, SanError};
+use crate::game_state::GameState;
+use crate::move_generator::Move;
+use std::fmt;
+
+/*
+Extended Position Description
+https://www.chessprogramming.org/Extended_Position_Description
+
+the first four fields of a fen (no move counters) followed by operations, each one is an opcode,
+some operands and a semicolon
+
+    r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - bm Nb5; id "WAC.001";
+
+the opcodes we know about
+    bm   best move(s), san
+    am   move(s) to avoid, san
+    id   name of the position
+    c0   comment, c1 - c9 work too
+    dm   direct mate in n moves
+    acd  analysis count depth, how deep the search went
+    ce   centipawn evaluation, from the side to move's point of view
+    pv   predicted variation, san moves starting from the position
+    hmvc / fmvn   half move clock and full move number, the last two fen fields
+everything else is kept as is so it gets written back out
+
+a " or \ inside a string operand is written with a \ in front of it, same as pgn tags
+*/
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Epd {
+    // placement, side to move, castling, en passant
+    pub position: String,
+    // (opcode, operands) in the order they were read, operands don't have their quotes
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+impl Epd {
+    pub fn from_game_state(game_state: &GameState) -> Epd {
+        let fen = fen_reader::make_fen(game_state);
+        Epd {
+            position: fen.split(' ').take(4).collect::<Vec<&str>>().join(" "),
+            operations: vec![],
+        }
+    }
+
+    // the position as a full fen, the move counters come from hmvc and fmvn when they're there
+    pub fn fen(&self) -> String {
+        format!(
+            "{} {} {}",
+            self.position,
+            self.get_str("hmvc").unwrap_or("0"),
+            self.get_str("fmvn").unwrap_or("1")
+        )
+    }
+
+    // parse_epd already made sure the position reads
+    pub fn game_state(&self) -> GameState {
+        fen_reader::make_game_state(self.fen().as_str()).expect("invalid epd position")
+    }
+
+    pub fn get(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(op, _)| op == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    // first operand of an opcode
+    pub fn get_str(&self, opcode: &str) -> Option<&str> {
+        self.get(opcode)?.first().map(|s| s.as_str())
+    }
+
+    // replaces the operation if it's already there, otherwise it goes on the end
+    pub fn set(&mut self, opcode: &str, operands: Vec<String>) {
+        match self.operations.iter_mut().find(|(op, _)| op == opcode) {
+            Some((_, old)) => *old = operands,
+            None => self.operations.push((String::from(opcode), operands)),
+        }
+    }
+
+    pub fn remove(&mut self, opcode: &str) {
+        self.operations.retain(|(op, _)| op != opcode);
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.get_str("id")
+    }
+
+    pub fn comment(&self) -> Option<&str> {
+        self.get_str("c0")
+    }
+
+    pub fn direct_mate(&self) -> Option<u32> {
+        self.get_str("dm")?.parse().ok()
+    }
+
+    pub fn analysis_depth(&self) -> Option<u32> {
+        self.get_str("acd")?.parse().ok()
+    }
+
+    pub fn centipawn_eval(&self) -> Option<i32> {
+        self.get_str("ce")?.parse().ok()
+    }
+
+    pub fn best_moves(&self) -> Result<Vec<Move>, SanError> {
+        self.moves("bm")
+    }
+
+    pub fn avoid_moves(&self) -> Result<Vec<Move>, SanError> {
+        self.moves("am")
+    }
+
+    // every operand is a move from this position
+    pub fn moves(&self, opcode: &str) -> Result<Vec<Move>, SanError> {
+        let game_state = self.game_state();
+        self.get(opcode)
+            .unwrap_or(&[])
+            .iter()
+            .map(|m| san::parse_san(m, &game_state))
+            .collect()
+    }
+
+    // unlike bm and am each move in the pv is played from the position the one before it left
+    pub fn pv(&self) -> Result<Vec<Move>, SanError> {
+        let mut game_state = self.game_state();
+        let mut line = vec![];
+        for operand in self.get("pv").unwrap_or(&[]) {
+            let mut m = san::parse_san(operand, &game_state)?;
+            game_state.make_move_mut(&mut m);
+            line.push(m);
+        }
+        Ok(line)
+    }
+
+    pub fn set_moves(&mut self, opcode: &str, moves: &[Move]) {
+        let game_state = self.game_state();
+        let operands = moves.iter().map(|m| move_to_san(m, &game_state)).collect();
+        self.set(opcode, operands);
+    }
+
+    pub fn set_pv(&mut self, line: &[Move]) {
+        let mut game_state = self.game_state();
+        let mut operands = vec![];
+        for m in line {
+            let mut m = *m;
+            operands.push(move_to_san(&m, &game_state));
+            game_state.make_move_mut(&mut m);
+        }
+        self.set("pv", operands);
+    }
+}
+
+// strings have to be quoted, so does anything that wouldn't read back in as one operand
+fn needs_quotes(opcode: &str, operand: &str) -> bool {
+    let is_string_opcode = opcode == "id"
+        || (opcode.len() == 2 && opcode.starts_with('c') && opcode.as_bytes()[1].is_ascii_digit());
+    is_string_opcode
+        || operand.is_empty()
+        || operand
+            .chars()
+            .any(|c| c.is_whitespace() || c == ';' || c == '"')
+}
+
+impl fmt::Display for Epd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.position)?;
+        for (opcode, operands) in self.operations.iter() {
+            write!(f, " {}", opcode)?;
+            for operand in operands {
+                if needs_quotes(opcode, operand) {
+                    let escaped = operand.replace('\\', "\\\\").replace('"', "\\\"");
+                    write!(f, " \"{}\"", escaped)?;
+                } else {
+                    write!(f, " {}", operand)?;
+                }
+            }
+            write!(f, ";")?;
+        }
+        Ok(())
+    }
+}
+
+pub fn parse_epd(line: &str) -> Result<Epd, String> {
+    let line = line.trim();
+    let mut rest = line;
+    let mut fields = vec![];
+    for _ in 0..4 {
+        rest = rest.trim_start();
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if end == 0 {
+            return Err(format!("expected 4 position fields in \"{}\"", line));
+        }
+        fields.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    let mut operations = vec![];
+    let mut opcode: Option<String> = None;
+    let mut operands = vec![];
+    let mut chars = rest.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == ';' {
+            chars.next();
+            match opcode.take() {
+                Some(opcode) => operations.push((opcode, std::mem::take(&mut operands))),
+                None => return Err(String::from("operation is missing its opcode")),
+            }
+        } else if c == '"' {
+            chars.next();
+            let mut operand = String::new();
+            loop {
+                match chars.next() {
+                    None => return Err(String::from("unterminated string operand")),
+                    Some('"') => break,
+                    // \" and \\, any other \ is just a \
+                    Some('\\') => match chars.peek() {
+                        Some(&c) if c == '"' || c == '\\' => {
+                            operand.push(c);
+                            chars.next();
+                        }
+                        _ => operand.push('\\'),
+                    },
+                    Some(c) => operand.push(c),
+                }
+            }
+            if opcode.is_none() {
+                return Err(format!("string \"{}\" is missing its opcode", operand));
+            }
+            operands.push(operand);
+        } else {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == ';' || c == '"' {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            if opcode.is_none() {
+                opcode = Some(token);
+            } else {
+                operands.push(token);
+            }
+        }
+    }
+    if let Some(opcode) = opcode {
+        return Err(format!("operation {} is missing its ';'", opcode));
+    }
+
+    let epd = Epd {
+        position: fields.join(" "),
+        operations,
+    };
+    fen_reader::make_game_state(epd.fen().as_str()).map_err(|err| err.to_string())?;
+    Ok(epd)
+}
+
+// one position per line, blank lines and lines starting with # are skipped
+pub fn read_epd_file(text: &str) -> Result<Vec<Epd>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim_start_matches('\u{feff}').trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .map(|(i, line)| {
+            parse_epd(line.trim_start_matches('\u{feff}'))
+                .map_err(|err| format!("line {}: {}", i + 1, err))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{Coordinate, PieceType};
+
+    const WAC_1: &str =
+        "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";";
+
+    #[test]
+    fn test_read_and_write() {
+        let epd = parse_epd(WAC_1).unwrap();
+        assert_eq!(
+            epd.position,
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - -"
+        );
+        assert_eq!(epd.id(), Some("WAC.001"));
+        assert_eq!(epd.get("bm").unwrap(), &[String::from("Qg6")]);
+        assert_eq!(epd.to_string(), WAC_1);
+        assert_eq!(parse_epd(epd.to_string().as_str()).unwrap(), epd);
+
+        let epd = parse_epd(
+            "8/8/8/8/8/8/8/K1k5 w - - c0 \"a; b \" ; hmvc 12; fmvn 40; noop; dm 3; ce -150; acd 9;",
+        )
+        .unwrap();
+        assert_eq!(epd.comment(), Some("a; b "));
+        assert_eq!(epd.fen(), "8/8/8/8/8/8/8/K1k5 w - - 12 40");
+        assert_eq!(epd.get("noop").unwrap().len(), 0);
+        assert_eq!(epd.direct_mate(), Some(3));
+        assert_eq!(epd.centipawn_eval(), Some(-150));
+        assert_eq!(epd.analysis_depth(), Some(9));
+        assert_eq!(
+            epd.to_string(),
+            "8/8/8/8/8/8/8/K1k5 w - - c0 \"a; b \"; hmvc 12; fmvn 40; noop; dm 3; ce -150; acd 9;"
+        );
+
+        assert!(parse_epd("8/8/8 w - - id \"x\";").is_err());
+        assert!(parse_epd("8/8/8/8/8/8/8/K1k5 w -").is_err());
+        assert!(parse_epd("8/8/8/8/8/8/8/K1k5 w - - bm Kb2").is_err());
+        assert!(parse_epd("8/8/8/8/8/8/8/K1k5 w - - id \"x;").is_err());
+
+        // quotes in a string get escaped so it reads back the same
+        let mut epd = parse_epd("8/8/8/8/8/8/8/K1k5 w - - c0 \"C:\\suites\";").unwrap();
+        assert_eq!(epd.comment(), Some("C:\\suites"));
+        epd.set("c0", vec![String::from("the \"best\" move, a\\b")]);
+        assert_eq!(
+            epd.to_string(),
+            "8/8/8/8/8/8/8/K1k5 w - - c0 \"the \\\"best\\\" move, a\\\\b\";"
+        );
+        assert_eq!(parse_epd(epd.to_string().as_str()).unwrap(), epd);
+    }
+
+    #[test]
+    fn test_moves() {
+        let mut epd = parse_epd(WAC_1).unwrap();
+        let best = epd.best_moves().unwrap();
+        assert_eq!(best.len(), 1);
+        assert_eq!(best[0].piece, PieceType::Queen);
+        assert_eq!(best[0].to, Coordinate::new(7, 6));
+        assert!(epd.avoid_moves().unwrap().is_empty());
+
+        epd.set("pv", vec![String::from("Qg6"), String::from("fxg6")]);
+        let pv = epd.pv().unwrap();
+        assert_eq!(pv.len(), 2);
+        assert_eq!(pv[1].from, Coordinate::new(6, 7));
+        epd.set_pv(&pv);
+        assert_eq!(
+            epd.get("pv").unwrap(),
+            &[String::from("Qg6"), String::from("fxg6")]
+        );
+
+        epd.set("am", vec![String::from("Qa3")]);
+        assert!(epd.avoid_moves().is_err());
+
+        epd.set_moves("bm", &best);
+        epd.remove("am");
+        assert_eq!(
+            epd.to_string(),
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\"; pv Qg6 fxg6;"
+        );
+    }
+
+    #[test]
+    fn test_from_game_state() {
+        let epd = Epd::from_game_state(&GameState::starting_game());
+        assert_eq!(
+            epd.to_string(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -"
+        );
+        assert_eq!(epd.fen(), fen_reader::INITIAL_BOARD);
+    }
+
+    #[test]
+    fn test_read_file() {
+        let text = format!("# a comment\n\n{}\n{}\n", WAC_1, WAC_1);
+        assert_eq!(read_epd_file(text.as_str()).unwrap().len(), 2);
+        let err = read_epd_file("\n8/8 w - -\n").unwrap_err();
+        assert!(err.starts_with("line 2"));
+    }
+}
@@ -4,7 +4,8 @@ use crate::board_console_printer;
 use crate::board_console_printer::print_board;
 use crate::chess_notation::pgn::Game;
 use crate::game_state::GameState;
-use std::path::Prefix::Verbatim;
+use crate::move_generator::generate_checks;
+use std::fmt;
 
 // @todo : board -> fen string
 
@@ -112,7 +113,7 @@ pub fn make_fen(board: &dyn BoardTrait) -> String {
     }
     // en passant square
     let en_passant = if board.en_passant_target().is_some() {
-        board.en_passant_target().unwrap().clone().to_string()
+        Coordinate::to(board.en_passant_target().unwrap())
     } else {
         String::from('-')
     };
@@ -126,121 +127,281 @@ pub fn make_fen(board: &dyn BoardTrait) -> String {
     )
 }
 
-fn read_piece(char: &str) -> Piece {
-    let color = if char.to_lowercase() == char {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    // wrong number of space separated fields, needs 6 (or 4, the clocks can be left off)
+    FieldCount(usize),
+    // needs 8 ranks separated by /
+    RankCount(usize),
+    // a rank that doesn't add up to 8 squares, rank is 1 - 8
+    RankWidth { rank: u8, width: u8 },
+    UnknownPiece(char),
+    SideToMove(String),
+    Castling(String),
+    EnPassant(String),
+    Clock(String),
+    // the position reads fine but couldn't happen in a game
+    KingCount { color: Color, count: usize },
+    PawnOnBackRank(Coordinate),
+    // the player who just moved left their king in check
+    OpponentInCheck(Color),
+    // castling right without the king and rook on their starting squares, ex. 'K'
+    CastlingRights(char),
+    // en passant square without a pawn that could have just moved two squares past it
+    ImplausibleEnPassant(Coordinate),
+    // the game state didn't agree with itself after setting it up
+    InvalidState,
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::FieldCount(count) => write!(f, "expected 6 fields, found {}", count),
+            FenError::RankCount(count) => write!(f, "expected 8 ranks, found {}", count),
+            FenError::RankWidth { rank, width } => {
+                write!(f, "rank {} is {} squares wide, not 8", rank, width)
+            }
+            FenError::UnknownPiece(char) => write!(f, "{} isn't a piece", char),
+            FenError::SideToMove(str) => write!(f, "side to move should be w or b, not {}", str),
+            FenError::Castling(str) => write!(f, "{} isn't a castling field", str),
+            FenError::EnPassant(str) => write!(f, "{} isn't an en passant square", str),
+            FenError::Clock(str) => write!(f, "{} isn't a move counter", str),
+            FenError::KingCount { color, count } => {
+                write!(f, "{} has {} kings, should have 1", color, count)
+            }
+            FenError::PawnOnBackRank(at) => write!(f, "pawn on the back rank at {}", at),
+            FenError::OpponentInCheck(color) => {
+                write!(f, "{} is in check but it's not their move", color)
+            }
+            FenError::CastlingRights(char) => write!(
+                f,
+                "castling right {} without the king and rook on their squares",
+                char
+            ),
+            FenError::ImplausibleEnPassant(at) => {
+                write!(f, "no pawn could have just moved past {}", at)
+            }
+            FenError::InvalidState => write!(f, "the game state is inconsistent"),
+        }
+    }
+}
+
+fn read_piece(char: char) -> Result<Piece, FenError> {
+    let color = if char.is_ascii_lowercase() {
         Color::Black
     } else {
         Color::White
     };
-    let piece_type = PieceType::from(char.to_lowercase().as_str()).unwrap();
-    Piece::new(color, piece_type, None)
+    let piece_type = PieceType::from(char.to_ascii_lowercase().to_string().as_str())
+        .ok_or(FenError::UnknownPiece(char))?;
+    Ok(Piece::new(color, piece_type, None))
 }
-fn make_pieces(piece_string: &str) -> Vec<Piece> {
+
+fn make_pieces(piece_string: &str) -> Result<Vec<Piece>, FenError> {
     // tokenize by row
-    let piece_chars = "PNBRQKpnbrqk";
-    let numbers = "123456789";
-    let rows = piece_string.split("/");
+    let rows: Vec<&str> = piece_string.split('/').collect();
+    if rows.len() != 8 {
+        return Err(FenError::RankCount(rows.len()));
+    }
     let mut pieces: Vec<Piece> = vec![];
-    for (i, row) in rows.enumerate() {
+    for (i, row) in rows.iter().enumerate() {
         let y = 8 - (i as u8);
-        let mut x: u8 = 1;
+        let mut width: u8 = 0;
         // read each character of the string
-        for (_, char) in row.chars().enumerate() {
-            let coordinate = Coordinate::new(x, y);
-            if numbers.contains(char) {
-                x += char.to_string().parse::<u8>().unwrap();
-            } else if piece_chars.contains(char) {
-                let mut piece = read_piece(char.to_string().as_str());
-                piece.set_at(coordinate);
-                pieces.push(piece);
-                x += 1;
+        for char in row.chars() {
+            if let Some(empty) = char.to_digit(10).filter(|&d| (1..=8).contains(&d)) {
+                width += empty as u8;
             } else {
-                panic!("{} char not recognized", char);
+                let mut piece = read_piece(char)?;
+                if width < 8 {
+                    piece.set_at(Coordinate::new(width + 1, y));
+                }
+                pieces.push(piece);
+                width += 1;
             }
+            if width > 8 {
+                break;
+            }
+        }
+        if width != 8 {
+            return Err(FenError::RankWidth { rank: y, width });
         }
     }
-    return pieces;
+    Ok(pieces)
 }
 
-fn read_pieces(piece_string: &str, board: &mut dyn BoardTrait) {
-    // tokenize by row
-    let piece_chars = "PNBRQKpnbrqk";
-    let numbers = "123456789";
-    let rows = piece_string.split("/");
-    for (i, row) in rows.enumerate() {
-        let y = 8 - (i as u8);
-        let mut x: u8 = 1;
-        // read each character of the string
-        for (_, char) in row.chars().enumerate() {
-            let coordinate = Coordinate::new(x, y);
-            if numbers.contains(char) {
-                x += char.to_string().parse::<u8>().unwrap();
-            } else if piece_chars.contains(char) {
-                let piece = read_piece(char.to_string().as_str());
-                board.place_piece(piece, &coordinate);
-                x += 1;
-            } else {
-                panic!("{} char not recognized", char);
-            }
+fn read_pieces(piece_string: &str, board: &mut dyn BoardTrait) -> Result<(), FenError> {
+    // make_pieces gave every piece a square or an error
+    for piece in make_pieces(piece_string)? {
+        if let Some(&at) = piece.at() {
+            board.place_piece(piece, &at);
         }
     }
+    Ok(())
 }
 
 pub fn make_initial_board() -> Board {
-    make_board(INITIAL_BOARD)
+    make_board(INITIAL_BOARD).expect("the initial board is a valid fen")
 }
 
-pub fn make_game_state(fen_string: &str) -> GameState {
-    let parts = fen_string.split(" ").collect::<Vec<&str>>();
-    let player_to_move = if parts[1] == "w" {
-        Color::White
-    } else {
-        Color::Black
+pub fn make_game_state(fen_string: &str) -> Result<GameState, FenError> {
+    let parts = fen_string.split_whitespace().collect::<Vec<&str>>();
+    if parts.len() != 6 && parts.len() != 4 {
+        return Err(FenError::FieldCount(parts.len()));
+    }
+    let pieces = make_pieces(parts[0])?;
+    let player_to_move = match parts[1] {
+        "w" => Color::White,
+        "b" => Color::Black,
+        other => return Err(FenError::SideToMove(String::from(other))),
     };
-    let white_can_castle_king_side = parts[2].contains("K");
-    let white_can_castle_queen_side = parts[2].contains("Q");
-    let black_can_castle_king_side = parts[2].contains("k");
-    let black_can_castle_queen_side = parts[2].contains("q");
-    let en_passant_target = if parts[3] == "-" {
-        None
-    } else {
-        Some(Coordinate::from(parts[3]))
+    let castling = parts[2];
+    let castling_chars_ok = castling
+        .chars()
+        .enumerate()
+        .all(|(i, c)| "KQkq".contains(c) && !castling[..i].contains(c));
+    if castling.is_empty() || (castling != "-" && !castling_chars_ok) {
+        return Err(FenError::Castling(String::from(castling)));
+    }
+    let en_passant_target = match parts[3] {
+        "-" => None,
+        square => match Coordinate::from(square) {
+            Some(at) if at.y() == 3 || at.y() == 6 => Some(at),
+            _ => return Err(FenError::EnPassant(String::from(square))),
+        },
     };
-    let half_move_clock = parts[4].parse::<u16>().unwrap();
-    let full_move_number = parts[5].parse::<u16>().unwrap();
-    let pieces = make_pieces(parts[0]);
+    let read_clock = |i: usize, default: u16| match parts.get(i) {
+        None => Ok(default),
+        Some(str) => str
+            .parse::<u16>()
+            .map_err(|_| FenError::Clock(String::from(*str))),
+    };
+    let half_move_clock = read_clock(4, 0)?;
+    let full_move_number = read_clock(5, 1)?;
+
     let board = BitBoard::init_from_pieces(pieces);
-    return GameState::make_game_state(
+    let game_state = GameState::make_game_state(
         player_to_move,
-        white_can_castle_king_side,
-        white_can_castle_queen_side,
-        black_can_castle_king_side,
-        black_can_castle_queen_side,
+        castling.contains('K'),
+        castling.contains('Q'),
+        castling.contains('k'),
+        castling.contains('q'),
         en_passant_target,
         half_move_clock,
         full_move_number,
         board,
     );
+    validate(&game_state)?;
+    Ok(game_state)
 }
 
-pub fn make_board(fen_string: &str) -> Board {
-    let parts = fen_string.split(" ").collect::<Vec<&str>>();
-    let player_to_move = if parts[1] == "w" {
-        Color::White
-    } else {
-        Color::Black
+// checks the position could come up in a real game
+pub fn validate(game_state: &GameState) -> Result<(), FenError> {
+    for &color in [Color::White, Color::Black].iter() {
+        let count = game_state
+            .get_kings()
+            .iter()
+            .filter(|king| king.color == color)
+            .count();
+        if count != 1 {
+            return Err(FenError::KingCount { color, count });
+        }
+    }
+    for color in [Color::White, Color::Black].iter() {
+        for piece in game_state.get_all_pieces(*color) {
+            let at = *piece.at().unwrap();
+            if piece.piece_type == PieceType::Pawn && (at.y() == 1 || at.y() == 8) {
+                return Err(FenError::PawnOnBackRank(at));
+            }
+        }
+    }
+
+    let player_to_move = game_state.player_to_move();
+    let opponent = player_to_move.opposite();
+    if !generate_checks(game_state, opponent).is_empty() {
+        return Err(FenError::OpponentInCheck(opponent));
+    }
+
+    let has = |x: u8, y: u8, color: Color, piece_type: PieceType| {
+        game_state
+            .get_piece_at(&Coordinate::new(x, y))
+            .is_some_and(|p| p.color == color && p.piece_type == piece_type)
+    };
+    for &(char, color, rook_x, king_side) in [
+        ('K', Color::White, 8, true),
+        ('Q', Color::White, 1, false),
+        ('k', Color::Black, 8, true),
+        ('q', Color::Black, 1, false),
+    ]
+    .iter()
+    {
+        let can_castle = if king_side {
+            game_state.can_castle_king_side(color)
+        } else {
+            game_state.can_castle_queen_side(color)
+        };
+        let y = if color == Color::White { 1 } else { 8 };
+        if can_castle
+            && !(has(5, y, color, PieceType::King) && has(rook_x, y, color, PieceType::Rook))
+        {
+            return Err(FenError::CastlingRights(char));
+        }
+    }
+
+    // the pawn that just moved two squares went past the en passant square, so the square and
+    // the one it started on are empty and it's sitting on the other side
+    if let Some(at) = game_state.en_passant_target() {
+        let (expected_y, pawn_y, start_y) = match opponent {
+            Color::White => (3, 4, 2),
+            Color::Black => (6, 5, 7),
+        };
+        let empty = |y: u8| {
+            game_state
+                .get_piece_at(&Coordinate::new(at.x(), y))
+                .is_none()
+        };
+        if at.y() != expected_y
+            || !empty(at.y())
+            || !empty(start_y)
+            || !has(at.x(), pawn_y, opponent, PieceType::Pawn)
+        {
+            return Err(FenError::ImplausibleEnPassant(at));
+        }
+    }
+
+    if !game_state.assert_valid_state() {
+        return Err(FenError::InvalidState);
+    }
+    Ok(())
+}
+
+pub fn make_board(fen_string: &str) -> Result<Board, FenError> {
+    let parts = fen_string.split_whitespace().collect::<Vec<&str>>();
+    if parts.len() != 6 {
+        return Err(FenError::FieldCount(parts.len()));
+    }
+    let player_to_move = match parts[1] {
+        "w" => Color::White,
+        "b" => Color::Black,
+        other => return Err(FenError::SideToMove(String::from(other))),
     };
     let white_can_castle_king_side = parts[2].contains("K");
     let white_can_castle_queen_side = parts[2].contains("Q");
     let black_can_castle_king_side = parts[2].contains("k");
     let black_can_castle_queen_side = parts[2].contains("q");
-    let en_passant_target = if parts[3] == "-" {
-        None
-    } else {
-        Some(Coordinate::from(parts[3]))
+    let en_passant_target = match parts[3] {
+        "-" => None,
+        square => match Coordinate::from(square) {
+            Some(at) => Some(at),
+            None => return Err(FenError::EnPassant(String::from(square))),
+        },
+    };
+    let read_clock = |str: &str| {
+        str.parse::<u32>()
+            .map_err(|_| FenError::Clock(String::from(str)))
     };
-    let half_move_clock = parts[4].parse::<u32>().unwrap();
-    let full_move_number = parts[5].parse::<u32>().unwrap();
+    let half_move_clock = read_clock(parts[4])?;
+    let full_move_number = read_clock(parts[5])?;
 
     let mut board = Board::make_board(
         player_to_move,
@@ -253,8 +414,8 @@ pub fn make_board(fen_string: &str) -> Board {
         full_move_number,
     );
 
-    read_pieces(parts[0], &mut board);
-    return board;
+    read_pieces(parts[0], &mut board)?;
+    Ok(board)
 }
 
 #[cfg(test)]
@@ -277,11 +438,112 @@ mod tests {
         let expected = self::INITIAL_BOARD;
         assert_eq!(expected, fen_result, "initial board fen string is correct");
         println!("{}", fen_result.as_str());
+
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2";
+        assert_eq!(make_fen(&make_game_state(fen).unwrap()), fen);
+    }
+
+    #[test]
+    fn test_fen_errors() {
+        fn err(fen: &str) -> FenError {
+            match make_game_state(fen) {
+                Ok(_) => panic!("{} shouldn't read", fen),
+                Err(err) => err,
+            }
+        }
+        assert!(make_game_state(INITIAL_BOARD).is_ok());
+        assert!(make_game_state(TEST_BOARD_2).is_ok());
+        // clocks can be left off
+        assert!(make_game_state("4k3/8/8/8/8/8/8/4K3 w - -").is_ok());
+        assert!(
+            make_game_state("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3").is_ok()
+        );
+
+        assert_eq!(err("4k3/8/8/8/8/8/8/4K3 w -"), FenError::FieldCount(3));
+        assert_eq!(err("4k3/8/8/8/8/8/4K3 w - - 0 1"), FenError::RankCount(7));
+        assert_eq!(
+            err("4k3/8/8/8/8/8/8/4K4 w - - 0 1"),
+            FenError::RankWidth { rank: 1, width: 9 }
+        );
+        assert_eq!(
+            err("4k3/8/8/8/8/8/8/4K2 w - - 0 1"),
+            FenError::RankWidth { rank: 1, width: 7 }
+        );
+        assert_eq!(
+            err("4k3/8/8/8/8/8/8/4X3 w - - 0 1"),
+            FenError::UnknownPiece('X')
+        );
+        assert!(matches!(
+            err("4k3/8/8/8/8/8/8/4K3 x - - 0 1"),
+            FenError::SideToMove(_)
+        ));
+        assert!(matches!(
+            err("4k3/8/8/8/8/8/8/4K3 w KK - 0 1"),
+            FenError::Castling(_)
+        ));
+        assert!(matches!(
+            err("4k3/8/8/8/8/8/8/4K3 w - e4 0 1"),
+            FenError::EnPassant(_)
+        ));
+        assert!(matches!(
+            err("4k3/8/8/8/8/8/8/4K3 w - - x 1"),
+            FenError::Clock(_)
+        ));
+
+        assert_eq!(
+            err("8/8/8/8/8/8/8/4K3 w - - 0 1"),
+            FenError::KingCount {
+                color: Color::Black,
+                count: 0
+            }
+        );
+        assert_eq!(
+            err("4k3/8/8/8/8/8/8/3KK3 w - - 0 1"),
+            FenError::KingCount {
+                color: Color::White,
+                count: 2
+            }
+        );
+        assert_eq!(
+            err("4k3/8/8/8/8/8/8/P3K3 w - - 0 1"),
+            FenError::PawnOnBackRank(Coordinate::new(1, 1))
+        );
+        assert_eq!(
+            err("4k3/8/8/8/8/8/4R3/4K3 w - - 0 1"),
+            FenError::OpponentInCheck(Color::Black)
+        );
+        assert!(make_game_state("4k3/8/8/8/8/8/4R3/4K3 b - - 0 1").is_ok());
+        assert_eq!(
+            err("4k3/8/8/8/8/8/8/4K2R w Kq - 0 1"),
+            FenError::CastlingRights('q')
+        );
+        assert_eq!(
+            err("r3k3/8/8/8/8/8/8/3K3R w Ka - 0 1"),
+            FenError::Castling(String::from("Ka"))
+        );
+        assert_eq!(
+            err("r3k3/8/8/8/8/8/8/3K3R w K - 0 1"),
+            FenError::CastlingRights('K')
+        );
+        // no pawn on e4 that could have just moved there
+        assert_eq!(
+            err("4k3/8/8/8/8/8/8/4K3 b - e3 0 1"),
+            FenError::ImplausibleEnPassant(Coordinate::new(5, 3))
+        );
+        // right square for the wrong side
+        assert_eq!(
+            err("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1"),
+            FenError::ImplausibleEnPassant(Coordinate::new(5, 3))
+        );
+        assert_eq!(
+            err("4k3/8/8/8/8/8/8/4K3 w - - 0 1 extra").to_string(),
+            "expected 6 fields, found 7"
+        );
     }
 
     #[test]
     fn test_initial_board() {
-        let board = make_board(INITIAL_BOARD);
+        let board = make_board(INITIAL_BOARD).unwrap();
         // print_board(&board);
         let white_pieces = board.get_all_pieces(Color::White);
         let black_pieces = board.get_all_pieces(Color::Black);
@@ -303,9 +565,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_make_board_errors() {
+        let err = |fen: &str| make_board(fen).err();
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNX w KQkq - 0 1"),
+            Some(FenError::UnknownPiece('X'))
+        );
+        assert_eq!(
+            err("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN w KQkq - 0 1"),
+            Some(FenError::RankWidth { rank: 1, width: 7 })
+        );
+        assert_eq!(err("8/8/8/8/8/8/8/8 w -"), Some(FenError::FieldCount(3)));
+        assert_eq!(
+            err("8/8/8/8/8/8/8/8 x - - 0 1"),
+            Some(FenError::SideToMove(String::from("x")))
+        );
+        assert_eq!(
+            err("8/8/8/8/8/8/8/8 w - z9 0 1"),
+            Some(FenError::EnPassant(String::from("z9")))
+        );
+        assert_eq!(
+            err("8/8/8/8/8/8/8/8 w - - one 1"),
+            Some(FenError::Clock(String::from("one")))
+        );
+    }
+
     #[test]
     fn test_board_2() {
-        let board = make_board(TEST_BOARD_2);
+        let board = make_board(TEST_BOARD_2).unwrap();
         fn has_piece(board: &BoardTrait, at: &Coordinate) -> bool {
             board.has_piece(at)
         }
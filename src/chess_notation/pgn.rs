@@ -1,17 +1,11 @@
-use regex::Regex;
-
-use crate::board::{Board, BoardTrait, Color, Coordinate, PieceType, CastlingRights};
-use crate::chess_notation::fen_reader::make_board;
-use crate::chess_notation::read_move;
-use crate::chess_notation::{fen_reader, ParsedMoveType};
-use crate::game::Game as chess_game;
-use crate::game_state::GameState;
-use crate::move_generator::{
-    self, gen_legal_moves, pseudo_legal_move_generator as p_gen, Move, MoveType,
-};
+use crate::board::Color;
+use crate::chess_notation::fen_reader;
+use crate::chess_notation::pgn_parser::{self, PgnError};
+use crate::game::{Game as chess_game, GameResult};
+use crate::move_generator::Move;
 use std::fmt;
 use std::fmt::Formatter;
-use std::fs;
+use std::time::Duration;
 
 const TEST_PGN: &str = r#"[Event "F/S Return Match"]
 [Site "Belgrade, Serbia JUG"]
@@ -30,7 +24,8 @@ hxg5 29. b3 Ke6 30. a3 Kd6 31. axb4 cxb4 32. Ra5 Nd5 33. f3 Bc8 34. Kf2 Bf5
 35. Ra7 g6 36. Ra6+ Kc5 37. Ke1 Nf4 38. g3 Nxh3 39. Kd2 Kb5 40. Rd6 Kc5 41. Ra6
 Nf2 42. g4 Bd3 43. Re6 1/2-1/2"#;
 
-enum Termination {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Termination {
     Abandoned,
     Adjudication,
     Death,
@@ -49,15 +44,16 @@ impl fmt::Display for Termination {
             Termination::Death => "death",
             Termination::Emergency => "emergency",
             Termination::Normal => "normal",
-            Termination::Time => "time",
+            Termination::Time => "time forfeit",
             Termination::Forfeit => "forfeit",
-            Termination::RulesInfraction => "rulesInfraction",
+            Termination::RulesInfraction => "rules infraction",
             Termination::Unterminated => "unterminated",
         };
         write!(f, "{}", str)
     }
 }
-enum Mode {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
     OverTheBoard,
     InternetChessServer,
 }
@@ -71,158 +67,157 @@ impl fmt::Display for Mode {
     }
 }
 
-pub fn read_pgn_file() {
-    //  let res = fs::metadata("./Games");
-    //     let is_dir: bool = match res {
-    //         Ok(f) => f.is_dir(),
-    //         Err(err) => false,
-    //     };
-    //     if is_dir {
-    //         let mut path_str = format!("./Games/");
-    //         path_str.push_str("1.pgn");
-    //         let path = Path::new(path_str.as_str());
-    //         let display = path.display();
-    //         let mut contents: String = match std::fs::read_to_string(&path) {
-    //             Err(err) => panic!("couldn't read {}: {}", display, err),
-    //             Ok(file) => file,
-    //         };
-    //         contents = contents
-    //             .trim_start_matches('\u{feff}')
-    //             .replace("\r\n", "\n");
-    //         let moves = pgn::moves_from_pgn(contents.as_str());
-    //         game.run_sim_game(moves);
-    //         return;
+// main line of the first game in the pgn, empty if there are no games
+pub fn moves_from_pgn(pgn: &str) -> Result<Vec<Move>, PgnError> {
+    let games = pgn_parser::parse_pgn(pgn)?;
+    Ok(games.first().map(|g| g.main_line()).unwrap_or_default())
 }
 
-pub fn moves_from_pgn(pgn: &str) -> Vec<Move> {
-    // reading from a pgn time
-    let mut moves: Vec<Move> = Vec::new();
-    // let mut scrap_board = fen_reader::make_board(fen_reader::INITIAL_BOARD);
-    let mut scrap_game_state = GameState::starting_game();
-
-    // for the moment were skipping the informational section
-    // break pgn into lines then grab all the lines with move text in them
-    // let lines: Vec<&str> = pgn.split('\n').collect();
-
-    let info_line_matcher = Regex::new(r"\s?\[.*\]\s?$").unwrap();
-    let empty_space_matcher = Regex::new(r"^\s$").unwrap();
-    let mut move_lines: Vec<&str> = Vec::new();
-    // let mut info_section = true;
-    // let mut move_section = false;
-    for _s in pgn.split("\n").into_iter() {
-        if info_line_matcher.is_match(_s) {
-            continue;
-        } else if _s.is_empty() || empty_space_matcher.is_match(_s) {
-            continue;
-        } else {
-            move_lines.push(_s);
-        }
+// "1-0" => 1.0, "0-1" => 0.0, "1/2-1/2" => 0.5, anything else (like "*") is None
+pub fn result_from_str(str: &str) -> Option<f32> {
+    match str.trim() {
+        "1-0" => Some(1.0),
+        "0-1" => Some(0.0),
+        "1/2-1/2" => Some(0.5),
+        _ => None,
     }
+}
 
-    let move_text = move_lines.join("");
+// lines in the move text are kept under this many characters
+const MAX_LINE_LENGTH: usize = 80;
 
-    // remove the comments
-    let comment_matcher = Regex::new(r"(\{[^*\}]*\})").unwrap();
-    let stuff: Vec<&str> = comment_matcher.split(&move_text).collect();
+// extra info about a move that goes in a comment after it, like {[%eval 0.25] [%clk 0:04:59]}
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct MoveAnnotation {
+    // centipawns, white's point of view
+    pub eval: Option<f32>,
+    // time left on the clock after the move
+    pub clock: Option<Duration>,
+}
 
-    // put it in a string again
-    let mut move_text_2 = String::new();
-    for _s in stuff {
-        move_text_2.push_str(_s);
+impl MoveAnnotation {
+    pub fn to_comment(&self) -> Option<String> {
+        let mut commands = vec![];
+        if let Some(eval) = self.eval {
+            // + 0.0 so we don't write -0.00
+            commands.push(format!("[%eval {:.2}]", eval / 100.0 + 0.0));
+        }
+        if let Some(clock) = self.clock {
+            commands.push(format!("[%clk {}]", format_clock(clock)));
+        }
+        if commands.is_empty() {
+            None
+        } else {
+            Some(format!("{{{}}}", commands.join(" ")))
+        }
     }
 
-    // take out all move turn stuff (eg. 1. 2. 3. )
-    let turn_matcher = Regex::new(r"(\d+\.)").unwrap();
-    let mut t = String::new();
-    let stuff: Vec<&str> = turn_matcher.split(move_text_2.as_str()).collect();
-    for _s in stuff {
-        t.push_str(_s);
+    // reads back what to_comment wrote, from the comments after a move
+    pub fn from_comments(comments: &[String]) -> MoveAnnotation {
+        let mut annotation = MoveAnnotation::default();
+        for comment in comments {
+            if let Some(eval) = comment_command(comment, "eval") {
+                annotation.eval = eval.parse::<f32>().ok().map(|pawns| pawns * 100.0);
+            }
+            if let Some(clock) = comment_command(comment, "clk") {
+                annotation.clock = parse_clock(clock);
+            }
+        }
+        annotation
     }
+}
 
-    //now we have mostly just move text and some $1, $2 stuff && empty lines
-    let move_candidates: Vec<&str> = t.split(' ').collect();
-    let mut color_to_move = Color::White;
-
-    for _m in move_candidates {
-        // skip empty
-        if _m.is_empty() {
-            continue;
-        }
-        // if is valid move
+// the argument of a [%name arg] command in a comment
+fn comment_command<'a>(comment: &'a str, name: &str) -> Option<&'a str> {
+    let start = comment.find(format!("[%{} ", name).as_str())? + name.len() + 3;
+    let end = start + comment[start..].find(']')?;
+    Some(comment[start..end].trim())
+}
 
-        let res = read_move(_m);
-        if res.is_none() {
-            // println!("MOVE NOT FOUND");
-            continue;
-        } else {
-            let (piece_type, coordinate, parsed_move, promotion_type) = res.unwrap();
-            println!(
-                "{} {} {} ",
-                piece_type,
-                coordinate.unwrap_or(Coordinate::new(0, 0)),
-                promotion_type.unwrap_or(PieceType::Pawn)
-            );
-
-            if coordinate.is_none()
-                && (ParsedMoveType::LongCastles == parsed_move
-                    || ParsedMoveType::ShortCastles == parsed_move)
-            {
-                // @todo :: handle castles
-                let mut new_move = match parsed_move {
-                    ParsedMoveType::LongCastles => Move::castle_queen_side(&CastlingRights::new(false, false), color_to_move ),
-                    ParsedMoveType::ShortCastles => Move::castle_king_side(&CastlingRights::new(false, false), color_to_move ),
-                    ParsedMoveType::Promotion => break,
-                    ParsedMoveType::Move => break,
-                };
-                moves.push(new_move);
-                scrap_game_state.make_move_mut(&mut new_move);
-                color_to_move = match color_to_move {
-                    Color::White => Color::Black,
-                    Color::Black => Color::White,
-                };
-                continue;
-            }
+// h:mm:ss
+pub fn format_clock(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
 
-            if promotion_type.is_some() {
-                //@todo
-            } else {
-            }
+// h:mm:ss back to a duration
+pub fn parse_clock(text: &str) -> Option<Duration> {
+    let mut seconds = 0;
+    for part in text.split(':') {
+        seconds = seconds * 60 + part.parse::<u64>().ok()?;
+    }
+    Some(Duration::from_secs(seconds))
+}
 
-            let to = coordinate.unwrap();
-            // //@todo :::: make moves
-            let mut found_pieces =
-                scrap_game_state.find_pieces_can_move_to_square(color_to_move, piece_type, to);
-            if found_pieces.is_empty() {
-                break;
-            }
-            let found_piece = found_pieces.pop().unwrap();
-            if found_piece.at().is_none() {
-                break;
-            }
-            let from = found_piece.at().unwrap();
-            let move_type = match parsed_move {
-                ParsedMoveType::LongCastles => break,
-                ParsedMoveType::ShortCastles => break,
-                ParsedMoveType::Promotion => MoveType::Promotion(promotion_type.unwrap()),
-                ParsedMoveType::Move => MoveType::Move,
-            };
-            let mut new_move = p_gen::make_move_to(from, &to, found_piece, move_type, &scrap_game_state);
-            moves.push(new_move);
-            scrap_game_state.make_move_mut(&mut new_move);
-            //@todo :: this roughly works
-
-            // flip color to move
-            color_to_move = match color_to_move {
-                Color::White => Color::Black,
-                Color::Black => Color::White,
-            };
+// numbered move text on one line, moves are san
+// numbering starts from the fen's move number, black moves get a 12... number when
+// they start the game or follow a comment
+pub fn make_move_text(
+    moves: &[String],
+    annotations: &[MoveAnnotation],
+    start_fen: &str,
+    result: &str,
+) -> String {
+    let parts: Vec<&str> = start_fen.split_whitespace().collect();
+    let mut color = match parts.get(1) {
+        Some(&"b") => Color::Black,
+        _ => Color::White,
+    };
+    let mut move_number = parts
+        .get(5)
+        .and_then(|n| n.parse::<u32>().ok())
+        .unwrap_or(1);
+    let mut tokens: Vec<String> = vec![];
+    let mut after_comment = false;
+    for (i, m) in moves.iter().enumerate() {
+        match color {
+            Color::White => tokens.push(format!("{}.", move_number)),
+            Color::Black if i == 0 || after_comment => tokens.push(format!("{}...", move_number)),
+            Color::Black => {}
+        }
+        tokens.push(m.clone());
+        let comment = annotations.get(i).and_then(|a| a.to_comment());
+        after_comment = comment.is_some();
+        if let Some(comment) = comment {
+            tokens.push(comment);
         }
+        if color == Color::Black {
+            move_number += 1;
+        }
+        color = color.opposite();
     }
+    tokens.push(String::from(result));
+    tokens.join(" ")
+}
 
-    return moves;
+// break the text into lines of at most width characters, only breaking on spaces
+pub fn wrap_move_text(text: &str, width: usize) -> String {
+    let mut lines: Vec<String> = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(line);
+            line = String::new();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines.join("\n")
 }
 
+// tag values are stored without the quotes, they're added when the game is printed
 pub struct Game {
+    // seven tag roster
     pub event: String,
     pub site: String,
     pub date: String,
@@ -230,136 +225,86 @@ pub struct Game {
     pub white: String,
     pub black: String,
     pub result: String,
+    pub time_control: Option<String>,
+    pub termination: Option<Termination>,
+    pub mode: Option<Mode>,
+    // only for games that don't start from the usual position
+    pub fen: Option<String>,
+    // anything else, written after the rest of the tags
+    pub extra_tags: Vec<(String, String)>,
     pub move_text: String,
 }
 
 impl Game {
-    pub fn new_from_pgn(pgn: String) -> chess_game {
-        // reading from a pgn time
-
-        // for the moment were skipping the informational section
-        // break pgn into lines then grab all the lines with move text in them
-        let lines: Vec<&str> = pgn.split('\n').collect();
-        let info_line_matcher = Regex::new(r"\[.*\]$").unwrap();
-        let mut move_lines: Vec<&str> = Vec::new();
-        let info_section = true;
-        let mut move_section = false;
-        for _s in pgn.split('\n').into_iter() {
-            if move_section {
-                move_lines.push(_s);
-            }
-            if !info_line_matcher.is_match(_s) && _s.is_empty() {
-                move_section = true;
-            }
-        }
-
-        // put all move text in a string
-        let mut move_text: String = String::new();
-        for _s in move_lines.into_iter() {
-            
-            move_text.push_str(_s);
-        }
-
-        // remove the comments
-        let comment_matcher = Regex::new(r"(\{[^*\}]*\})").unwrap();
-        let stuff: Vec<&str> = comment_matcher.split(&move_text).collect();
-
-        // put it in a string again
-        let mut move_text_2 = String::new();
-        for _s in stuff {
-            move_text_2.push_str(_s);
-        }
-
-        // take out all move turn stuff (eg. 1. 2. 3. )
-        let turn_matcher = Regex::new(r"(\d+\.)").unwrap();
-        let mut t = String::new();
-        let stuff: Vec<&str> = turn_matcher.split(move_text_2.as_str()).collect();
-        for _s in stuff {
-            t.push_str(_s);
-        }
-
-        //now we have mostly just move text and some $1, $2 stuff && empty lines
-        let move_candidates: Vec<&str> = t.split(' ').collect();
-        for _m in move_candidates {
-            // skip empty
-            if _m.is_empty() {
-                continue;
-            }
-            // if is valid move
-            // do stuff
-            
-            let res = read_move(_m);
-            if res.is_none() {
-                println!("MOVE NOT FOUND");
-            } else {
-                let (piece_type, coordinate, parsed_move, promotion_type) = res.unwrap();
-                // println!(
-                //     "{} {} {} ",
-                //     piece_type,
-                //     coordinate.unwrap_or(Coordinate::new(0, 0)),
-                //     promotion_type.unwrap_or(PieceType::Pawn)
-                // );
+    // plays out the main line of the first game in the pgn
+    pub fn new_from_pgn(pgn: String) -> Result<chess_game, PgnError> {
+        let games = pgn_parser::parse_pgn(pgn.as_str())?;
+        let pgn_game = match games.first() {
+            Some(pgn_game) => pgn_game,
+            None => {
+                return Err(PgnError {
+                    line: 1,
+                    column: 1,
+                    message: String::from("no games found"),
+                })
             }
+        };
+        let start = pgn_game.starting_position().map_err(|err| PgnError {
+            line: 1,
+            column: 1,
+            message: format!("bad FEN tag: {}", err),
+        })?;
+        let mut game = chess_game::new_from_game_state(start);
+        for mut m in pgn_game.main_line() {
+            game.make_move(&mut m);
         }
-        return chess_game::new();
+        Ok(game)
     }
     pub fn new_from_game(game: &chess_game) -> Game {
-        // partition and step_by work here ?
-        // try step_by and zip
-        // zip together moves
-        fn make_move_pairs(mut moves: Vec<String>) -> Vec<(String, Option<String>)> {
-            let mut pairs = Vec::new();
-            while moves.len() > 0 {
-                let first = moves.remove(0);
-                let second = if moves.len() > 0 {
-                    Some(moves.remove(0))
-                } else {
-                    None
-                };
-                pairs.push((first, second));
-            }
-            pairs
-        }
-        let pairs = make_move_pairs(game.moves());
-
-        let mut move_text =
-            pairs
-                .into_iter()
-                .enumerate()
-                .fold(String::new(), |mut acc, (idx, (white, black))| {
-                    // let black = black.unwrap_or(String::new());
-                    // let mut move_string = format!("{}. {} {} ", idx + 1, white, black);
-                    let mut move_string = format!("{}. {}", idx + 1, white);
-                    if black.is_some() {
-                        move_string.push_str(format!(" {} ", black.unwrap()).as_str())
-                    }
-                    acc.push_str(move_string.as_str());
-                    acc
-                });
-        match game.result() {
-            InProgress => move_text.push_str(" *"),
-            _ => (),
+        let (result, termination) = match game.result() {
+            GameResult::InProgress => ("*", Termination::Unterminated),
+            GameResult::Draw => ("1/2-1/2", game.termination()),
+            GameResult::Win {
+                winning_player: Color::White,
+            } => ("1-0", game.termination()),
+            GameResult::Win {
+                winning_player: Color::Black,
+            } => ("0-1", game.termination()),
         };
+        let fen = if game.start_fen() == fen_reader::INITIAL_BOARD {
+            None
+        } else {
+            Some(String::from(game.start_fen()))
+        };
+        let move_text =
+            make_move_text(&game.moves(), &game.annotations(), game.start_fen(), result);
 
         Game {
-            event: String::from(r#""""#),
-            site: String::from(r#""""#),
-            date: String::from(r#""""#),
-            round: String::from(r#""""#),
-            white: String::from(r#""""#),
-            black: String::from(r#""""#),
-            result: String::from(r#""""#),
+            event: String::from("Casual game"),
+            site: String::from("?"),
+            date: game.start_date(),
+            round: String::from("-"),
+            white: String::from(game.white_name()),
+            black: String::from(game.black_name()),
+            result: String::from(result),
+            time_control: Some(game.time_control().to_pgn_tag()),
+            termination: Some(termination),
+            mode: None,
+            fen,
+            extra_tags: vec![],
             move_text,
         }
     }
-    fn print_tag(&self, name: &str, value: &String) -> String {
-        format!("[{} {}]", name, value)
+
+    fn print_tag(&self, name: &str, value: &str) -> String {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("[{} \"{}\"]", name, value)
     }
 }
 
 impl fmt::Display for Game {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let tags = vec![
+        let mut tags = vec![
             self.print_tag("Event", &self.event),
             self.print_tag("Site", &self.site),
             self.print_tag("Date", &self.date),
@@ -368,15 +313,32 @@ impl fmt::Display for Game {
             self.print_tag("Black", &self.black),
             self.print_tag("Result", &self.result),
         ];
-        let str = format!("{}\n\n{}", tags.join("\n"), self.move_text);
-        write!(f, "{}", str)
+        if let Some(time_control) = &self.time_control {
+            tags.push(self.print_tag("TimeControl", time_control));
+        }
+        if let Some(termination) = self.termination {
+            tags.push(self.print_tag("Termination", termination.to_string().as_str()));
+        }
+        if let Some(mode) = self.mode {
+            tags.push(self.print_tag("Mode", mode.to_string().as_str()));
+        }
+        if let Some(fen) = &self.fen {
+            tags.push(self.print_tag("SetUp", "1"));
+            tags.push(self.print_tag("FEN", fen));
+        }
+        for (name, value) in &self.extra_tags {
+            tags.push(self.print_tag(name, value));
+        }
+        let move_text = wrap_move_text(self.move_text.as_str(), MAX_LINE_LENGTH);
+        write!(f, "{}\n\n{}", tags.join("\n"), move_text)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::board::CastlingRights;
+    use crate::board::{CastlingRights, Coordinate, PieceType};
+    use crate::move_generator::MoveType;
 
     #[test]
     fn move_from_game() {
@@ -663,26 +625,90 @@ mod tests {
 
     #[test]
     fn test_pgn() {
+        let move_text = TEST_PGN.split("\n\n").nth(1).unwrap();
         let game = Game {
-            event: String::from(r#""F/S Return Match""#),
-            site: String::from(r#""Belgrade, Serbia JUG""#),
-            date: String::from(r#""1992.11.04""#),
-            round: String::from(r#""29""#),
-            white: String::from(r#""Fischer, Robert J.""#),
-            black: String::from(r#""Spassky, Boris V.""#),
-            result: String::from(r#""1/2-1/2""#),
-            move_text: String::from(
-                "1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 {This opening is called the Ruy Lopez.}
-4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 10. d4 Nbd7
-11. c4 c6 12. cxb5 axb5 13. Nc3 Bb7 14. Bg5 b4 15. Nb1 h6 16. Bh4 c5 17. dxe5
-Nxe4 18. Bxe7 Qxe7 19. exd6 Qf6 20. Nbd2 Nxd6 21. Nc4 Nxc4 22. Bxc4 Nb6
-23. Ne5 Rae8 24. Bxf7+ Rxf7 25. Nxf7 Rxe1+ 26. Qxe1 Kxf7 27. Qe3 Qg5 28. Qxg5
-hxg5 29. b3 Ke6 30. a3 Kd6 31. axb4 cxb4 32. Ra5 Nd5 33. f3 Bc8 34. Kf2 Bf5
-35. Ra7 g6 36. Ra6+ Kc5 37. Ke1 Nf4 38. g3 Nxh3 39. Kd2 Kb5 40. Rd6 Kc5 41. Ra6
-Nf2 42. g4 Bd3 43. Re6 1/2-1/2",
-            ),
+            event: String::from("F/S Return Match"),
+            site: String::from("Belgrade, Serbia JUG"),
+            date: String::from("1992.11.04"),
+            round: String::from("29"),
+            white: String::from("Fischer, Robert J."),
+            black: String::from("Spassky, Boris V."),
+            result: String::from("1/2-1/2"),
+            time_control: None,
+            termination: None,
+            mode: None,
+            fen: None,
+            extra_tags: vec![],
+            move_text: move_text.replace('\n', " "),
         };
-        let str = String::from(TEST_PGN);
-        assert_eq!(str.to_string(), format!("{}", game).to_string())
+        let pgn = game.to_string();
+        let tags = TEST_PGN.split("\n\n").next().unwrap();
+        assert!(pgn.starts_with(tags));
+        assert!(pgn.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+
+        let games = pgn_parser::parse_pgn(pgn.as_str()).unwrap();
+        let original = pgn_parser::parse_pgn(TEST_PGN).unwrap();
+        assert_eq!(games, original);
+    }
+
+    #[test]
+    fn test_wrap_move_text() {
+        let text =
+            "1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 {This opening is called the Ruy Lopez.} 4. Ba4 Nf6";
+        assert_eq!(
+            wrap_move_text(text, 40),
+            "1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 {This\nopening is called the Ruy Lopez.} 4. Ba4\nNf6"
+        );
+    }
+
+    #[test]
+    fn test_make_move_text() {
+        let moves: Vec<String> = ["e5", "Nf3", "Nc6"]
+            .iter()
+            .map(|m| String::from(*m))
+            .collect();
+        let annotations = [
+            MoveAnnotation::default(),
+            MoveAnnotation {
+                eval: Some(-25.0),
+                clock: Some(Duration::from_secs(3725)),
+            },
+            MoveAnnotation {
+                eval: Some(0.0),
+                clock: None,
+            },
+        ];
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        assert_eq!(
+            make_move_text(&moves, &annotations, fen, "*"),
+            "1... e5 2. Nf3 {[%eval -0.25] [%clk 1:02:05]} 2... Nc6 {[%eval 0.00]} *"
+        );
+        let comments = [String::from("[%eval -0.25] [%clk 1:02:05]")];
+        assert_eq!(MoveAnnotation::from_comments(&comments), annotations[1]);
+        assert_eq!(MoveAnnotation::from_comments(&[]), annotations[0]);
+    }
+
+    #[test]
+    fn test_new_from_game() {
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1";
+        let mut game = chess_game::new_from_game_state(fen_reader::make_game_state(fen).unwrap());
+        game.set_player_names("White \"Quoted\"", "Black");
+        let pgn_game =
+            pgn_parser::parse_pgn(format!("[FEN \"{}\"]\n\n1. e4 Kd7 2. e5 *", fen).as_str())
+                .unwrap()
+                .remove(0);
+        for mut m in pgn_game.main_line() {
+            game.make_move(&mut m);
+        }
+        let pgn = Game::new_from_game(&game).to_string();
+        assert!(pgn.contains("[White \"White \\\"Quoted\\\"\"]"));
+        assert!(pgn.contains("[Termination \"unterminated\"]"));
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]"));
+        assert!(pgn.ends_with("\n\n1. e4 Kd7 2. e5 *"));
+
+        // reads back in the same
+        let read = pgn_parser::parse_pgn(pgn.as_str()).unwrap().remove(0);
+        assert_eq!(read.get_tag("White"), Some("White \"Quoted\""));
+        assert_eq!(read.main_line(), pgn_game.main_line());
     }
 }
@@ -0,0 +1,647 @@
+use crate::board::BoardTrait;
+use crate::chess_notation::fen_reader::{self, FenError};
+use crate::chess_notation::san;
+use crate::game_state::GameState;
+use crate::move_generator::Move;
+use std::fmt;
+use std::fmt::Formatter;
+use std::iter::Peekable;
+use std::mem;
+use std::str::Chars;
+
+/*
+pgn reader
+http://www.saremba.de/chessgml/standards/pgn/pgn-complete.htm
+
+two parts
+    Tokenizer : text => tokens ( [ ] "strings" symbols $nags {comments} ( ) ), keeps track of line and column
+    Parser    : tokens => games, every move gets checked against the position it's played in
+
+variations are alternatives to the move right before them, so in
+    1. e4 (1. d4 d5) e5
+d4 is stored as a variation on e4
+*/
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl PgnError {
+    fn new(line: usize, column: usize, message: &str) -> PgnError {
+        PgnError {
+            line,
+            column,
+            message: String::from(message),
+        }
+    }
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    TagStart,
+    TagEnd,
+    Str(String),
+    // move text, move numbers, tag names and results
+    Symbol(String),
+    Nag(u8),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+const FIGURINES: &str = "♔♕♖♗♘♙♚♛♜♝♞♟";
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_+#=:-/.".contains(c) || FIGURINES.contains(c)
+}
+
+// ! ? !! ?? !? ?! are short for $1 - $6
+fn suffix_to_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+pub struct Tokenizer<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(text: &'a str) -> Tokenizer<'a> {
+        Tokenizer {
+            chars: text.chars().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn read_while(&mut self, keep: fn(char) -> bool) -> String {
+        let mut str = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !keep(c) {
+                break;
+            }
+            str.push(c);
+            self.next_char();
+        }
+        str
+    }
+
+    fn read_token(&mut self) -> Option<Result<Token, PgnError>> {
+        loop {
+            let (line, column) = (self.line, self.column);
+            let c = *self.chars.peek()?;
+            let token = |kind| Some(Ok(Token { kind, line, column }));
+            let error = |message: &str| Some(Err(PgnError::new(line, column, message)));
+
+            // the \u{feff} is the byte order mark some windows programs start files with
+            if c.is_whitespace() || c == '\u{feff}' {
+                self.next_char();
+                continue;
+            }
+            // lines starting with % are escaped, skip them
+            if c == '%' && column == 1 {
+                self.read_while(|c| c != '\n');
+                continue;
+            }
+            if is_symbol_char(c) || c == '*' {
+                let symbol = if c == '*' {
+                    self.next_char();
+                    String::from("*")
+                } else {
+                    self.read_while(is_symbol_char)
+                };
+                return token(TokenKind::Symbol(symbol));
+            }
+
+            self.next_char();
+            return match c {
+                '[' => token(TokenKind::TagStart),
+                ']' => token(TokenKind::TagEnd),
+                '(' => token(TokenKind::VariationStart),
+                ')' => token(TokenKind::VariationEnd),
+                '"' => {
+                    let mut str = String::new();
+                    loop {
+                        match self.next_char() {
+                            None => return error("string is never closed"),
+                            Some('"') => break,
+                            Some('\\') => match self.next_char() {
+                                Some(escaped) => str.push(escaped),
+                                None => return error("string is never closed"),
+                            },
+                            Some(c) => str.push(c),
+                        }
+                    }
+                    token(TokenKind::Str(str))
+                }
+                '{' => {
+                    let comment = self.read_while(|c| c != '}');
+                    if self.next_char().is_none() {
+                        return error("comment is never closed");
+                    }
+                    token(TokenKind::Comment(String::from(comment.trim())))
+                }
+                ';' => {
+                    let comment = self.read_while(|c| c != '\n');
+                    token(TokenKind::Comment(String::from(comment.trim())))
+                }
+                '$' => match self.read_while(|c| c.is_ascii_digit()).parse::<u8>() {
+                    Ok(nag) => token(TokenKind::Nag(nag)),
+                    Err(_) => error("$ has to be followed by a number from 0 to 255"),
+                },
+                '!' | '?' => {
+                    let mut suffix = String::from(c);
+                    suffix.push_str(self.read_while(|c| c == '!' || c == '?').as_str());
+                    match suffix_to_nag(suffix.as_str()) {
+                        Some(nag) => token(TokenKind::Nag(nag)),
+                        None => error(format!("unknown annotation {}", suffix).as_str()),
+                    }
+                }
+                _ => error(format!("unexpected character {}", c).as_str()),
+            };
+        }
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_token()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnMove {
+    // the move as it was written in the file
+    pub san: String,
+    pub chess_move: Move,
+    pub nags: Vec<u8>,
+    pub comments_before: Vec<String>,
+    pub comments: Vec<String>,
+    // lines played instead of this move
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    fn new(san: &str, chess_move: Move, comments_before: Vec<String>) -> PgnMove {
+        PgnMove {
+            san: String::from(san),
+            chess_move,
+            nags: vec![],
+            comments_before,
+            comments: vec![],
+            variations: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnGame {
+    // in the order they're in the file
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<PgnMove>,
+    // comments that don't go with any move
+    pub comments: Vec<String>,
+    // NAGs before the first move, about the starting position rather than a move
+    pub nags: Vec<u8>,
+    // 1-0, 0-1, 1/2-1/2 or *
+    pub result: String,
+}
+
+impl PgnGame {
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        get_tag(&self.tags, name)
+    }
+
+    // the [FEN] tag if there is one, otherwise the usual starting position
+    pub fn starting_position(&self) -> Result<GameState, FenError> {
+        starting_position(&self.tags)
+    }
+
+    pub fn main_line(&self) -> Vec<Move> {
+        self.moves.iter().map(|m| m.chess_move).collect()
+    }
+
+    pub fn end_position(&self) -> Result<GameState, FenError> {
+        let mut game_state = self.starting_position()?;
+        for mut m in self.main_line() {
+            game_state.make_move_mut(&mut m);
+        }
+        Ok(game_state)
+    }
+}
+
+fn get_tag<'a>(tags: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    tags.iter()
+        .find(|(tag, _)| tag == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn starting_position(tags: &[(String, String)]) -> Result<GameState, FenError> {
+    match get_tag(tags, "FEN") {
+        Some(fen) => fen_reader::make_game_state(fen),
+        None => Ok(GameState::starting_game()),
+    }
+}
+
+fn is_result(symbol: &str) -> bool {
+    matches!(symbol, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+// "12." => "", "12...Nf6" => "Nf6", "1.e4" => "e4", "0-0" => "0-0"
+fn strip_move_number(symbol: &str) -> &str {
+    let rest = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+    if rest.len() == symbol.len() {
+        return symbol;
+    }
+    if rest.is_empty() || rest.starts_with('.') {
+        return rest.trim_start_matches('.');
+    }
+    symbol
+}
+
+// moves, the result if there was one, leftover comments, NAGs from before the first move
+type Line = (Vec<PgnMove>, Option<String>, Vec<String>, Vec<u8>);
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    // where the next token starts, or the end of the last one if we're out of tokens
+    fn error_here(&self, message: &str) -> PgnError {
+        match self
+            .tokens
+            .get(self.position)
+            .or_else(|| self.tokens.last())
+        {
+            Some(token) => PgnError::new(token.line, token.column, message),
+            None => PgnError::new(1, 1, message),
+        }
+    }
+
+    fn parse_tag(&mut self) -> Result<(String, String), PgnError> {
+        self.next_token();
+        let name = match self.peek().map(|t| &t.kind) {
+            Some(TokenKind::Symbol(name)) => name.clone(),
+            _ => return Err(self.error_here("expected a tag name")),
+        };
+        self.next_token();
+        let value = match self.peek().map(|t| &t.kind) {
+            Some(TokenKind::Str(value)) => value.clone(),
+            _ => return Err(self.error_here("expected the tag value in quotes")),
+        };
+        self.next_token();
+        match self.peek().map(|t| &t.kind) {
+            Some(TokenKind::TagEnd) => {
+                self.next_token();
+                Ok((name, value))
+            }
+            _ => Err(self.error_here("expected ] to close the tag")),
+        }
+    }
+
+    fn parse_game(&mut self) -> Result<Option<PgnGame>, PgnError> {
+        let mut comments = vec![];
+        let mut tags = vec![];
+        while let Some(token) = self.peek() {
+            match &token.kind {
+                TokenKind::TagStart => tags.push(self.parse_tag()?),
+                TokenKind::Comment(comment) if tags.is_empty() => {
+                    comments.push(comment.clone());
+                    self.next_token();
+                }
+                _ => break,
+            }
+        }
+
+        let mut game_state = starting_position(&tags)
+            .map_err(|err| self.error_here(format!("bad FEN tag: {}", err).as_str()))?;
+        let (moves, result, trailing_comments, nags) = self.parse_line(&mut game_state, 0)?;
+        comments.extend(trailing_comments);
+        if tags.is_empty() && moves.is_empty() && result.is_none() {
+            return Ok(None);
+        }
+        let result = result
+            .or_else(|| get_tag(&tags, "Result").map(String::from))
+            .unwrap_or_else(|| String::from("*"));
+        Ok(Some(PgnGame {
+            tags,
+            moves,
+            comments,
+            nags,
+            result,
+        }))
+    }
+
+    // reads moves until the end of the variation / game, checking each one on the game state
+    // the game state is put back how it was before returning
+    fn parse_line(&mut self, game_state: &mut GameState, depth: usize) -> Result<Line, PgnError> {
+        let mut moves: Vec<PgnMove> = vec![];
+        let mut pending_comments: Vec<String> = vec![];
+        let mut leading_nags: Vec<u8> = vec![];
+        let result = loop {
+            let token = match self.peek() {
+                Some(token) => token.clone(),
+                None if depth > 0 => return Err(self.error_here("variation is never closed")),
+                None => break None,
+            };
+            match token.kind {
+                TokenKind::TagStart if depth == 0 => break None,
+                TokenKind::TagStart => return Err(self.error_here("variation is never closed")),
+                TokenKind::TagEnd | TokenKind::Str(_) => {
+                    return Err(self.error_here("unexpected token in the move text"));
+                }
+                TokenKind::VariationEnd if depth == 0 => {
+                    return Err(self.error_here("unexpected ) with no variation to close"));
+                }
+                TokenKind::VariationEnd => {
+                    self.next_token();
+                    break None;
+                }
+                TokenKind::VariationStart => {
+                    let previous = match moves.last_mut() {
+                        Some(previous) => previous,
+                        None => return Err(self.error_here("a variation has to come after a move")),
+                    };
+                    self.next_token();
+                    game_state.unmake_move_mut(&mut previous.chess_move);
+                    let (variation, _, comments, nags) = self.parse_line(game_state, depth + 1)?;
+                    game_state.make_move_mut(&mut previous.chess_move);
+                    previous.comments.extend(comments);
+                    if !nags.is_empty() {
+                        return Err(self.error_here("a variation can't start with a NAG"));
+                    }
+                    if !variation.is_empty() {
+                        previous.variations.push(variation);
+                    }
+                }
+                TokenKind::Nag(nag) => {
+                    self.next_token();
+                    match moves.last_mut() {
+                        Some(previous) => previous.nags.push(nag),
+                        None => leading_nags.push(nag),
+                    }
+                }
+                TokenKind::Comment(comment) => {
+                    self.next_token();
+                    match moves.last_mut() {
+                        Some(previous) => previous.comments.push(comment),
+                        None => pending_comments.push(comment),
+                    }
+                }
+                TokenKind::Symbol(symbol) => {
+                    if is_result(symbol.as_str()) {
+                        if depth > 0 {
+                            return Err(self.error_here("a variation can't have a result"));
+                        }
+                        self.next_token();
+                        break Some(symbol);
+                    }
+                    let text = strip_move_number(symbol.as_str());
+                    if text.is_empty() || text == "e.p." {
+                        self.next_token();
+                        continue;
+                    }
+                    let mut m = match san::parse_san(text, game_state) {
+                        Ok(m) => m,
+                        Err(err) => return Err(self.error_here(err.to_string().as_str())),
+                    };
+                    self.next_token();
+                    game_state.make_move_mut(&mut m);
+                    moves.push(PgnMove::new(text, m, mem::take(&mut pending_comments)));
+                }
+            }
+        };
+
+        for m in moves.iter_mut().rev() {
+            game_state.unmake_move_mut(&mut m.chess_move);
+        }
+        Ok((moves, result, pending_comments, leading_nags))
+    }
+}
+
+pub fn tokenize(text: &str) -> Result<Vec<Token>, PgnError> {
+    Tokenizer::new(text).collect()
+}
+
+// just the [Name "value"] tags, nothing else is allowed in the text
+pub fn parse_tags(text: &str) -> Result<Vec<(String, String)>, PgnError> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        position: 0,
+    };
+    let mut tags = vec![];
+    while let Some(token) = parser.peek() {
+        match token.kind {
+            TokenKind::TagStart => tags.push(parser.parse_tag()?),
+            _ => return Err(parser.error_here("expected a tag")),
+        }
+    }
+    Ok(tags)
+}
+
+// every game in the text
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        position: 0,
+    };
+    let mut games = vec![];
+    while parser.peek().is_some() {
+        if let Some(game) = parser.parse_game()? {
+            games.push(game);
+        }
+    }
+    Ok(games)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::*;
+    use crate::chess_notation::move_to_san;
+
+    const GAMES: &str = r#"[Event "Variations"]
+[White "Someone \"Quoted\""]
+[Result "1-0"]
+
+{starts with a comment} 1. e4 $1 e5 (1... c5 2. Nf3 (2. Nc3 Nc6) d6; a comment to the end of the line
+) 2. Nf3 Nc6!? 3. Bb5 a6 {The Ruy Lopez} 1-0
+
+% escaped line
+[Event "Set up"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+[Result "*"]
+
+1. e4 Kd7 2. e5 *
+"#;
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize("[Event \"x\"]\n1. e4 $2 {hi} ?!").unwrap();
+        let kinds: Vec<TokenKind> = tokens.iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::TagStart,
+                TokenKind::Symbol(String::from("Event")),
+                TokenKind::Str(String::from("x")),
+                TokenKind::TagEnd,
+                TokenKind::Symbol(String::from("1.")),
+                TokenKind::Symbol(String::from("e4")),
+                TokenKind::Nag(2),
+                TokenKind::Comment(String::from("hi")),
+                TokenKind::Nag(6),
+            ]
+        );
+        assert_eq!((tokens[5].line, tokens[5].column), (2, 4));
+
+        let err = tokenize("1. e4 {never closed").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 7));
+        assert!(tokenize("1. e4 <").is_err());
+    }
+
+    #[test]
+    fn test_parse_pgn() {
+        let games = parse_pgn(GAMES).unwrap();
+        assert_eq!(games.len(), 2);
+
+        let game = &games[0];
+        assert_eq!(game.get_tag("White"), Some("Someone \"Quoted\""));
+        assert_eq!(game.result, "1-0");
+        let sans: Vec<&str> = game.moves.iter().map(|m| m.san.as_str()).collect();
+        assert_eq!(sans, vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"]);
+        assert_eq!(game.moves[0].comments_before, vec!["starts with a comment"]);
+        assert_eq!(game.moves[0].nags, vec![1]);
+        assert_eq!(game.moves[3].nags, vec![5]);
+        assert_eq!(game.moves[5].comments, vec!["The Ruy Lopez"]);
+
+        // 1... c5 is an alternative to 1... e5 with its own variation on 2. Nf3
+        let variation = &game.moves[1].variations[0];
+        assert_eq!(variation.len(), 3);
+        assert_eq!(variation[0].san, "c5");
+        assert_eq!(
+            variation[2].comments,
+            vec!["a comment to the end of the line"]
+        );
+        let nested = &variation[1].variations[0];
+        assert_eq!(nested[0].san, "Nc3");
+        assert_eq!(nested[1].chess_move.to, Coordinate::new(3, 6));
+
+        let game = &games[1];
+        assert_eq!(game.result, "*");
+        assert_eq!(game.moves.len(), 3);
+        let end = game.end_position().unwrap();
+        assert_eq!(
+            fen_reader::make_fen(&end),
+            "8/3k4/8/4P3/8/8/8/4K3 b - - 0 2"
+        );
+    }
+
+    #[test]
+    fn test_parse_pgn_errors() {
+        let err = parse_pgn("[Event \"x\"]\n\n1. e4 e5 2. Ke3 *").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 13));
+        assert!(err.message.contains("not a legal move"));
+
+        let err = parse_pgn("1. e4 (1. d4 d5 2. c4").unwrap_err();
+        assert_eq!(err.message, "variation is never closed");
+        let err = parse_pgn("1. e4 e5 )").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 10));
+        let err = parse_pgn("(1. d4) 1. e4").unwrap_err();
+        assert_eq!(err.message, "a variation has to come after a move");
+        let err = parse_pgn("[Event x]").unwrap_err();
+        assert_eq!(err.message, "expected the tag value in quotes");
+        let err = parse_pgn("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n\n*").unwrap_err();
+        assert_eq!(err.message, "bad FEN tag: white has 0 kings, should have 1");
+        let err = parse_pgn("1. e4 ($2 1. d4) *").unwrap_err();
+        assert_eq!(err.message, "a variation can't start with a NAG");
+    }
+
+    #[test]
+    fn test_before_the_first_move() {
+        let mut game = parse_pgn("[Event \"x\"]\n\n{intro} $14 1. e4 *")
+            .unwrap()
+            .remove(0);
+        assert_eq!(game.nags, vec![14]);
+        assert_eq!(game.moves[0].comments_before, vec!["intro"]);
+        assert!(game.moves[0].nags.is_empty());
+        // the tags can be changed after parsing, a broken FEN is an error not a panic
+        game.tags
+            .push((String::from("FEN"), String::from("not a fen")));
+        assert!(game.starting_position().is_err());
+        assert!(game.end_position().is_err());
+    }
+
+    #[test]
+    fn test_parse_game_files() {
+        let games = parse_pgn(include_str!("../../Games/1.pgn")).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].get_tag("White"), Some("Paul Morphy"));
+        assert_eq!(games[0].moves.len(), 33);
+        let last = games[0].moves.last().unwrap();
+        let mut game_state = games[0].starting_position().unwrap();
+        for m in games[0].moves[..32].iter() {
+            game_state.make_move_mut(&mut m.chess_move.clone());
+        }
+        assert_eq!(move_to_san(&last.chess_move, &game_state), "Rd8#");
+        assert_eq!(games[0].result, "1-0");
+
+        let games = parse_pgn(include_str!("../../Games/2.pgn")).unwrap();
+        assert_eq!(games.len(), 1);
+    }
+}
@@ -0,0 +1,412 @@
+use crate::chess_notation::pgn_parser::{self, PgnError, PgnGame};
+use std::io::{BufRead, BufReader, Read};
+
+/*
+streams games out of a pgn database one at a time so we never hold more than a single game in memory
+
+games are split on lines, a game is its tag lines plus the move text lines after them,
+the next tag line after move text starts the next game. tags are read for every game,
+the move text is only kept when it's needed, so filtering and skipping through a big file
+doesn't pay for checking moves
+
+    let reader = PgnReader::new(File::open("games.pgn")?).with_filter(GameFilter { ... });
+    for game in reader {
+        let game = game?.parse()?;
+    }
+*/
+
+// all the set fields have to match, a game missing the tag doesn't match
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GameFilter {
+    // part of the White or Black name, not case sensitive
+    pub player: Option<String>,
+    // 1-0, 0-1, 1/2-1/2 or *
+    pub result: Option<String>,
+    // ECO code or the start of one, "C4" matches C40 - C49
+    pub eco: Option<String>,
+    // inclusive, yyyy.mm.dd, unknown parts of the game's date (1858.??.??) count as 00
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+}
+
+fn get_tag<'a>(tags: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    tags.iter()
+        .find(|(tag, _)| tag == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn normalize_date(date: &str) -> String {
+    date.trim().replace('?', "0")
+}
+
+impl GameFilter {
+    pub fn matches(&self, tags: &[(String, String)]) -> bool {
+        if let Some(player) = &self.player {
+            let player = player.to_lowercase();
+            let plays = ["White", "Black"].iter().any(|side| {
+                get_tag(tags, side).is_some_and(|name| name.to_lowercase().contains(&player))
+            });
+            if !plays {
+                return false;
+            }
+        }
+        if let Some(result) = &self.result {
+            if get_tag(tags, "Result") != Some(result.as_str()) {
+                return false;
+            }
+        }
+        if let Some(eco) = &self.eco {
+            if !get_tag(tags, "ECO").is_some_and(|code| code.starts_with(eco.as_str())) {
+                return false;
+            }
+        }
+        if self.date_from.is_some() || self.date_to.is_some() {
+            let date = match get_tag(tags, "Date") {
+                Some(date) => normalize_date(date),
+                None => return false,
+            };
+            if let Some(from) = &self.date_from {
+                if date < normalize_date(from) {
+                    return false;
+                }
+            }
+            if let Some(to) = &self.date_to {
+                if date > normalize_date(to) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+// a game that's been split out of the file but not checked yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawGame {
+    pub tags: Vec<(String, String)>,
+    // the game as it is in the file, only the tag lines when reading headers only
+    pub text: String,
+    // line in the file the game starts on
+    pub line: usize,
+}
+
+impl RawGame {
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        get_tag(&self.tags, name)
+    }
+
+    // read and check the moves, errors have line numbers for the whole file
+    pub fn parse(&self) -> Result<PgnGame, PgnError> {
+        let offset = |mut err: PgnError| {
+            err.line += self.line - 1;
+            err
+        };
+        let mut games = pgn_parser::parse_pgn(self.text.as_str()).map_err(offset)?;
+        if games.is_empty() {
+            return Err(offset(PgnError {
+                line: 1,
+                column: 1,
+                message: String::from("no game found"),
+            }));
+        }
+        Ok(games.remove(0))
+    }
+}
+
+// tracks {} comments across lines so a comment line starting with [ isn't taken for a tag
+fn is_in_comment_after(line: &str, mut in_comment: bool) -> bool {
+    for c in line.chars() {
+        match c {
+            '}' if in_comment => in_comment = false,
+            '{' if !in_comment => in_comment = true,
+            ';' if !in_comment => break,
+            _ => {}
+        }
+    }
+    in_comment
+}
+
+pub struct PgnReader<R: Read> {
+    reader: BufReader<R>,
+    filter: Option<GameFilter>,
+    headers_only: bool,
+    line_number: usize,
+    // the first tag line of the next game, read while finishing the last one
+    next_line: Option<(usize, String)>,
+    buffer: Vec<u8>,
+}
+
+impl<R: Read> PgnReader<R> {
+    pub fn new(reader: R) -> PgnReader<R> {
+        PgnReader {
+            reader: BufReader::new(reader),
+            filter: None,
+            headers_only: false,
+            line_number: 0,
+            next_line: None,
+            buffer: vec![],
+        }
+    }
+
+    pub fn with_filter(mut self, filter: GameFilter) -> PgnReader<R> {
+        self.filter = Some(filter);
+        self
+    }
+
+    // don't keep the move text, much faster when you only want to look at the tags
+    pub fn headers_only(mut self) -> PgnReader<R> {
+        self.headers_only = true;
+        self
+    }
+
+    fn read_line(&mut self) -> Result<Option<(usize, String)>, PgnError> {
+        if let Some(line) = self.next_line.take() {
+            return Ok(Some(line));
+        }
+        self.buffer.clear();
+        match self.reader.read_until(b'\n', &mut self.buffer) {
+            Ok(0) => Ok(None),
+            Ok(_) => {
+                self.line_number += 1;
+                // not every database is utf-8, don't give up on the whole file for a bad name
+                let mut line = String::from_utf8_lossy(&self.buffer).into_owned();
+                if self.line_number == 1 && line.starts_with('\u{feff}') {
+                    line.remove(0);
+                }
+                Ok(Some((self.line_number, line)))
+            }
+            Err(err) => Err(PgnError {
+                line: self.line_number + 1,
+                column: 1,
+                message: err.to_string(),
+            }),
+        }
+    }
+
+    // the next game in the file whether it passes the filter or not
+    // matches is whether it passed, the move text is only kept for games that do
+    fn read_game(&mut self, keep_moves: bool) -> Option<Result<(RawGame, bool), PgnError>> {
+        let mut text = String::new();
+        let mut header = String::new();
+        let mut start_line = None;
+        let mut tags = None;
+        let mut in_comment = false;
+        loop {
+            let (line_number, line) = match self.read_line() {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(err) => return Some(Err(err)),
+            };
+            let is_tag = !in_comment && line.trim_start().starts_with('[');
+            if is_tag && tags.is_some() {
+                self.next_line = Some((line_number, line));
+                break;
+            }
+            if tags.is_none() && (is_tag || line.trim().is_empty()) {
+                if is_tag {
+                    start_line.get_or_insert(line_number);
+                    header.push_str(line.as_str());
+                }
+                if start_line.is_some() {
+                    text.push_str(line.as_str());
+                }
+                continue;
+            }
+
+            // first line of the move text, time to decide if we want this game
+            if tags.is_none() {
+                let start = *start_line.get_or_insert(line_number);
+                match pgn_parser::parse_tags(header.as_str()) {
+                    Ok(parsed) => tags = Some(parsed),
+                    Err(mut err) => {
+                        err.line += start - 1;
+                        return Some(Err(err));
+                    }
+                }
+            }
+            in_comment = is_in_comment_after(line.as_str(), in_comment);
+            if keep_moves && self.passes_filter(tags.as_ref().unwrap()) {
+                text.push_str(line.as_str());
+            }
+        }
+
+        let start_line = start_line?;
+        let tags = match tags {
+            Some(tags) => tags,
+            // only tags, no move text
+            None => match pgn_parser::parse_tags(header.as_str()) {
+                Ok(tags) => tags,
+                Err(mut err) => {
+                    err.line += start_line - 1;
+                    return Some(Err(err));
+                }
+            },
+        };
+        let matches = self.passes_filter(&tags);
+        Some(Ok((
+            RawGame {
+                tags,
+                text,
+                line: start_line,
+            },
+            matches,
+        )))
+    }
+
+    fn passes_filter(&self, tags: &[(String, String)]) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|filter| filter.matches(tags))
+    }
+
+    // skips the next n games that would have come out of the iterator
+    // returns how many were skipped, less than n if the file ran out
+    pub fn skip_games(&mut self, n: usize) -> Result<usize, PgnError> {
+        let mut skipped = 0;
+        while skipped < n {
+            match self.read_game(false) {
+                None => break,
+                Some(Err(err)) => return Err(err),
+                Some(Ok((_, true))) => skipped += 1,
+                Some(Ok((_, false))) => {}
+            }
+        }
+        Ok(skipped)
+    }
+}
+
+impl<R: Read> Iterator for PgnReader<R> {
+    type Item = Result<RawGame, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.read_game(!self.headers_only)? {
+                Ok((game, true)) => return Some(Ok(game)),
+                Ok((_, false)) => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATABASE: &str = r#"[Event "One"]
+[Date "1858.??.??"]
+[White "Paul Morphy"]
+[Black "Duke Karl"]
+[Result "1-0"]
+[ECO "C41"]
+
+1. e4 e5 2. Nf3 d6 {a comment
+[that looks like a tag]} 3. d4 1-0
+
+[Event "Two"]
+[Date "1972.07.11"]
+[White "Spassky, Boris"]
+[Black "Fischer, Robert"]
+[Result "0-1"]
+[ECO "D59"]
+
+1. d4 Nf6 2. c4 e6 0-1
+[Event "Three"]
+[Date "2021.05.04"]
+[White "horatiofox"]
+[Black "chessincheck"]
+[Result "*"]
+[ECO "B12"]
+
+1. e4 c6 2. Ke3 *
+"#;
+
+    fn reader() -> PgnReader<&'static [u8]> {
+        PgnReader::new(DATABASE.as_bytes())
+    }
+
+    #[test]
+    fn test_read_games() {
+        let games: Vec<RawGame> = reader().map(|g| g.unwrap()).collect();
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[0].get_tag("Event"), Some("One"));
+        assert_eq!(games[1].line, 11);
+        assert_eq!(games[0].parse().unwrap().moves.len(), 5);
+        assert_eq!(games[1].parse().unwrap().result, "0-1");
+
+        // the illegal king move is on line 26 of the whole file
+        let err = games[2].parse().unwrap_err();
+        assert_eq!((err.line, err.column), (26, 13));
+    }
+
+    #[test]
+    fn test_skip_and_headers_only() {
+        let mut games = reader();
+        assert_eq!(games.skip_games(2).unwrap(), 2);
+        assert_eq!(
+            games.next().unwrap().unwrap().get_tag("Event"),
+            Some("Three")
+        );
+        assert!(games.next().is_none());
+        assert_eq!(reader().skip_games(10).unwrap(), 3);
+
+        let headers: Vec<RawGame> = reader().headers_only().map(|g| g.unwrap()).collect();
+        assert_eq!(headers.len(), 3);
+        assert!(!headers[0].text.contains("e4"));
+        assert_eq!(headers[2].get_tag("ECO"), Some("B12"));
+    }
+
+    #[test]
+    fn test_filters() {
+        let events = |filter: GameFilter| -> Vec<String> {
+            reader()
+                .with_filter(filter)
+                .map(|g| String::from(g.unwrap().get_tag("Event").unwrap()))
+                .collect()
+        };
+        let player = GameFilter {
+            player: Some(String::from("fischer")),
+            ..GameFilter::default()
+        };
+        assert_eq!(events(player), vec!["Two"]);
+        let result = GameFilter {
+            result: Some(String::from("1-0")),
+            ..GameFilter::default()
+        };
+        assert_eq!(events(result), vec!["One"]);
+        let eco = GameFilter {
+            eco: Some(String::from("C4")),
+            ..GameFilter::default()
+        };
+        assert_eq!(events(eco), vec!["One"]);
+        let dates = GameFilter {
+            date_from: Some(String::from("1900.01.01")),
+            date_to: Some(String::from("2000.12.31")),
+            ..GameFilter::default()
+        };
+        assert_eq!(events(dates), vec!["Two"]);
+        let before = GameFilter {
+            date_to: Some(String::from("1858.12.31")),
+            ..GameFilter::default()
+        };
+        assert_eq!(events(before), vec!["One"]);
+
+        // the filtered out games don't count when skipping
+        let mut reader = reader().with_filter(GameFilter {
+            player: Some(String::from("o")),
+            ..GameFilter::default()
+        });
+        assert_eq!(reader.skip_games(1).unwrap(), 1);
+        let game = reader.next().unwrap().unwrap();
+        assert_eq!(game.get_tag("Event"), Some("Two"));
+        assert!(game.parse().is_ok());
+    }
+
+    #[test]
+    fn test_read_game_file() {
+        let file = include_bytes!("../../Games/2.pgn");
+        let games: Vec<RawGame> = PgnReader::new(&file[..]).map(|g| g.unwrap()).collect();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].parse().unwrap().moves.len(), 33);
+    }
+}
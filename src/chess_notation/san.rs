@@ -0,0 +1,448 @@
+use crate::board::*;
+use crate::game_state::GameState;
+use crate::move_generator::{gen_legal_moves, gen_pseudo_legal_moves, Move, MoveType};
+use std::fmt;
+use std::fmt::Formatter;
+
+/*
+standard algebraic notation parser
+https://en.wikipedia.org/wiki/Algebraic_notation_(chess)
+
+reading a move happens in two steps
+    read_san   : just the text, what piece, where it's going, any file / rank to disambiguate with
+    parse_san  : find the one legal move in the position that matches it
+
+besides plain SAN it also takes the stuff people (and other programs) actually write
+    0-0 and 0-0-0 for castles
+    e.p. after en passant captures
+    figurines (♘f3) instead of piece letters
+    promotions as e8=Q, e8Q, e8(Q) or fxe8N
+    long algebraic like e2e4, e2-e4 or Ng1-f3, bare squares (g1f3, e1g1) move whatever is there
+    +, #, !, ? at the end, these are ignored
+*/
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SanError {
+    // not a move at all, ex. "1-0", "$1", "Zz9"
+    Malformed(String),
+    // the player to move doesn't have the piece the move talks about
+    NoSuchPiece(String),
+    // reads fine, the piece is there, but the move isn't legal
+    Illegal(String),
+    // the piece can move there, but it would leave the king in check
+    LeavesKingInCheck(String),
+    // more than one legal move matches, ex. Nd7 when both knights can go there
+    Ambiguous(String),
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SanError::Malformed(san) => write!(f, "{} isn't a move I can read", san),
+            SanError::NoSuchPiece(san) => {
+                write!(f, "{}: there's no piece that could make that move", san)
+            }
+            SanError::Illegal(san) => write!(f, "{} is not a legal move", san),
+            SanError::LeavesKingInCheck(san) => {
+                write!(
+                    f,
+                    "{} is not a legal move, it leaves the king in check",
+                    san
+                )
+            }
+            SanError::Ambiguous(san) => write!(
+                f,
+                "{} is ambiguous, more than one piece can make that move",
+                san
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SanMove {
+    Castle {
+        king_side: bool,
+    },
+    Move {
+        piece: PieceType,
+        from_file: Option<u8>,
+        from_rank: Option<u8>,
+        capture: bool,
+        to: Coordinate,
+        promotion: Option<PieceType>,
+    },
+}
+
+fn read_piece_letter(c: char) -> Option<PieceType> {
+    match c {
+        'K' | '♔' | '♚' => Some(PieceType::King),
+        'Q' | '♕' | '♛' => Some(PieceType::Queen),
+        'R' | '♖' | '♜' => Some(PieceType::Rook),
+        'B' | '♗' | '♝' => Some(PieceType::Bishop),
+        'N' | '♘' | '♞' => Some(PieceType::Knight),
+        _ => None,
+    }
+}
+
+fn read_file(c: char) -> Option<u8> {
+    match c {
+        'a'..='h' => Some(c as u8 - b'a' + 1),
+        _ => None,
+    }
+}
+
+fn read_rank(c: char) -> Option<u8> {
+    match c {
+        '1'..='8' => Some(c as u8 - b'0'),
+        _ => None,
+    }
+}
+
+// reads the text of a move without looking at any position
+pub fn read_san(san: &str) -> Result<SanMove, SanError> {
+    let malformed = || SanError::Malformed(String::from(san));
+    let mut text = san.trim();
+    // check, mate and annotation marks
+    text = text.trim_end_matches(|c| "+#!?".contains(c));
+    if text.ends_with("e.p.") {
+        text = text[..text.len() - "e.p.".len()].trim_end();
+    }
+
+    match text {
+        "O-O" | "0-0" | "o-o" => return Ok(SanMove::Castle { king_side: true }),
+        "O-O-O" | "0-0-0" | "o-o-o" => return Ok(SanMove::Castle { king_side: false }),
+        _ => {}
+    }
+
+    // pawn figurines are just left off like a pawn letter would be
+    let mut chars: Vec<char> = text.chars().filter(|&c| c != '♙' && c != '♟').collect();
+    if chars.is_empty() {
+        return Err(malformed());
+    }
+    let piece = match read_piece_letter(chars[0]) {
+        Some(piece) => {
+            chars.remove(0);
+            piece
+        }
+        None => PieceType::Pawn,
+    };
+
+    // promotion on the end, e8=Q e8Q e8(Q)
+    let mut promotion = None;
+    if chars.last() == Some(&')') {
+        chars.pop();
+    }
+    if chars.len() > 2 {
+        let last = chars[chars.len() - 1];
+        let promoted_to = read_piece_letter(last.to_ascii_uppercase());
+        if promoted_to.is_some() && promoted_to != Some(PieceType::King) {
+            chars.pop();
+            if chars.last() == Some(&'(') {
+                chars.pop();
+            }
+            if chars.last() == Some(&'=') {
+                chars.pop();
+            }
+            promotion = promoted_to;
+        }
+    }
+
+    // the square we're going to is always last
+    if chars.len() < 2 {
+        return Err(malformed());
+    }
+    let to_rank = read_rank(chars.pop().unwrap()).ok_or_else(malformed)?;
+    let to_file = read_file(chars.pop().unwrap()).ok_or_else(malformed)?;
+    let to = Coordinate::new(to_file, to_rank);
+
+    // whatever's left is the optional from file, from rank and capture / dash
+    let mut capture = false;
+    if let Some(&last) = chars.last() {
+        if last == 'x' || last == ':' || last == '-' {
+            capture = last != '-';
+            chars.pop();
+        }
+    }
+    let mut rest = chars.into_iter().peekable();
+    let from_file = match rest.peek().and_then(|&c| read_file(c)) {
+        Some(file) => {
+            rest.next();
+            Some(file)
+        }
+        None => None,
+    };
+    let from_rank = match rest.peek().and_then(|&c| read_rank(c)) {
+        Some(rank) => {
+            rest.next();
+            Some(rank)
+        }
+        None => None,
+    };
+    if rest.next().is_some() {
+        return Err(malformed());
+    }
+
+    if promotion.is_some() && piece != PieceType::Pawn {
+        return Err(malformed());
+    }
+    Ok(SanMove::Move {
+        piece,
+        from_file,
+        from_rank,
+        capture,
+        to,
+        promotion,
+    })
+}
+
+// finds the legal move for the player to move that matches the san
+pub fn parse_san(san: &str, game_state: &GameState) -> Result<Move, SanError> {
+    parse_san_for(san, game_state, game_state.get_player_to_move())
+}
+
+pub fn parse_san_for(san: &str, game_state: &GameState, color: Color) -> Result<Move, SanError> {
+    let parsed = read_san(san)?;
+    let legal_moves = gen_legal_moves(game_state, color);
+    let (piece, from_file, from_rank, capture, to, promotion) = match parsed {
+        SanMove::Castle { king_side } => {
+            return legal_moves
+                .into_iter()
+                .find(|m| {
+                    if king_side {
+                        m.is_king_side_castle()
+                    } else {
+                        m.is_queen_side_castle()
+                    }
+                })
+                .ok_or_else(|| SanError::Illegal(String::from(san)));
+        }
+        SanMove::Move {
+            piece,
+            from_file,
+            from_rank,
+            capture,
+            to,
+            promotion,
+        } => (piece, from_file, from_rank, capture, to, promotion),
+    };
+
+    let from_matches = |at: &Coordinate| {
+        from_file.is_none_or(|file| at.x() == file) && from_rank.is_none_or(|rank| at.y() == rank)
+    };
+    // plain coordinates (g1f3, e1g1) don't say what piece it is, whatever is on the square moves
+    let coordinates = piece == PieceType::Pawn && from_file.is_some() && from_rank.is_some();
+    let san_matches = |m: &Move| {
+        let is_castle = matches!(m.move_type(), MoveType::Castling { .. });
+        let promotion_matches = match (promotion, m.move_type()) {
+            (None, _) => true,
+            (Some(wanted), MoveType::Promotion(promoted_to)) => wanted == *promoted_to,
+            (Some(_), _) => false,
+        };
+        (m.piece == piece || coordinates)
+            && m.to == to
+            && (!is_castle || coordinates)
+            && from_matches(&m.from)
+            && promotion_matches
+            && (!capture || m.captured.is_some())
+    };
+    let mut matches: Vec<Move> = legal_moves.into_iter().filter(|m| san_matches(m)).collect();
+
+    match matches.len() {
+        1 => Ok(matches.remove(0)),
+        0 => {
+            let has_piece = game_state
+                .get_all_pieces(color)
+                .iter()
+                .filter(|p| p.piece_type == piece || coordinates)
+                .any(|p| p.at().is_some_and(&from_matches));
+            // the pseudo legal moves ignore checks, if it's in there the king is the problem
+            let leaves_king_in_check = gen_pseudo_legal_moves(game_state, color)
+                .iter()
+                .any(&san_matches);
+            if leaves_king_in_check {
+                Err(SanError::LeavesKingInCheck(String::from(san)))
+            } else if has_piece {
+                Err(SanError::Illegal(String::from(san)))
+            } else {
+                Err(SanError::NoSuchPiece(String::from(san)))
+            }
+        }
+        _ => Err(SanError::Ambiguous(String::from(san))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_notation::fen_reader;
+
+    fn coordinate(str: &str) -> Coordinate {
+        Coordinate::from(str).unwrap()
+    }
+
+    #[test]
+    fn test_read_san() {
+        assert_eq!(
+            read_san("Nbd7"),
+            Ok(SanMove::Move {
+                piece: PieceType::Knight,
+                from_file: Some(2),
+                from_rank: None,
+                capture: false,
+                to: coordinate("d7"),
+                promotion: None,
+            })
+        );
+        assert_eq!(
+            read_san("fxe8N+"),
+            Ok(SanMove::Move {
+                piece: PieceType::Pawn,
+                from_file: Some(6),
+                from_rank: None,
+                capture: true,
+                to: coordinate("e8"),
+                promotion: Some(PieceType::Knight),
+            })
+        );
+        for promotion in ["e8=Q", "e8Q", "e8(Q)", "e8=q#"].iter() {
+            match read_san(promotion) {
+                Ok(SanMove::Move { promotion, .. }) => {
+                    assert_eq!(promotion, Some(PieceType::Queen))
+                }
+                other => panic!("{:?}", other),
+            }
+        }
+        assert_eq!(read_san("0-0"), Ok(SanMove::Castle { king_side: true }));
+        assert_eq!(read_san("O-O-O+"), Ok(SanMove::Castle { king_side: false }));
+        assert_eq!(read_san("♘f3"), read_san("Nf3"));
+        assert_eq!(read_san("R1a3!?"), read_san("R1a3"));
+        assert_eq!(read_san("exd6 e.p."), read_san("exd6"));
+        assert_eq!(read_san("e2-e4"), read_san("e2e4"));
+
+        for bad in [
+            "", "1-0", "1/2-1/2", "*", "$1", "Zz9", "e9", "Nf3=Q", "Kxe8xe7", "12.",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                read_san(bad),
+                Err(SanError::Malformed(String::from(*bad))),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_san() {
+        let game_state = GameState::starting_game();
+        let m = parse_san("Nf3", &game_state).unwrap();
+        assert_eq!(m.from, coordinate("g1"));
+        assert_eq!(m.to, coordinate("f3"));
+        let m = parse_san("e4", &game_state).unwrap();
+        assert_eq!(m.from, coordinate("e2"));
+        assert_eq!(
+            parse_san("Qh5", &game_state),
+            Err(SanError::Illegal(String::from("Qh5")))
+        );
+        assert_eq!(
+            parse_san(
+                "Nf3",
+                &fen_reader::make_game_state("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap()
+            ),
+            Err(SanError::NoSuchPiece(String::from("Nf3")))
+        );
+        assert_eq!(
+            parse_san("0-0", &game_state),
+            Err(SanError::Illegal(String::from("0-0")))
+        );
+        // the bishop is pinned, the king can't walk onto the rook's file
+        let pinned = fen_reader::make_game_state("3rk3/8/8/8/4r3/8/4B3/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            parse_san("Bf3", &pinned),
+            Err(SanError::LeavesKingInCheck(String::from("Bf3")))
+        );
+        assert_eq!(
+            parse_san("Kd1", &pinned),
+            Err(SanError::LeavesKingInCheck(String::from("Kd1")))
+        );
+        assert_eq!(parse_san("Kf1", &pinned).unwrap().to, coordinate("f1"));
+    }
+
+    #[test]
+    fn test_parse_san_disambiguation() {
+        // knights on b1, f1 and b3 can all go to d2
+        let game_state = fen_reader::make_game_state("4k3/8/8/8/8/1N6/8/1N2KN2 w - - 0 1").unwrap();
+        let ambiguous = ["Nd2", "Nbd2", "N1d2"];
+        for san in ambiguous.iter() {
+            assert_eq!(
+                parse_san(san, &game_state),
+                Err(SanError::Ambiguous(String::from(*san)))
+            );
+        }
+        assert_eq!(
+            parse_san("Nfd2", &game_state).unwrap().from,
+            coordinate("f1")
+        );
+        assert_eq!(
+            parse_san("N3d2", &game_state).unwrap().from,
+            coordinate("b3")
+        );
+        assert_eq!(
+            parse_san("Nb1d2", &game_state).unwrap().from,
+            coordinate("b1")
+        );
+        assert_eq!(
+            parse_san("Ncd2", &game_state),
+            Err(SanError::NoSuchPiece(String::from("Ncd2")))
+        );
+    }
+
+    #[test]
+    fn test_parse_san_special_moves() {
+        // castles
+        let fen = "r3k2r/pppq1ppp/2npbn2/2b1p3/2B1P3/2NPBN2/PPPQ1PPP/R3K2R w KQkq - 0 1";
+        let game_state = fen_reader::make_game_state(fen).unwrap();
+        assert!(parse_san("O-O", &game_state).unwrap().is_king_side_castle());
+        assert!(parse_san("0-0-0", &game_state)
+            .unwrap()
+            .is_queen_side_castle());
+        // and as coordinates, with anything else that's just squares
+        assert!(parse_san("e1g1", &game_state)
+            .unwrap()
+            .is_king_side_castle());
+        let m = parse_san("c3b5", &game_state).unwrap();
+        assert_eq!(m.piece, PieceType::Knight);
+        assert_eq!(
+            parse_san("c3c4", &game_state),
+            Err(SanError::Illegal(String::from("c3c4")))
+        );
+        assert_eq!(
+            parse_san("d5d4", &game_state),
+            Err(SanError::NoSuchPiece(String::from("d5d4")))
+        );
+
+        // en passant
+        let fen = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        let game_state = fen_reader::make_game_state(fen).unwrap();
+        let m = parse_san("exd6 e.p.", &game_state).unwrap();
+        assert_eq!(m.move_type(), &MoveType::EnPassant);
+
+        // promotions, with and without a capture
+        let fen = "3rk3/4P3/8/8/8/8/8/4K3 w - - 0 1";
+        let game_state = fen_reader::make_game_state(fen).unwrap();
+        let m = parse_san("exd8=N+", &game_state).unwrap();
+        assert_eq!(m.move_type(), &MoveType::Promotion(PieceType::Knight));
+        assert_eq!(m.captured, Some(PieceType::Rook));
+        let fen = "4k3/1P6/8/8/8/8/8/4K3 w - - 0 1";
+        let game_state = fen_reader::make_game_state(fen).unwrap();
+        let m = parse_san("b8Q", &game_state).unwrap();
+        assert_eq!(m.move_type(), &MoveType::Promotion(PieceType::Queen));
+        // have to say what it promotes to
+        assert_eq!(
+            parse_san("b8", &game_state),
+            Err(SanError::Ambiguous(String::from("b8")))
+        );
+    }
+}
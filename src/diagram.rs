@@ -0,0 +1,325 @@
+use crate::bit_board::BitBoard;
+use crate::board::{BoardTrait, Color, Coordinate, Piece, PieceType};
+use crate::chess_notation::fen_reader;
+use crate::move_generator::pin::Pin;
+use crate::move_generator::Move;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+/*
+board diagrams as standalone svg files, for reports and slides
+
+    Diagram::new()
+        .with_orientation(Color::Black)   black's side at the bottom
+        .with_coordinates(false)          no border with the rank and file labels
+        .with_move(&best)                 an arrow from the move's from square to its to square
+        .with_pins(&pins)                 the pinned pieces highlighted, an arrow from each pinner
+        .with_highlights(vec![e4, d5])
+        .write(&game_state, Path::new("position.svg"))
+
+the pieces are the unicode chess figurines drawn as text, any font with them in will do
+*/
+
+const LIGHT: &str = "#f0d9b5";
+const DARK: &str = "#b58863";
+const BORDER: &str = "#404040";
+const HIGHLIGHT: &str = "#f6e05e";
+const ARROW: &str = "#15781b";
+
+#[derive(Debug, Clone)]
+pub struct Diagram {
+    // in pixels
+    square_size: u32,
+    orientation: Color,
+    coordinates: bool,
+    arrows: Vec<(Coordinate, Coordinate)>,
+    highlights: Vec<Coordinate>,
+}
+
+impl Default for Diagram {
+    fn default() -> Self {
+        Diagram::new()
+    }
+}
+
+impl Diagram {
+    pub fn new() -> Diagram {
+        Diagram {
+            square_size: 45,
+            orientation: Color::White,
+            coordinates: true,
+            arrows: vec![],
+            highlights: vec![],
+        }
+    }
+
+    pub fn with_square_size(mut self, square_size: u32) -> Diagram {
+        self.square_size = square_size;
+        self
+    }
+
+    // the side at the bottom
+    pub fn with_orientation(mut self, bottom: Color) -> Diagram {
+        self.orientation = bottom;
+        self
+    }
+
+    pub fn with_coordinates(mut self, coordinates: bool) -> Diagram {
+        self.coordinates = coordinates;
+        self
+    }
+
+    pub fn with_arrow(mut self, from: Coordinate, to: Coordinate) -> Diagram {
+        self.arrows.push((from, to));
+        self
+    }
+
+    pub fn with_move(self, m: &Move) -> Diagram {
+        self.with_arrow(m.from, m.to)
+    }
+
+    pub fn with_highlights(mut self, highlights: Vec<Coordinate>) -> Diagram {
+        self.highlights.extend(highlights);
+        self
+    }
+
+    pub fn with_pins(mut self, pins: &[Pin]) -> Diagram {
+        for pin in pins.iter() {
+            self.highlights.push(pin.pinned_at);
+            if let (Some(from), Some(to)) = (pin.pinned_by.at(), pin.pinned_to.at()) {
+                self.arrows.push((*from, *to));
+            }
+        }
+        self
+    }
+
+    // the svg document for board
+    pub fn svg(&self, board: &dyn BoardTrait) -> String {
+        let size = self.square_size;
+        let border = self.border();
+        let width = size * 8 + border * 2;
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{0}\" viewBox=\"0 0 {0} {0}\">",
+            width
+        );
+        let _ = writeln!(
+            svg,
+            "<defs><marker id=\"arrowhead\" viewBox=\"0 0 10 10\" refX=\"5\" refY=\"5\" markerWidth=\"3\" markerHeight=\"3\" orient=\"auto\"><path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"{}\"/></marker></defs>",
+            ARROW
+        );
+        if self.coordinates {
+            let _ = writeln!(
+                svg,
+                "<rect x=\"0\" y=\"0\" width=\"{0}\" height=\"{0}\" fill=\"{1}\"/>",
+                width, BORDER
+            );
+        }
+        for y in 1..=8 {
+            for x in 1..=8 {
+                let at = Coordinate::new(x, y);
+                let (left, top) = self.corner(at);
+                let fill = match BitBoard::get_square_color_at(at) {
+                    Color::White => LIGHT,
+                    Color::Black => DARK,
+                };
+                let _ = writeln!(
+                    svg,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{2}\" height=\"{2}\" fill=\"{3}\"/>",
+                    left, top, size, fill
+                );
+                if self.highlights.contains(&at) {
+                    let _ = writeln!(
+                        svg,
+                        "<rect x=\"{}\" y=\"{}\" width=\"{2}\" height=\"{2}\" fill=\"{3}\" fill-opacity=\"0.6\"/>",
+                        left, top, size, HIGHLIGHT
+                    );
+                }
+            }
+        }
+        if self.coordinates {
+            self.write_coordinates(&mut svg);
+        }
+        for y in 1..=8 {
+            for x in 1..=8 {
+                let at = Coordinate::new(x, y);
+                if let Some(piece) = board.get_piece_at(&at) {
+                    self.write_piece(&mut svg, at, piece);
+                }
+            }
+        }
+        for &(from, to) in self.arrows.iter() {
+            self.write_arrow(&mut svg, from, to);
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    pub fn write(&self, board: &dyn BoardTrait, path: &Path) -> Result<(), String> {
+        fs::write(path, self.svg(board))
+            .map_err(|err| format!("couldn't write {}: {}", path.display(), err))
+    }
+
+    fn border(&self) -> u32 {
+        if self.coordinates {
+            self.square_size / 2
+        } else {
+            0
+        }
+    }
+
+    // the top left corner of a square
+    fn corner(&self, at: Coordinate) -> (u32, u32) {
+        let (column, row) = match self.orientation {
+            Color::White => (at.x() - 1, 8 - at.y()),
+            Color::Black => (8 - at.x(), at.y() - 1),
+        };
+        (
+            self.border() + column as u32 * self.square_size,
+            self.border() + row as u32 * self.square_size,
+        )
+    }
+
+    fn center(&self, at: Coordinate) -> (f32, f32) {
+        let (left, top) = self.corner(at);
+        let half = self.square_size as f32 / 2.0;
+        (left as f32 + half, top as f32 + half)
+    }
+
+    // rank numbers down the left, file letters along the bottom
+    fn write_coordinates(&self, svg: &mut String) {
+        let border = self.border() as f32;
+        let font_size = self.square_size as f32 * 0.35;
+        let bottom = border * 1.5 + self.square_size as f32 * 8.0;
+        for i in 1..=8 {
+            let (x, y) = self.center(Coordinate::new(i, i));
+            let _ = writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\" fill=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>",
+                border / 2.0, y, font_size, LIGHT, i
+            );
+            let _ = writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\" fill=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>",
+                x, bottom, font_size, LIGHT, (b'a' + i - 1) as char
+            );
+        }
+    }
+
+    // the solid figurines for both sides, white's filled in white with a black outline
+    fn write_piece(&self, svg: &mut String, at: Coordinate, piece: &Piece) {
+        let glyph = match piece.piece_type {
+            PieceType::King => "\u{265a}",
+            PieceType::Queen => "\u{265b}",
+            PieceType::Rook => "\u{265c}",
+            PieceType::Bishop => "\u{265d}",
+            PieceType::Knight => "\u{265e}",
+            // the text presentation selector, or some fonts draw an emoji pawn
+            PieceType::Pawn => "\u{265f}\u{fe0e}",
+        };
+        let (fill, stroke) = match piece.color {
+            Color::White => ("#ffffff", "#000000"),
+            Color::Black => ("#000000", "#000000"),
+        };
+        let (x, y) = self.center(at);
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" font-family=\"'DejaVu Sans', 'Segoe UI Symbol', serif\" font-size=\"{}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"1\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>",
+            x, y, self.square_size as f32 * 0.8, fill, stroke, glyph
+        );
+    }
+
+    // stops short of the middle of the to square so the head doesn't hide the piece
+    fn write_arrow(&self, svg: &mut String, from: Coordinate, to: Coordinate) {
+        let (x1, y1) = self.center(from);
+        let (x2, y2) = self.center(to);
+        let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt();
+        if length == 0.0 {
+            return;
+        }
+        let width = self.square_size as f32 * 0.2;
+        let shorten = (self.square_size as f32 * 0.3).min(length / 2.0);
+        let x2 = x2 - (x2 - x1) / length * shorten;
+        let y2 = y2 - (y2 - y1) / length * shorten;
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\" stroke-width=\"{}\" stroke-opacity=\"0.8\" marker-end=\"url(#arrowhead)\"/>",
+            x1, y1, x2, y2, ARROW, width
+        );
+    }
+}
+
+// the same thing straight from a fen
+pub fn fen_to_svg(fen: &str, diagram: &Diagram) -> Result<String, String> {
+    match fen_reader::make_game_state(fen) {
+        Ok(game_state) => Ok(diagram.svg(&game_state)),
+        Err(err) => Err(format!("bad fen {}: {}", fen, err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::GameState;
+    use crate::move_generator::find_pinned_pieces;
+
+    fn count(svg: &str, text: &str) -> usize {
+        svg.matches(text).count()
+    }
+
+    #[test]
+    fn test_svg() {
+        let svg = Diagram::new().svg(&GameState::starting_game());
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"404\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(count(&svg, LIGHT), 32 + 16);
+        assert_eq!(count(&svg, DARK), 32);
+        assert_eq!(count(&svg, "fill=\"#ffffff\""), 16);
+        assert_eq!(count(&svg, "\u{265a}"), 2);
+        assert_eq!(count(&svg, "\u{265f}"), 16);
+        assert_eq!(count(&svg, "<line"), 0);
+        // a1 is dark and at the bottom left, inside the border
+        assert!(svg.contains(&format!(
+            "<rect x=\"22\" y=\"337\" width=\"45\" height=\"45\" fill=\"{}\"/>",
+            DARK
+        )));
+
+        let svg = Diagram::new()
+            .with_coordinates(false)
+            .with_orientation(Color::Black)
+            .svg(&GameState::starting_game());
+        assert!(svg.contains("width=\"360\""));
+        assert!(!svg.contains("sans-serif"));
+        // a1 in the top right
+        assert!(svg.contains(&format!(
+            "<rect x=\"315\" y=\"0\" width=\"45\" height=\"45\" fill=\"{}\"/>",
+            DARK
+        )));
+    }
+
+    #[test]
+    fn test_arrows_and_highlights() {
+        let fen = "4k3/8/8/8/q7/8/2B5/3K4 w - - 0 1";
+        let game_state = fen_reader::make_game_state(fen).unwrap();
+        let pins = find_pinned_pieces(&game_state, Color::White);
+        assert_eq!(pins.len(), 1);
+        let diagram = Diagram::new()
+            .with_coordinates(false)
+            .with_pins(&pins)
+            .with_arrow(Coordinate::new(5, 1), Coordinate::new(5, 2));
+        let svg = diagram.svg(&game_state);
+        assert_eq!(count(&svg, "<line"), 2);
+        assert_eq!(count(&svg, HIGHLIGHT), 1);
+        // c2 highlighted, the pinned bishop
+        assert!(svg.contains(&format!(
+            "<rect x=\"90\" y=\"270\" width=\"45\" height=\"45\" fill=\"{}\"",
+            HIGHLIGHT
+        )));
+        // the arrow from a4 toward d1 starts in the middle of a4
+        assert!(svg.contains("<line x1=\"22.5\" y1=\"202.5\""));
+        assert_eq!(fen_to_svg(fen, &diagram), Ok(svg));
+        assert!(fen_to_svg("not a fen", &diagram).is_err());
+    }
+}
@@ -1,25 +1,107 @@
+pub mod clock;
+pub mod replay;
+pub mod tui;
 use crate::ai;
-use crate::ai::evaluator::evaluate;
+use crate::ai::book::{Book, BookSelection};
+use crate::ai::evaluator::{Evaluation, Evaluator};
+use crate::ai::tablebase::Tablebases;
+use crate::ai::AiSearch;
 use crate::board::*;
-use crate::board_console_printer::print_board;
+use crate::board_console_printer::BoardRenderer;
 use crate::chess_notation;
-use crate::chess_notation::pgn::Game as PgnGame;
-use crate::chess_notation::{fen_reader, parse_move, print_move};
+use crate::chess_notation::pgn::{format_clock, Game as PgnGame, MoveAnnotation, Termination};
+use crate::chess_notation::pgn_parser;
+use crate::chess_notation::{fen_reader, move_to_san, san};
 use crate::game_state::GameState;
-use crate::move_generator::Move;
+use crate::move_generator::{gen_legal_moves, generate_checks, Move};
 use chrono::{DateTime, Local};
 use std::fs::{self, File, Metadata};
 use std::io;
 use std::io::prelude::*;
 use std::path::Path;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-pub struct Player {
-    time_used: u16,      // milliseconds
-    time_remaining: u16, // milliseconds
-    name: String,
+pub use clock::{Player, TimeControl};
+
+// how a turn went
+enum Turn {
+    Moved,
+    OutOfTime,
+    // out of input, or the ai had nothing to play, the game stops where it is
+    Stopped,
+    // somebody resigned or a draw was agreed
+    Ended(GameResult, String),
+}
+
+// what running a command came to
+enum Reply {
+    // something to tell the player, it's still their move
+    Say(String),
+    Done(Turn),
+}
+
+// what can be typed in place of a move
+enum Command {
+    Undo,
+    Hint,
+    Resign,
+    Draw,
+    Flip,
+    Fen,
+    Pgn,
+    Moves,
+    Eval,
+    Save(String),
+    Help,
+}
+
+impl Command {
+    fn parse(text: &str) -> Option<Command> {
+        if let Some(("save", path)) = text.split_once(' ') {
+            return Some(Command::Save(String::from(path.trim())));
+        }
+        match text.to_lowercase().as_str() {
+            "undo" | "takeback" => Some(Command::Undo),
+            "hint" => Some(Command::Hint),
+            "resign" => Some(Command::Resign),
+            "draw" => Some(Command::Draw),
+            "flip" => Some(Command::Flip),
+            "fen" => Some(Command::Fen),
+            "pgn" => Some(Command::Pgn),
+            "moves" => Some(Command::Moves),
+            "eval" => Some(Command::Eval),
+            "save" => Some(Command::Save(String::new())),
+            "help" | "?" => Some(Command::Help),
+            _ => None,
+        }
+    }
+}
+
+const HELP: &str = "type a move, like Nf3 or g1f3, or one of
+    undo     take back your last move and the reply to it
+    hint     ask the engine what it would play
+    resign   give up the game
+    draw     offer a draw, the engine takes it unless it thinks it's better
+    flip     turn the board around
+    fen      the position as a fen
+    pgn      the game so far as a pgn
+    moves    list the legal moves
+    eval     what the engine thinks of the position
+    save <file>  write the game out, carry on with it later with --resume <file>
+    help     this";
+
+// a score for people, +1.50 is white a pawn and a half up
+pub(crate) fn describe_score(evaluation: &Evaluation) -> String {
+    match evaluation.mated_player {
+        Some(mated_player) => format!("{} gets mated", mated_player),
+        None => format!("{:+.2}", evaluation.score / 100.0 + 0.0),
+    }
 }
 
+// the ai takes a draw when its score is at most this, centipawns from its side
+const DRAW_ACCEPT_SCORE: f32 = 0.0;
+
 #[derive(Debug, Copy, Clone)]
 pub enum GameResult {
     InProgress,
@@ -41,30 +123,409 @@ pub enum GameResult {
 pub struct Game {
     board: GameState,
     moves: Vec<String>,
+    // eval / clock for each move in moves, for the pgn
+    annotations: Vec<MoveAnnotation>,
+    start_fen: String,
+    white: Player,
+    black: Player,
+    time_control: TimeControl,
+    // when the player to move's clock started
+    turn_started: Instant,
+    termination: Termination,
+    // the side the person plays in run_human_versus_ai
+    human_color: Color,
     ai: ai::Ai,
     ai2: ai::Ai,
     start_time: String,
     result: GameResult,
     enable_logging: bool,
     game_start: Instant,
+    // position part of the fen after every ply, for spotting repetitions
+    positions: Vec<String>,
+    // the moves made, for taking them back
+    played: Vec<Move>,
+    // a draw offer the other side hasn't answered yet
+    draw_offer: Option<Color>,
+    // how the board is printed, flipped turns it round
+    renderer: BoardRenderer,
+    flipped: bool,
+    // the sides people are playing, the ais play the rest
+    humans: Vec<Color>,
+}
+
+// the fen without the move counters, the same position has the same key
+fn position_key(board: &GameState) -> String {
+    let fen = fen_reader::make_fen(board);
+    fen.split_whitespace()
+        .take(4)
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn is_light_square(at: &Coordinate) -> bool {
+    (at.x() + at.y()) % 2 == 1
+}
+
+// can this side mate by any series of legal moves, with the other side's pieces allowed to get
+// in their own king's way. a lone king can't, a lone knight can't mate a lone king and bishops
+// can't mate anything that only has bishops if every bishop is on the same coloured squares
+pub fn has_mating_material(board: &GameState, color: Color) -> bool {
+    let heavy_or_pawns = [PieceType::Queen, PieceType::Rook, PieceType::Pawn]
+        .iter()
+        .any(|&piece_type| !board.get_pieces(color, piece_type).is_empty());
+    if heavy_or_pawns {
+        return true;
+    }
+    let knights = board.get_pieces(color, PieceType::Knight);
+    let bishops = board.get_pieces(color, PieceType::Bishop);
+    if knights.is_empty() && bishops.is_empty() {
+        return false;
+    }
+    let defenders = board.get_all_pieces(color.opposite());
+    if !knights.is_empty() {
+        // anything next to the king can be the square it gets stuck on
+        return knights.len() + bishops.len() > 1 || defenders.len() > 1;
+    }
+    let defender_only_bishops = defenders
+        .iter()
+        .all(|piece| piece.piece_type == PieceType::King || piece.piece_type == PieceType::Bishop);
+    if !defender_only_bishops {
+        return true;
+    }
+    let all_bishops: Vec<&Piece> = bishops
+        .iter()
+        .chain(board.get_pieces(color.opposite(), PieceType::Bishop).iter())
+        .copied()
+        .collect();
+    let light = all_bishops
+        .iter()
+        .filter(|bishop| is_light_square(bishop.at().unwrap()))
+        .count();
+    light > 0 && light < all_bishops.len()
+}
+
+// nobody can mate no matter what gets played, K v K, K and a minor piece v K
+// or only bishops left and they're all on the same coloured squares
+pub fn is_insufficient_material(board: &GameState) -> bool {
+    let colors = [Color::White, Color::Black];
+    let heavy_or_pawns = colors.iter().any(|&color| {
+        [PieceType::Queen, PieceType::Rook, PieceType::Pawn]
+            .iter()
+            .any(|&piece_type| !board.get_pieces(color, piece_type).is_empty())
+    });
+    if heavy_or_pawns {
+        return false;
+    }
+    let knights: usize = colors
+        .iter()
+        .map(|&color| board.get_pieces(color, PieceType::Knight).len())
+        .sum();
+    let bishops: Vec<&Piece> = colors
+        .iter()
+        .flat_map(|&color| board.get_pieces(color, PieceType::Bishop))
+        .collect();
+    if knights + bishops.len() <= 1 {
+        return true;
+    }
+    let light = bishops
+        .iter()
+        .filter(|bishop| is_light_square(bishop.at().unwrap()))
+        .count();
+    knights == 0 && (light == 0 || light == bishops.len())
 }
 
 impl Game {
     pub fn new() -> Game {
+        Game::new_from_game_state(GameState::starting_game())
+    }
+
+    pub fn new_from_game_state(board: GameState) -> Game {
         let mut ai = ai::Ai::new(Color::Black);
         ai.default_search_depth = 4;
         let mut ai2 = ai::Ai::new(Color::White);
         ai2.default_search_depth = 4;
         Game {
-            board: GameState::starting_game(),
+            start_fen: fen_reader::make_fen(&board),
+            positions: vec![position_key(&board)],
+            board,
             ai,
             ai2,
             moves: vec![],
+            annotations: vec![],
+            white: Player::new("?", TimeControl::Unlimited),
+            black: Player::new("?", TimeControl::Unlimited),
+            time_control: TimeControl::Unlimited,
+            turn_started: Instant::now(),
+            termination: Termination::Normal,
+            human_color: Color::White,
             start_time: Local::now().format("%Y-%m-%d_%H%M%S").to_string(),
             result: GameResult::InProgress,
             enable_logging: false,
             game_start: Instant::now(),
+            played: vec![],
+            draw_offer: None,
+            renderer: BoardRenderer::new(),
+            flipped: false,
+            humans: vec![],
+        }
+    }
+
+    pub fn new_from_fen(fen: &str) -> Result<Game, String> {
+        match fen_reader::make_game_state(fen) {
+            Ok(board) => Ok(Game::new_from_game_state(board)),
+            Err(err) => Err(format!("bad fen {}: {}", fen, err)),
+        }
+    }
+
+    // the position in the pgn's first game right before color plays move_number
+    pub fn new_from_pgn_at(pgn: &str, move_number: u16, color: Color) -> Result<Game, String> {
+        let games = pgn_parser::parse_pgn(pgn).map_err(|err| err.to_string())?;
+        let pgn_game = match games.first() {
+            Some(pgn_game) => pgn_game,
+            None => return Err(String::from("no games in the pgn")),
+        };
+        let mut board = pgn_game
+            .starting_position()
+            .map_err(|err| err.to_string())?;
+        let mut moves = pgn_game.main_line().into_iter();
+        loop {
+            if board.get_full_move_number() == move_number && board.get_player_to_move() == color {
+                return Ok(Game::new_from_game_state(board));
+            }
+            match moves.next() {
+                Some(mut m) => board.make_move_mut(&mut m),
+                None => {
+                    let dots = if color == Color::White { "." } else { "..." };
+                    return Err(format!("the game never gets to {}{}", move_number, dots));
+                }
+            }
+        }
+    }
+
+    // which side the person plays in run_human_versus_ai, the ai takes the other one
+    pub fn with_human_playing(mut self, color: Color) -> Game {
+        self.human_color = color;
+        self
+    }
+
+    // how the board is drawn after every move
+    pub fn with_board_renderer(mut self, renderer: BoardRenderer) -> Game {
+        self.renderer = renderer;
+        self
+    }
+
+    // how many plies deep the ai playing color searches when it's not on a clock
+    pub fn with_ai_depth(mut self, color: Color, depth: u8) -> Game {
+        self.ai_mut(color).default_search_depth = depth;
+        self
+    }
+
+    // the ai playing color thinks this long a move, less if its clock is running low
+    pub fn with_ai_move_time(mut self, color: Color, move_time: Duration) -> Game {
+        self.ai_mut(color).set_move_time(Some(move_time));
+        self
+    }
+
+    pub fn with_ai_search(mut self, color: Color, search: AiSearch) -> Game {
+        self.ai_mut(color).set_search(search);
+        self
+    }
+
+    // settings like depth=6,movetime=500,search=minimax for the ai playing color
+    pub fn with_ai_settings(mut self, color: Color, settings: &str) -> Result<Game, String> {
+        self.ai_mut(color).apply_settings(settings)?;
+        Ok(self)
+    }
+
+    // the ai playing color's settings, in the form with_ai_settings takes
+    pub fn ai_settings(&self, color: Color) -> String {
+        let ai = self.ai_for(color);
+        let mut settings = format!(
+            "depth={},search={}",
+            ai.default_search_depth,
+            ai.search_function()
+        );
+        if let Some(move_time) = ai.move_time() {
+            settings.push_str(format!(",movetime={}", move_time.as_millis()).as_str());
         }
+        settings
+    }
+
+    // the pgn with extra tags for what the pgn doesn't keep, the clocks to the millisecond,
+    // the ai settings and who's playing, so resume_from_pgn can carry on from it
+    pub fn to_saved_pgn(&self) -> String {
+        let mut pgn_game = PgnGame::new_from_game(self);
+        let humans = match self.humans.as_slice() {
+            [] => String::from("none"),
+            [color] => color.to_string(),
+            _ => String::from("both"),
+        };
+        let mut tags = vec![
+            ("ResumeTimeControl", self.time_control.to_string()),
+            ("ResumeHumans", humans),
+        ];
+        for &(color, clock, time_used, ai) in [
+            (
+                Color::White,
+                "ResumeWhiteClock",
+                "ResumeWhiteTimeUsed",
+                "ResumeWhiteAi",
+            ),
+            (
+                Color::Black,
+                "ResumeBlackClock",
+                "ResumeBlackTimeUsed",
+                "ResumeBlackAi",
+            ),
+        ]
+        .iter()
+        {
+            tags.push((clock, format!("{:.3}", self.clock(color).as_secs_f64())));
+            let used = self.player(color).time_used();
+            tags.push((time_used, format!("{:.3}", used.as_secs_f64())));
+            tags.push((ai, self.ai_settings(color)));
+        }
+        pgn_game.extra_tags = tags
+            .into_iter()
+            .map(|(name, value)| (String::from(name), value))
+            .collect();
+        pgn_game.to_string()
+    }
+
+    // writes the game so it can be picked back up with --resume
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        match fs::write(path, format!("{}\n", self.to_saved_pgn())) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(format!("couldn't write {}: {}", path, err)),
+        }
+    }
+
+    // an unfinished game from a pgn, everything comes back from to_saved_pgn's tags
+    // a plain pgn works too, the clocks come from its [%clk] comments if it has them
+    pub fn resume_from_pgn(pgn: &str) -> Result<Game, String> {
+        let games = pgn_parser::parse_pgn(pgn).map_err(|err| err.to_string())?;
+        let pgn_game = match games.first() {
+            Some(pgn_game) => pgn_game,
+            None => return Err(String::from("no games in the pgn")),
+        };
+        if pgn_game.result != "*" {
+            return Err(format!("that game's over, it finished {}", pgn_game.result));
+        }
+        let starting_position = pgn_game
+            .starting_position()
+            .map_err(|err| err.to_string())?;
+        let first_to_move = starting_position.get_player_to_move();
+        let mut game = Game::new_from_game_state(starting_position);
+        let time_control = match pgn_game.get_tag("ResumeTimeControl") {
+            Some(text) => TimeControl::parse(text)?,
+            None => pgn_game
+                .get_tag("TimeControl")
+                .and_then(|text| TimeControl::parse(text).ok())
+                .unwrap_or(TimeControl::Unlimited),
+        };
+        game.set_time_control(time_control);
+        if let (Some(white), Some(black)) = (pgn_game.get_tag("White"), pgn_game.get_tag("Black")) {
+            game.set_player_names(white, black);
+        }
+        for &(color, name) in [
+            (Color::White, "ResumeWhiteAi"),
+            (Color::Black, "ResumeBlackAi"),
+        ]
+        .iter()
+        {
+            if let Some(settings) = pgn_game.get_tag(name) {
+                game = game.with_ai_settings(color, settings)?;
+            }
+        }
+        for pgn_move in &pgn_game.moves {
+            let mut m = pgn_move.chess_move;
+            game.play_move(&mut m, MoveAnnotation::from_comments(&pgn_move.comments));
+        }
+
+        let seconds = |name: &str| -> Result<Option<Duration>, String> {
+            match pgn_game.get_tag(name).map(|text| text.parse::<f64>()) {
+                None => Ok(None),
+                Some(Ok(seconds)) if seconds >= 0.0 => Ok(Some(Duration::from_secs_f64(seconds))),
+                _ => Err(format!("bad {} tag", name)),
+            }
+        };
+        for &(color, clock, time_used) in [
+            (Color::White, "ResumeWhiteClock", "ResumeWhiteTimeUsed"),
+            (Color::Black, "ResumeBlackClock", "ResumeBlackTimeUsed"),
+        ]
+        .iter()
+        {
+            // the plies color played, every other one starting from whoever moved first
+            let skip = if color == first_to_move { 0 } else { 1 };
+            let annotations: Vec<&MoveAnnotation> =
+                game.annotations.iter().skip(skip).step_by(2).collect();
+            let last_clock = annotations
+                .iter()
+                .rev()
+                .find_map(|annotation| annotation.clock);
+            let player = game.player(color);
+            let remaining = seconds(clock)?
+                .or(last_clock)
+                .unwrap_or_else(|| player.time_remaining());
+            let used = seconds(time_used)?.unwrap_or_else(|| player.time_used());
+            let moves_made = annotations.len() as u32;
+            game.player_mut(color)
+                .set_clock(remaining, used, moves_made);
+        }
+
+        game.humans = match pgn_game.get_tag("ResumeHumans") {
+            None | Some("white") => vec![Color::White],
+            Some("black") => vec![Color::Black],
+            Some("both") => vec![Color::White, Color::Black],
+            Some("none") => vec![],
+            Some(other) => return Err(format!("bad ResumeHumans tag {}", other)),
+        };
+        if let Some(&human) = game.humans.first() {
+            game.human_color = human;
+        }
+        Ok(game)
+    }
+
+    pub fn human_color(&self) -> Color {
+        self.human_color
+    }
+
+    // the sides people played last time the game was played, see resume_from_pgn
+    pub fn humans(&self) -> &[Color] {
+        self.humans.as_slice()
+    }
+
+    pub fn board(&self) -> &GameState {
+        &self.board
+    }
+
+    // swap out how the ai playing color evaluates positions
+    pub fn set_ai_evaluator(&mut self, color: Color, evaluator: Box<dyn Evaluator>) {
+        if self.ai.color() == color {
+            self.ai.set_evaluator(evaluator);
+        } else {
+            self.ai2.set_evaluator(evaluator);
+        }
+    }
+
+    // have the ai playing color use a network for its evaluation, None for the hand written one
+    pub fn set_ai_nnue(&mut self, color: Color, network: Option<Arc<ai::nnue::Network>>) {
+        if self.ai.color() == color {
+            self.ai.set_nnue(network);
+        } else {
+            self.ai2.set_nnue(network);
+        }
+    }
+
+    // both ais play out of the book for the first max_plies plies
+    pub fn set_ai_book(&mut self, book: Arc<Book>, max_plies: u16, selection: BookSelection) {
+        self.ai.set_book(book.clone(), max_plies, selection);
+        self.ai2.set_book(book, max_plies, selection);
+    }
+
+    pub fn set_ai_tablebases(&mut self, tablebases: Arc<Tablebases>) {
+        self.ai.set_tablebases(tablebases.clone());
+        self.ai2.set_tablebases(tablebases);
     }
 
     pub fn get_turn(&self) -> u32 {
@@ -83,11 +544,192 @@ impl Game {
         self.moves.clone()
     }
 
-    pub fn make_move(&mut self, move_: &mut Move) {
-        let log = print_move(&move_, &self.board);
-        println!("move = \n{}", log);
+    pub fn annotations(&self) -> Vec<MoveAnnotation> {
+        self.annotations.clone()
+    }
+
+    // yyyy.mm.dd, like the pgn Date tag
+    pub fn start_date(&self) -> String {
+        self.start_time[..10].replace('-', ".")
+    }
+
+    // fen of the position the game started from
+    pub fn start_fen(&self) -> &str {
+        self.start_fen.as_str()
+    }
+
+    pub fn white_name(&self) -> &str {
+        self.white.name()
+    }
+
+    pub fn black_name(&self) -> &str {
+        self.black.name()
+    }
+
+    pub fn set_player_names(&mut self, white: &str, black: &str) {
+        self.white.set_name(white);
+        self.black.set_name(black);
+    }
+
+    pub fn player(&self, color: Color) -> &Player {
+        match color {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        }
+    }
+
+    fn player_mut(&mut self, color: Color) -> &mut Player {
+        match color {
+            Color::White => &mut self.white,
+            Color::Black => &mut self.black,
+        }
+    }
+
+    // both clocks start over with the time control's base time
+    pub fn set_time_control(&mut self, time_control: TimeControl) {
+        self.time_control = time_control;
+        self.white = Player::new(self.white.name(), time_control);
+        self.black = Player::new(self.black.name(), time_control);
+    }
+
+    pub fn time_control(&self) -> TimeControl {
+        self.time_control
+    }
+
+    // normal, or time forfeit if someone's flag fell
+    pub fn termination(&self) -> Termination {
+        self.termination
+    }
+
+    // what color's clock shows right now, the player to move's is running
+    pub fn clock(&self, color: Color) -> Duration {
+        let player = self.player(color);
+        if color == self.board.get_player_to_move() {
+            player.time_left_while_thinking(self.turn_started.elapsed())
+        } else {
+            player.time_remaining()
+        }
+    }
+
+    fn print_clocks(&self) {
+        if self.time_control != TimeControl::Unlimited {
+            println!(
+                "White {}  Black {}",
+                format_clock(self.clock(Color::White)),
+                format_clock(self.clock(Color::Black))
+            );
+        }
+    }
+
+    // stops the running clock and starts the other one, false if the flag fell first
+    fn stop_clock(&mut self) -> bool {
+        let took = self.turn_started.elapsed();
+        let color = self.board.get_player_to_move();
+        let on_time = self.player_mut(color).finish_move(took);
+        self.turn_started = Instant::now();
+        on_time
+    }
+
+    fn log_move(&mut self, log: String, annotation: MoveAnnotation) {
         self.moves.push(log);
+        self.annotations.push(annotation);
+    }
+
+    // what the ai thought of the position after its move and what's left on the clock
+    fn annotation(&self, color: Color, eval: Option<f32>) -> MoveAnnotation {
+        let player = self.player(color);
+        MoveAnnotation {
+            eval,
+            clock: if player.is_timed() {
+                Some(player.time_remaining())
+            } else {
+                None
+            },
+        }
+    }
+
+    // logs the move then makes it, returns the san
+    fn play_move(&mut self, move_: &mut Move, annotation: MoveAnnotation) -> String {
+        let log = move_to_san(move_, &self.board);
+        self.log_move(log.clone(), annotation);
+        let color = self.board.get_player_to_move();
         self.board.make_move_mut(move_);
+        self.positions.push(position_key(&self.board));
+        self.played.push(*move_);
+        // moving instead of answering turns the offer down
+        if self.draw_offer != Some(color) {
+            self.draw_offer = None;
+        }
+        log
+    }
+
+    // takes back the last two plies, so the side to move gets to move again
+    fn take_back(&mut self) -> Result<(), String> {
+        if self.played.len() < 2 {
+            return Err(String::from("there's no move of yours to take back"));
+        }
+        // one ply each, so both clocks have to be able to go back
+        if !self.white.can_take_back_move() || !self.black.can_take_back_move() {
+            return Err(String::from(
+                "the clocks from before the game was resumed weren't saved, \
+                 so moves from then can't be taken back",
+            ));
+        }
+        for _ in 0..2 {
+            let mut m = self.played.pop().unwrap();
+            self.board.unmake_move_mut(&mut m);
+            let color = self.board.get_player_to_move();
+            self.player_mut(color).take_back_move();
+            self.moves.pop();
+            self.annotations.pop();
+            self.positions.pop();
+        }
+        self.draw_offer = None;
+        // the clock starts over from where it was before the move
+        self.turn_started = Instant::now();
+        Ok(())
+    }
+
+    // the renderer set up for the current position, the last move highlighted and the king
+    // in check if there is one
+    fn board_renderer(&self) -> BoardRenderer {
+        let to_move = self.board.get_player_to_move();
+        let check = if generate_checks(&self.board, to_move).is_empty() {
+            None
+        } else {
+            self.board
+                .get_king(to_move)
+                .and_then(|king| king.at())
+                .copied()
+        };
+        let highlights = match self.played.last() {
+            Some(m) => vec![m.from, m.to],
+            None => vec![],
+        };
+        let bottom = if self.flipped {
+            self.renderer.orientation().opposite()
+        } else {
+            self.renderer.orientation()
+        };
+        self.renderer
+            .clone()
+            .with_orientation(bottom)
+            .with_highlights(highlights)
+            .with_check(check)
+    }
+
+    fn show_board(&self) {
+        self.board_renderer().print(&self.board);
+    }
+
+    // the move in san for the current position
+    fn san(&self, m: &Move) -> String {
+        move_to_san(m, &self.board)
+    }
+
+    pub fn make_move(&mut self, move_: &mut Move) {
+        let log = self.play_move(move_, MoveAnnotation::default());
+        println!("move = \n{}", log);
     }
 
     pub fn make_moves(&mut self, moves: Vec<(Move, Option<Move>)>) {
@@ -99,8 +741,7 @@ impl Game {
         }
     }
 
-    // write the current game as a pgn file with a FEN of the ending position
-    // at the end of the file
+    // write the current game as a pgn file
     fn write_log(&self) {
         if (!self.enable_logging) {
             return;
@@ -112,8 +753,13 @@ impl Game {
             Ok(f) => f.is_dir(),
             Err(err) => false,
         };
+        if !is_dir {
+            if let Err(why) = fs::create_dir_all("./GameLogs") {
+                panic!("couldn't create ./GameLogs: {}", why);
+            }
+        }
 
-        let path_str = format!("./GameLogs/{}-output.txt", self.start_time);
+        let path_str = format!("./GameLogs/{}.pgn", self.start_time);
         let path = Path::new(path_str.as_str());
         let display = path.display();
 
@@ -123,9 +769,8 @@ impl Game {
             Ok(file) => file,
         };
 
-        let pgn = PgnGame::new_from_game(&self);
-        let fen = fen_reader::make_fen(&self.board);
-        let log = format!("{}\n{}", pgn, fen);
+        // a plain pgn, the Resume tags are only for save
+        let log = format!("{}\n", PgnGame::new_from_game(self));
 
         // Write the `LOREM_IPSUM` string to `file`, returns `io::Result<()>`
         match file.write_all(log.as_bytes()) {
@@ -134,59 +779,271 @@ impl Game {
         }
     }
 
-    fn ai_make_move(&mut self, ai: &mut ai::Ai) {
-        println!("{} to move", ai.color());
-        print_board(&self.board);
-
-        let mut m = ai.make_move(&mut self.board, None).unwrap();
-        let log = print_move(&m, &self.board);
-        println!("{} transposition table hits", ai.transposition_table_hits);
-        println!("{} moves \n{}", ai.color(), log);
-        self.moves.push(log);
-        self.board.make_move_mut(&mut m);
+    pub fn ai_for(&self, color: Color) -> &ai::Ai {
+        if self.ai.color() == color {
+            &self.ai
+        } else {
+            &self.ai2
+        }
     }
 
-    fn ai1_make_move(&mut self) {
-        println!("{} to move", self.ai.color());
-        print_board(&self.board);
+    fn ai_mut(&mut self, color: Color) -> &mut ai::Ai {
+        if self.ai.color() == color {
+            &mut self.ai
+        } else {
+            &mut self.ai2
+        }
+    }
 
-        let mut m = self.ai.make_move(&mut self.board, None).unwrap();
-        let log = print_move(&m, &self.board);
-        println!(
-            "{} transposition table hits",
-            self.ai.transposition_table_hits
-        );
-        println!("{} moves \n{}", self.ai.color(), log);
-        self.moves.push(log);
-        self.board.make_move_mut(&mut m);
+    // the ai thinks for as long as its clock or move time allows, or to its usual depth
+    // returns its move and what it thought of the position
+    fn think(&mut self, color: Color) -> Option<(Move, Option<f32>)> {
+        let clock_budget = self.player(color).move_budget();
+        let ai = if self.ai.color() == color {
+            &mut self.ai
+        } else {
+            &mut self.ai2
+        };
+        let budget = match (clock_budget, ai.move_time()) {
+            (Some(clock_budget), Some(move_time)) => Some(clock_budget.min(move_time)),
+            (clock_budget, move_time) => clock_budget.or(move_time),
+        };
+        let m = match budget {
+            Some(budget) => ai.make_move_with_time(&mut self.board, budget),
+            None => ai.make_move(&mut self.board, None),
+        };
+        let eval = ai.last_evaluation().map(|evaluation| evaluation.score);
+        m.map(|m| (m, eval))
     }
-    fn ai2_make_move(&mut self) {
-        println!("{} to move", self.ai2.color());
-        print_board(&self.board);
 
-        let mut m = self.ai2.make_move(&mut self.board, None).unwrap();
-        let log = print_move(&m, &self.board);
-        println!(
-            "{} transposition table hits",
-            self.ai2.transposition_table_hits
-        );
-        println!("{} moves \n{}", self.ai2.color(), log);
-        self.moves.push(log);
-        self.board.make_move_mut(&mut m);
+    fn ai_turn(&mut self, color: Color) -> Turn {
+        println!("{} to move", color);
+        let (mut m, eval) = match self.think(color) {
+            Some(thought) => thought,
+            None => return Turn::Stopped,
+        };
+        if !self.stop_clock() {
+            return Turn::OutOfTime;
+        }
+        self.print_ai_stats_for_last_move(self.ai_for(color));
+        let annotation = self.annotation(color, eval);
+        let log = self.play_move(&mut m, annotation);
+        println!("{} moves \n{}", color, log);
+        Turn::Moved
     }
 
-    fn end_game(&mut self, winner: Option<Color>) {
-        print_board(&self.board);
-        if self.board.get_is_draw() {
-            self.result = GameResult::Draw;
-            println!("DRAW...EVERYONE LOSES. CONGRATS.");
-            self.write_log();
+    // color's ai searches the position, the board's left as it was
+    pub fn analyse(&mut self, color: Color) -> Option<(Evaluation, Option<Move>)> {
+        let ai = if self.ai.color() == color {
+            &mut self.ai
         } else {
-            self.result = GameResult::Win {
-                winning_player: winner.unwrap(),
+            &mut self.ai2
+        };
+        ai.analyse(&mut self.board)
+    }
+
+    // the ai playing color takes a draw if it doesn't think it's better
+    fn ai_takes_draw(&mut self, color: Color) -> bool {
+        let evaluation = match self.analyse(color) {
+            Some((evaluation, _)) => evaluation,
+            None => return false,
+        };
+        let score = match color {
+            Color::White => evaluation.score,
+            Color::Black => -evaluation.score,
+        };
+        score <= DRAW_ACCEPT_SCORE
+    }
+
+    // what the command has to say, or the turn if it ended it
+    fn run_command(&mut self, command: Command, color: Color, humans: &[Color]) -> Reply {
+        let opponent = color.opposite();
+        let message = match command {
+            Command::Undo => match self.take_back() {
+                Ok(()) => format!("took back the last move, {} to move", color),
+                Err(why) => why,
+            },
+            Command::Hint => match self.analyse(color) {
+                Some((_, Some(m))) => format!("try {}", self.san(&m)),
+                _ => String::from("there's nothing to play"),
+            },
+            Command::Resign => {
+                let reason = format!("{} resigns, {} wins", color, opponent);
+                let result = GameResult::Win {
+                    winning_player: opponent,
+                };
+                return Reply::Done(Turn::Ended(result, reason));
+            }
+            Command::Draw => {
+                let agreed = if humans.contains(&opponent) {
+                    self.draw_offer == Some(opponent)
+                } else {
+                    self.ai_takes_draw(opponent)
+                };
+                if agreed {
+                    let reason = String::from("draw agreed");
+                    return Reply::Done(Turn::Ended(GameResult::Draw, reason));
+                }
+                if humans.contains(&opponent) {
+                    self.draw_offer = Some(color);
+                    format!(
+                        "{} offers a draw, {} can type draw to take it or just move",
+                        color, opponent
+                    )
+                } else {
+                    format!("{} declines the draw", opponent)
+                }
+            }
+            Command::Flip => {
+                self.flipped = !self.flipped;
+                String::new()
+            }
+            Command::Fen => fen_reader::make_fen(&self.board),
+            Command::Pgn => PgnGame::new_from_game(self).to_string(),
+            Command::Moves => self.legal_moves_san(color).join(" "),
+            Command::Eval => match self.analyse(color) {
+                Some((evaluation, best)) => {
+                    let score = describe_score(&evaluation);
+                    let depth = self.ai_for(color).last_search_depth();
+                    match best {
+                        Some(m) => {
+                            format!("{} at depth {}, best is {}", score, depth, self.san(&m))
+                        }
+                        None => score,
+                    }
+                }
+                None => String::from("there's nothing to evaluate"),
+            },
+            Command::Save(path) => {
+                if path.is_empty() {
+                    String::from("save where? save <file>")
+                } else {
+                    match self.save(path.as_str()) {
+                        Ok(_) => format!("saved to {}, carry on with --resume {}", path, path),
+                        Err(err) => err,
+                    }
+                }
+            }
+            Command::Help => String::from(HELP),
+        };
+        Reply::Say(message)
+    }
+
+    // every legal move for color, in san
+    fn legal_moves_san(&self, color: Color) -> Vec<String> {
+        gen_legal_moves(&self.board, color)
+            .iter()
+            .map(|m| self.san(m))
+            .collect()
+    }
+
+    // reads lines till one is a legal move, san (Nf3) or coordinates (g1f3)
+    // or a command that ends the turn, see HELP
+    fn human_turn<R: BufRead>(
+        &mut self,
+        color: Color,
+        lines: &mut io::Lines<R>,
+        humans: &[Color],
+    ) -> Turn {
+        self.print_clocks();
+        println!("{} to move", color);
+        for line in lines {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            let text = line.trim();
+            if text.is_empty() {
+                continue;
+            }
+            if let Some(command) = Command::parse(text) {
+                let moves_the_board = matches!(command, Command::Undo | Command::Flip);
+                match self.run_command(command, color, humans) {
+                    Reply::Done(turn) => return turn,
+                    Reply::Say(message) => {
+                        if moves_the_board {
+                            self.show_board();
+                        }
+                        if !message.is_empty() {
+                            println!("{}", message);
+                        }
+                        continue;
+                    }
+                }
+            }
+            // parse_san_for rather than parse_move, so we can say why it's not allowed
+            let mut m = match san::parse_san_for(text, &self.board, color) {
+                Ok(m) => m,
+                Err(err) => {
+                    println!("{}", err);
+                    continue;
+                }
             };
-            println!("{} wins", winner.unwrap());
-            self.write_log();
+            if !self.stop_clock() {
+                return Turn::OutOfTime;
+            }
+            let annotation = self.annotation(color, None);
+            let log = self.play_move(&mut m, annotation);
+            println!("move = \n{}", log);
+            return Turn::Moved;
+        }
+        Turn::Stopped
+    }
+
+    // checkmate, stalemate, 50 moves, three fold repetition or nobody can mate
+    pub fn find_game_end(&self) -> Option<(GameResult, String)> {
+        let to_move = self.board.get_player_to_move();
+        if gen_legal_moves(&self.board, to_move).is_empty() {
+            if generate_checks(&self.board, to_move).is_empty() {
+                return Some((GameResult::Draw, String::from("stalemate")));
+            }
+            let winning_player = to_move.opposite();
+            return Some((
+                GameResult::Win { winning_player },
+                format!("checkmate, {} wins", winning_player),
+            ));
+        }
+        if self.board.get_half_move_clock() >= 100 {
+            return Some((GameResult::Draw, String::from("draw by the 50 move rule")));
+        }
+        let key = self.positions.last().unwrap();
+        if self.positions.iter().filter(|&p| p == key).count() >= 3 {
+            return Some((GameResult::Draw, String::from("draw by repetition")));
+        }
+        if is_insufficient_material(&self.board) {
+            return Some((
+                GameResult::Draw,
+                String::from("draw, neither side can mate"),
+            ));
+        }
+        None
+    }
+
+    fn finish_game(&mut self, result: GameResult, reason: &str) {
+        self.show_board();
+        self.result = result;
+        println!("{}", reason);
+        self.write_log();
+    }
+
+    // running out of time loses, unless the other side had no way of mating anyway
+    fn flag_fell(&mut self, color: Color) {
+        self.termination = Termination::Time;
+        let opponent = color.opposite();
+        if has_mating_material(&self.board, opponent) {
+            let reason = format!("{} ran out of time, {} wins", color, opponent);
+            self.finish_game(
+                GameResult::Win {
+                    winning_player: opponent,
+                },
+                reason.as_str(),
+            );
+        } else {
+            let reason = format!(
+                "{} ran out of time, {} can't mate so it's a draw",
+                color, opponent
+            );
+            self.finish_game(GameResult::Draw, reason.as_str());
         }
     }
 
@@ -199,158 +1056,415 @@ impl Game {
         );
     }
 
-    pub fn run_sim_game(mut self, mut moves: Vec<Move>) {
-        let mut white_to_move = true;
-        for _move in moves.iter_mut() {
-            println!("Game time elasped : {:?}", self.get_time_elapsed());
-            if (white_to_move) {
-                //PLAYER 1'S TURN
-                println!("{} to move", self.ai2.color());
-                print_board(&self.board);
-                // let evaluation = evaluate(&self.board, None, None);
-
-                let m = self.ai2.make_move(&mut self.board, None).unwrap();
-                let log = print_move(&m, &self.board);
-                self.print_ai_stats_for_last_move(&self.ai2);
-                println!("{} AI moves \n{}", self.ai2.color(), log);
-
-                let log = print_move(&_move, &self.board);
-                println!("{} player choose move \n{}", self.ai2.color(), log);
-                self.moves.push(log);
-                self.board.make_move_mut(_move);
-                white_to_move = false;
-            } else {
-                //PLAYER 2'S TURN
-                println!("{} to move", self.ai.color());
-                print_board(&self.board);
-
-                let m = self.ai.make_move(&mut self.board, None).unwrap();
-                let log = print_move(&m, &self.board);
-                self.print_ai_stats_for_last_move(&self.ai);
-                println!("{} AI moves \n{}", self.ai.color(), log);
-                let log = print_move(&_move, &self.board);
-                println!("{} player choose move \n{}", self.ai.color(), log);
-                self.moves.push(log);
-                self.board.make_move_mut(_move);
-
-                // if evaluation.is_checkmate() {
-                //     self.end_game(evaluation.mated_player.unwrap().opposite());
-                // }
-                white_to_move = true;
-            }
-        }
-        println!("The game ended here ");
-        let t1 = match self.ai.total_time_elapsed_during_search() {
-            Some(duration) => duration.as_secs(),
-            None => 0,
-        };
-        let t2 = match self.ai2.total_time_elapsed_during_search() {
-            Some(duration) => duration.as_secs(),
-            None => 0,
-        };
-        if t1 > 0 {
-            println!(
-                "AI searched {} moves, over {} seconds, at a rate of {} moves / second ",
-                self.ai.total_minimax_calls(),
-                t1,
-                self.ai.total_minimax_calls() / t1 as u128
-            );
-        } else {
-            println!(
-                "AI searched {} moves, over {} seconds",
-                self.ai.total_minimax_calls(),
-                t1
-            );
+    pub fn run_ai_versus_ai(mut self) {
+        self.set_player_names("chess_engine", "chess_engine");
+        self.play(io::empty(), &[]);
+    }
+
+    pub fn run_human_versus_ai(mut self) {
+        let human = self.human_color;
+        match human {
+            Color::White => self.set_player_names("Human", "chess_engine"),
+            Color::Black => self.set_player_names("chess_engine", "Human"),
         }
-        if t2 > 0 {
-            println!(
-                "AI searched {} moves, over {} seconds, at a rate of {} moves / second ",
-                self.ai2.total_minimax_calls(),
-                t2,
-                self.ai2.total_minimax_calls() / t2 as u128
-            );
-        } else {
-            println!(
-                "AI searched {} moves, over {} seconds",
-                self.ai2.total_minimax_calls(),
-                t2
-            );
+        println!("You're playing {}, type help for the commands.", human);
+        let stdin = io::stdin();
+        self.play(stdin.lock(), &[human]);
+    }
+
+    // carries on a resumed game with people playing the same sides as before
+    pub fn run_resumed(self) {
+        match self.humans.len() {
+            0 => self.run_ai_versus_ai(),
+            1 => self.run_human_versus_ai(),
+            _ => self.run_player_versus_player(),
         }
     }
 
-    pub fn run_ai_versus_ai(mut self) {
+    // two people at the same board, the game gets logged as it goes
+    pub fn run_player_versus_player(mut self) {
+        self.enable_logging = true;
+        self.set_player_names("White", "Black");
+        println!("type help for the commands");
+        let stdin = io::stdin();
+        self.play(stdin.lock(), &[Color::White, Color::Black]);
+    }
+
+    // plays till the game's over, the humans type their moves in and the ais do the rest
+    fn play<R: BufRead>(&mut self, input: R, humans: &[Color]) {
+        let mut lines = input.lines();
+        self.humans = humans.to_vec();
+        self.turn_started = Instant::now();
         loop {
-            self.ai2_make_move();
-            let evaluation = evaluate(&self.board, None, None);
-            if evaluation.is_checkmate() {
-                self.end_game(Some(evaluation.mated_player.unwrap().opposite()));
-                break;
+            if let Some((result, reason)) = self.find_game_end() {
+                self.finish_game(result, reason.as_str());
+                return;
             }
-            if self.board.get_is_draw() {
-                self.end_game(None);
-                break;
+            self.show_board();
+            let color = self.board.get_player_to_move();
+            let turn = if humans.contains(&color) {
+                self.human_turn(color, &mut lines, humans)
+            } else {
+                self.ai_turn(color)
+            };
+            match turn {
+                Turn::Moved => self.write_log(),
+                Turn::OutOfTime => {
+                    self.flag_fell(color);
+                    return;
+                }
+                Turn::Ended(result, reason) => {
+                    self.finish_game(result, reason.as_str());
+                    return;
+                }
+                Turn::Stopped => return,
             }
-            self.write_log();
-            self.ai1_make_move();
-            let evaluation = evaluate(&self.board, None, None);
-            if self.board.get_is_draw() {
-                self.end_game(None);
-                break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn play(fen: &str, moves: &str) -> Game {
+        let mut game = Game::new_from_game_state(fen_reader::make_game_state(fen).unwrap());
+        game.play(
+            Cursor::new(moves.replace(' ', "\n")),
+            &[Color::White, Color::Black],
+        );
+        game
+    }
+
+    #[test]
+    fn test_play_players() {
+        // fool's mate, the bad input in between is just skipped
+        let game = play(fen_reader::INITIAL_BOARD, "f3 e4 e5 Qh5 g4 nonsense Qh4");
+        assert_eq!(game.moves().len(), 4);
+        assert!(matches!(
+            game.result(),
+            GameResult::Win {
+                winning_player: Color::Black
             }
-            if evaluation.is_checkmate() {
-                self.end_game(Some(evaluation.mated_player.unwrap().opposite()));
-                break;
+        ));
+        // coordinates work too
+        let game = play(fen_reader::INITIAL_BOARD, "e2e4 e7e5 g1f3");
+        assert_eq!(game.moves(), vec!["e4", "e5", "Nf3"]);
+        assert!(matches!(game.result(), GameResult::InProgress));
+    }
+
+    #[test]
+    fn test_commands() {
+        // undo takes back both sides' moves
+        let game = play(fen_reader::INITIAL_BOARD, "e4 e5 undo d4 d5");
+        assert_eq!(game.moves(), vec!["d4", "d5"]);
+        assert_eq!(game.positions.len(), 3);
+        assert_eq!(
+            fen_reader::make_fen(game.board()),
+            "rnbqkbnr/ppp1pppp/8/3p4/3P4/8/PPP1PPPP/RNBQKBNR w KQkq d6 0 2"
+        );
+        // and puts the clocks back, the increments included
+        let mut game = Game::new_from_game_state(GameState::starting_game());
+        game.set_time_control(TimeControl::parse("60+5").unwrap());
+        game.play(Cursor::new("e4\ne5\nundo"), &[Color::White, Color::Black]);
+        assert!(game.moves().is_empty());
+        for color in [Color::White, Color::Black].iter() {
+            let player = game.player(*color);
+            assert_eq!(player.time_remaining(), Duration::from_secs(60));
+            assert_eq!(player.time_used(), Duration::from_secs(0));
+            assert_eq!(player.moves_made(), 0);
+        }
+        // nothing to take back yet, and none of these use up the turn
+        let game = play(
+            "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+            "undo hint flip fen pgn moves eval help",
+        );
+        assert!(game.moves().is_empty());
+        assert!(game.flipped);
+
+        let game = play(fen_reader::INITIAL_BOARD, "e4 resign");
+        assert!(matches!(
+            game.result(),
+            GameResult::Win {
+                winning_player: Color::White
             }
-            self.write_log();
+        ));
+    }
+
+    #[test]
+    fn test_draw_offers() {
+        // black offers then moves, white takes it
+        let game = play(fen_reader::INITIAL_BOARD, "e4 draw e5 draw");
+        assert!(matches!(game.result(), GameResult::Draw));
+        // white moving turns it down
+        let game = play(fen_reader::INITIAL_BOARD, "e4 draw e5 Nf3");
+        assert!(matches!(game.result(), GameResult::InProgress));
+        assert_eq!(game.draw_offer, None);
+
+        // the ai takes a draw two queens down, not two queens up
+        let mut game = Game::new_from_fen("4k3/8/8/8/8/8/8/QQ2K3 w - - 0 1")
+            .unwrap()
+            .with_ai_depth(Color::Black, 2);
+        game.play(Cursor::new("draw"), &[Color::White]);
+        assert!(matches!(game.result(), GameResult::Draw));
+        let mut game = Game::new_from_fen("qq2k3/8/8/8/8/8/8/4K3 w - - 0 1")
+            .unwrap()
+            .with_ai_depth(Color::Black, 2);
+        game.play(Cursor::new("draw"), &[Color::White]);
+        assert!(matches!(game.result(), GameResult::InProgress));
+    }
+
+    #[test]
+    fn test_save_and_resume() {
+        let board = fen_reader::make_game_state(fen_reader::INITIAL_BOARD).unwrap();
+        let mut game = Game::new_from_game_state(board)
+            .with_ai_settings(Color::Black, "depth=2,movetime=300,search=minimax")
+            .unwrap();
+        game.set_time_control(TimeControl::parse("60d5").unwrap());
+        game.play(
+            Cursor::new("Nf3 Nf6 Ng1 Ng8 Nf3 Nf6 Ng1".replace(' ', "\n")),
+            &[Color::White, Color::Black],
+        );
+        assert_eq!(game.moves().len(), 7);
+        let saved = game.to_saved_pgn();
+        let resumed = Game::resume_from_pgn(saved.as_str()).unwrap();
+        assert_eq!(resumed.moves(), game.moves());
+        assert_eq!(resumed.positions, game.positions);
+        assert_eq!(
+            fen_reader::make_fen(resumed.board()),
+            fen_reader::make_fen(game.board())
+        );
+        assert_eq!(resumed.time_control(), game.time_control());
+        assert_eq!(resumed.humans, vec![Color::White, Color::Black]);
+        assert_eq!(
+            resumed.ai_settings(Color::Black),
+            "depth=2,search=minimax,movetime=300"
+        );
+        for &color in [Color::White, Color::Black].iter() {
+            let (was, now) = (game.player(color), resumed.player(color));
+            assert_eq!(now.moves_made(), was.moves_made());
+            assert_eq!(
+                now.time_remaining().as_millis(),
+                was.time_remaining().as_millis()
+            );
         }
+        // the repetition history came back too, Ng8 is the third time
+        let mut resumed = resumed;
+        resumed.play(Cursor::new("Ng8"), &[Color::White, Color::Black]);
+        assert!(matches!(resumed.result(), GameResult::Draw));
+
+        // a plain pgn, the clocks come from the comments
+        let pgn = "[TimeControl \"300+2\"]\n\n1. e4 {[%clk 0:04:55]} e5 {[%clk 0:04:41]} *";
+        let resumed = Game::resume_from_pgn(pgn).unwrap();
+        assert_eq!(
+            resumed.player(Color::Black).time_remaining(),
+            Duration::from_secs(281)
+        );
+        assert_eq!(resumed.player(Color::White).moves_made(), 1);
+        assert_eq!(resumed.human_color(), Color::White);
+
+        let finished = play(fen_reader::INITIAL_BOARD, "f3 e5 g4 Qh4").to_saved_pgn();
+        assert!(Game::resume_from_pgn(finished.as_str()).is_err());
     }
 
-    pub fn run_human_versus_ai(mut self) {
-        let stdin = io::stdin();
-        println!("You're playing white.");
-        print_board(&self.board);
-        println!("What's your move?");
-        for line in stdin.lock().lines() {
-            // white move
-            let command = line.unwrap().clone();
-            let mut m = parse_move(command.as_str(), &self.board, Color::White);
-            if m.is_none() {
-                println!("That move is illegal!");
-                continue;
-            }
-            let mut m = m.unwrap();
-            let log = print_move(&m, &self.board);
-            println!("move = \n{}", log);
-            self.moves.push(log);
-            self.board.make_move_mut(&mut m);
-            self.write_log();
-
-            // print eval
-            let eval = ai::evaluator::evaluate(&self.board, None, None);
-            println!("eval {}", eval.score);
-            if eval.is_checkmate() {
-                self.end_game(Some(eval.mated_player.unwrap().opposite()));
-                break;
+    #[test]
+    fn test_undo_after_resume() {
+        let pgn = "[TimeControl \"300+2\"]\n\n1. e4 {[%clk 0:04:55]} e5 {[%clk 0:04:41]} *";
+        let clocks = |game: &Game| {
+            (
+                game.player(Color::White).time_remaining(),
+                game.player(Color::Black).time_remaining(),
+            )
+        };
+        let resumed = (Duration::from_secs(295), Duration::from_secs(281));
+
+        // the moves from before the resume can't go back, their clocks weren't saved
+        let mut game = Game::resume_from_pgn(pgn).unwrap();
+        game.play(Cursor::new("undo"), &[Color::White, Color::Black]);
+        assert_eq!(game.moves(), vec!["e4", "e5"]);
+        assert_eq!(clocks(&game), resumed);
+
+        // moves made since can, and the clocks go back with them
+        game.play(Cursor::new("Nf3\nNc6\nundo"), &[Color::White, Color::Black]);
+        assert_eq!(game.moves(), vec!["e4", "e5"]);
+        assert_eq!(clocks(&game), resumed);
+        assert_eq!(game.player(Color::White).moves_made(), 1);
+    }
+
+    #[test]
+    fn test_save_command() {
+        let path = std::env::temp_dir().join("chess_engine_test_save_command.pgn");
+        let mut game = Game::new();
+        let input = format!("e4\ne5\nsave {}", path.display());
+        game.play(Cursor::new(input), &[Color::White, Color::Black]);
+        let saved = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(saved.contains("[ResumeHumans \"both\"]"));
+        let resumed = Game::resume_from_pgn(saved.as_str()).unwrap();
+        assert_eq!(resumed.moves(), game.moves());
+        assert_eq!(resumed.board().get_player_to_move(), Color::White);
+    }
+
+    #[test]
+    fn test_find_game_end() {
+        let game = play("7k/4Q3/6K1/8/8/8/8/8 w - - 0 1", "Qf7");
+        assert!(matches!(game.result(), GameResult::Draw));
+        assert_eq!(game.find_game_end().unwrap().1, "stalemate");
+
+        // back to the starting position a third time
+        let game = play(
+            fen_reader::INITIAL_BOARD,
+            "Nf3 Nf6 Ng1 Ng8 Nf3 Nf6 Ng1 Ng8 e4",
+        );
+        assert_eq!(game.moves().len(), 8);
+        assert_eq!(game.find_game_end().unwrap().1, "draw by repetition");
+
+        let game = play("4k3/8/8/8/8/8/3p4/4K3 w - - 0 1", "Kxd2");
+        assert_eq!(
+            game.find_game_end().unwrap().1,
+            "draw, neither side can mate"
+        );
+
+        let game = play("4k3/8/8/8/8/8/8/R3K3 w - - 99 80", "Kd2");
+        assert_eq!(game.find_game_end().unwrap().1, "draw by the 50 move rule");
+    }
+
+    #[test]
+    fn test_flag_fall() {
+        // no time at all, the first move is already too late
+        let mut game = Game::new();
+        game.set_time_control(TimeControl::parse("0").unwrap());
+        game.play(Cursor::new("e4"), &[Color::White, Color::Black]);
+        assert!(game.moves().is_empty());
+        assert!(matches!(
+            game.result(),
+            GameResult::Win {
+                winning_player: Color::Black
             }
+        ));
+        assert_eq!(game.termination(), Termination::Time);
+        let pgn = PgnGame::new_from_game(&game).to_string();
+        assert!(pgn.contains("[TimeControl \"0\"]"));
+        assert!(pgn.contains("[Termination \"time forfeit\"]"));
 
-            print_board(&self.board);
-            // black moves now
-            let mut m = self.ai.make_move(&mut self.board, None).unwrap();
-            let log = print_move(&m, &self.board);
-            println!("move = \n{}", log);
-            self.moves.push(log);
-            self.board.make_move_mut(&mut m);
-            // self.moves.push(m);
-            println!("Black moves... {}", m);
-            let eval = ai::evaluator::evaluate(&self.board, None, None);
-            println!("eval {}", eval.score);
-            self.print_ai_stats_for_last_move(&self.ai);
-            if eval.is_checkmate() {
-                self.end_game(Some(eval.mated_player.unwrap().opposite()));
-                break;
+        // black only has a king, it's a draw
+        let board = fen_reader::make_game_state("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let mut game = Game::new_from_game_state(board);
+        game.set_time_control(TimeControl::parse("0").unwrap());
+        game.play(Cursor::new("Ra7"), &[Color::White]);
+        assert!(matches!(game.result(), GameResult::Draw));
+
+        // a knight can't mate a lone king but white's pawn could end up blocking its own king
+        let board = fen_reader::make_game_state("4k1n1/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        let mut game = Game::new_from_game_state(board);
+        game.set_time_control(TimeControl::parse("0").unwrap());
+        game.play(Cursor::new("e4"), &[Color::White]);
+        assert!(matches!(
+            game.result(),
+            GameResult::Win {
+                winning_player: Color::Black
             }
-            print_board(&self.board);
-            self.write_log();
+        ));
+    }
+
+    #[test]
+    fn test_ai_on_the_clock() {
+        let board = fen_reader::make_game_state("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let mut game = Game::new_from_game_state(board);
+        game.set_time_control(TimeControl::parse("3+1").unwrap());
+        game.play(Cursor::new("Ra7"), &[Color::White]);
+        // the ai answered then the input ran out
+        assert_eq!(game.moves().len(), 2);
+        assert!(matches!(game.result(), GameResult::InProgress));
+        let black = game.player(Color::Black);
+        assert_eq!(black.moves_made(), 1);
+        // thinks for under a second, and gets the increment back
+        assert!(black.time_used() < Duration::from_secs(2));
+        assert!(black.time_remaining() > Duration::from_secs(2));
+        assert_eq!(game.annotations()[1].clock, Some(black.time_remaining()));
+        assert!(game.annotations()[1].eval.is_some());
+    }
+
+    #[test]
+    fn test_new_from_pgn_at() {
+        let pgn = "[Event \"?\"]\n\n1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 *";
+        let game = Game::new_from_pgn_at(pgn, 2, Color::White).unwrap();
+        assert_eq!(
+            game.start_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"
+        );
+        let game = Game::new_from_pgn_at(pgn, 3, Color::Black).unwrap();
+        assert_eq!(game.board().get_player_to_move(), Color::Black);
+        assert!(game.board().get_piece_at(&Coordinate::new(2, 5)).is_some());
+        // right after the last move is fine, past it isn't
+        assert!(Game::new_from_pgn_at(pgn, 4, Color::White).is_ok());
+        assert_eq!(
+            Game::new_from_pgn_at(pgn, 4, Color::Black).err(),
+            Some(String::from("the game never gets to 4..."))
+        );
+        assert!(Game::new_from_fen("not a fen").is_err());
+    }
+
+    #[test]
+    fn test_ai_settings() {
+        let mut game = Game::new()
+            .with_human_playing(Color::Black)
+            .with_ai_depth(Color::White, 1)
+            .with_ai_search(Color::White, AiSearch::Random);
+        assert_eq!(game.human_color(), Color::Black);
+        game.play(Cursor::new("Nc6"), &[game.human_color()]);
+        // the ai moves first, then again after Nc6, then the input runs out
+        assert_eq!(game.moves().len(), 3);
+        assert_eq!(game.moves()[1], "Nc6");
+        let ai = game.ai_for(Color::White);
+        assert_eq!(ai.search_function(), AiSearch::Random);
+        assert_eq!(ai.default_search_depth, 1);
+
+        let mut game = Game::new_from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 0 1")
+            .unwrap()
+            .with_ai_move_time(Color::Black, Duration::from_millis(50));
+        game.play(Cursor::new(""), &[Color::White]);
+        let ai = game.ai_for(Color::Black);
+        assert_eq!(game.moves().len(), 1);
+        assert!(ai.time_elapsed().unwrap() < Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        let insufficient = [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4KN2 w - - 0 1",
+            "4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1",
+        ];
+        for fen in insufficient.iter() {
+            let board = fen_reader::make_game_state(fen).unwrap();
+            assert!(is_insufficient_material(&board), "{}", fen);
         }
+        let sufficient = [
+            "4k3/8/8/8/8/8/8/4K2R w - - 0 1",
+            "4k3/8/8/8/8/8/8/3NK1N1 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1",
+            "4kn2/8/8/8/8/8/8/2B1K3 w - - 0 1",
+        ];
+        for fen in sufficient.iter() {
+            let board = fen_reader::make_game_state(fen).unwrap();
+            assert!(!is_insufficient_material(&board), "{}", fen);
+        }
+
+        let board = fen_reader::make_game_state("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1").unwrap();
+        assert!(has_mating_material(&board, Color::White));
+        assert!(!has_mating_material(&board, Color::Black));
+        let board = fen_reader::make_game_state("4k3/8/8/8/8/8/8/1B2KB2 w - - 0 1").unwrap();
+        assert!(!has_mating_material(&board, Color::White));
+        // the other side's own pieces can box its king in
+        let board = fen_reader::make_game_state("4k3/4p3/8/8/8/8/8/1N2K3 w - - 0 1").unwrap();
+        assert!(has_mating_material(&board, Color::White));
+        let board = fen_reader::make_game_state("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1").unwrap();
+        assert!(!has_mating_material(&board, Color::White));
+        let board = fen_reader::make_game_state("2b1k3/8/8/8/8/8/8/1B2K3 w - - 0 1").unwrap();
+        assert!(!has_mating_material(&board, Color::White));
+        let board = fen_reader::make_game_state("3bk3/8/8/8/8/8/8/1B2K3 w - - 0 1").unwrap();
+        assert!(has_mating_material(&board, Color::White));
     }
 }
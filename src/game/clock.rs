@@ -0,0 +1,366 @@
+use std::cmp::min;
+use std::fmt;
+use std::fmt::Formatter;
+use std::time::Duration;
+
+/*
+time controls and the clock each player has
+
+    sudden death       300       5 minutes for the whole game
+    fischer            300+2     2 seconds added after every move
+    bronstein delay    300b2     you get back what you used on the move, up to 2 seconds
+    simple delay       300d2     the clock waits 2 seconds before it starts counting down
+    moves per session  40/5400   40 moves in 90 minutes, then another 90 minutes for the next 40
+
+that text is what TimeControl::parse takes, times are in seconds
+the pgn TimeControl tag uses the same thing, minus the delays it has no way of writing
+*/
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TimeControl {
+    Unlimited,
+    SuddenDeath { base: Duration },
+    Fischer { base: Duration, increment: Duration },
+    Bronstein { base: Duration, delay: Duration },
+    SimpleDelay { base: Duration, delay: Duration },
+    MovesPerSession { moves: u32, session: Duration },
+}
+
+// a guess at how many more moves the game goes when the time control doesn't say
+const MOVES_TO_GO: u32 = 30;
+
+fn parse_seconds(text: &str) -> Result<Duration, String> {
+    match text.parse::<u64>() {
+        Ok(seconds) => Ok(Duration::from_secs(seconds)),
+        Err(err) => Err(format!("bad number of seconds {}: {}", text, err)),
+    }
+}
+
+impl TimeControl {
+    pub fn parse(text: &str) -> Result<TimeControl, String> {
+        let text = text.trim();
+        if text == "-" || text.is_empty() {
+            return Ok(TimeControl::Unlimited);
+        }
+        if let Some((moves, session)) = text.split_once('/') {
+            let moves = match moves.parse::<u32>() {
+                Ok(moves) if moves > 0 => moves,
+                _ => return Err(format!("bad number of moves in {}", text)),
+            };
+            return Ok(TimeControl::MovesPerSession {
+                moves,
+                session: parse_seconds(session)?,
+            });
+        }
+        if let Some((base, increment)) = text.split_once('+') {
+            return Ok(TimeControl::Fischer {
+                base: parse_seconds(base)?,
+                increment: parse_seconds(increment)?,
+            });
+        }
+        if let Some((base, delay)) = text.split_once('b') {
+            return Ok(TimeControl::Bronstein {
+                base: parse_seconds(base)?,
+                delay: parse_seconds(delay)?,
+            });
+        }
+        if let Some((base, delay)) = text.split_once('d') {
+            return Ok(TimeControl::SimpleDelay {
+                base: parse_seconds(base)?,
+                delay: parse_seconds(delay)?,
+            });
+        }
+        Ok(TimeControl::SuddenDeath {
+            base: parse_seconds(text)?,
+        })
+    }
+
+    // what's on the clock at the start of the game
+    pub fn base(&self) -> Option<Duration> {
+        match *self {
+            TimeControl::Unlimited => None,
+            TimeControl::SuddenDeath { base }
+            | TimeControl::Fischer { base, .. }
+            | TimeControl::Bronstein { base, .. }
+            | TimeControl::SimpleDelay { base, .. } => Some(base),
+            TimeControl::MovesPerSession { session, .. } => Some(session),
+        }
+    }
+
+    pub fn to_pgn_tag(&self) -> String {
+        match *self {
+            TimeControl::Unlimited => String::from("-"),
+            TimeControl::Fischer { base, increment } => {
+                format!("{}+{}", base.as_secs(), increment.as_secs())
+            }
+            TimeControl::MovesPerSession { moves, session } => {
+                format!("{}/{}", moves, session.as_secs())
+            }
+            _ => format!("{}", self.base().unwrap().as_secs()),
+        }
+    }
+
+    // how long to think about a move with `remaining` left after `moves_made` moves
+    // None when there's no clock, just search to the usual depth
+    pub fn move_budget(&self, remaining: Duration, moves_made: u32) -> Option<Duration> {
+        let (moves_to_go, bonus) = match *self {
+            TimeControl::Unlimited => return None,
+            TimeControl::SuddenDeath { .. } => (MOVES_TO_GO, Duration::from_secs(0)),
+            TimeControl::Fischer { increment, .. } => (MOVES_TO_GO, increment),
+            TimeControl::Bronstein { delay, .. } | TimeControl::SimpleDelay { delay, .. } => {
+                (MOVES_TO_GO, delay)
+            }
+            TimeControl::MovesPerSession { moves, .. } => {
+                (moves - moves_made % moves, Duration::from_secs(0))
+            }
+        };
+        let budget = remaining / moves_to_go + bonus * 3 / 4;
+        // never bet more than half the clock on one move, the delay is free with a simple delay
+        let free = match *self {
+            TimeControl::SimpleDelay { delay, .. } => delay,
+            _ => Duration::from_secs(0),
+        };
+        Some(min(budget, remaining / 2 + free).max(Duration::from_millis(10)))
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            TimeControl::Bronstein { base, delay } => {
+                write!(f, "{}b{}", base.as_secs(), delay.as_secs())
+            }
+            TimeControl::SimpleDelay { base, delay } => {
+                write!(f, "{}d{}", base.as_secs(), delay.as_secs())
+            }
+            _ => write!(f, "{}", self.to_pgn_tag()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Player {
+    time_used: Duration,
+    time_remaining: Duration,
+    name: String,
+    moves_made: u32,
+    time_control: TimeControl,
+    // (time used, time remaining) before each finish_move, for taking moves back
+    history: Vec<(Duration, Duration)>,
+}
+
+impl Player {
+    pub fn new(name: &str, time_control: TimeControl) -> Player {
+        Player {
+            time_used: Duration::from_secs(0),
+            time_remaining: time_control.base().unwrap_or(Duration::from_secs(0)),
+            name: String::from(name),
+            moves_made: 0,
+            time_control,
+            history: vec![],
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = String::from(name);
+    }
+
+    pub fn time_used(&self) -> Duration {
+        self.time_used
+    }
+
+    pub fn time_remaining(&self) -> Duration {
+        self.time_remaining
+    }
+
+    pub fn moves_made(&self) -> u32 {
+        self.moves_made
+    }
+
+    // put the clock back how it was, for resuming a saved game
+    // what the clock showed before each move isn't saved, so those moves can't be taken back
+    pub fn set_clock(&mut self, time_remaining: Duration, time_used: Duration, moves_made: u32) {
+        self.time_remaining = time_remaining;
+        self.time_used = time_used;
+        self.moves_made = moves_made;
+        self.history.clear();
+    }
+
+    // false when the clock doesn't know how it was before the last move, see set_clock
+    pub fn can_take_back_move(&self) -> bool {
+        !self.is_timed() || !self.history.is_empty()
+    }
+
+    // the clock goes back to how it was before the last finish_move
+    pub fn take_back_move(&mut self) {
+        if let Some((time_used, time_remaining)) = self.history.pop() {
+            self.time_used = time_used;
+            self.time_remaining = time_remaining;
+            self.moves_made = self.moves_made.saturating_sub(1);
+        }
+    }
+
+    pub fn is_timed(&self) -> bool {
+        self.time_control != TimeControl::Unlimited
+    }
+
+    // what the clock shows `thinking` into their move
+    pub fn time_left_while_thinking(&self, thinking: Duration) -> Duration {
+        let charged = match self.time_control {
+            TimeControl::SimpleDelay { delay, .. } => thinking.saturating_sub(delay),
+            _ => thinking,
+        };
+        self.time_remaining.saturating_sub(charged)
+    }
+
+    // how long the ai playing this side should think
+    pub fn move_budget(&self) -> Option<Duration> {
+        self.time_control
+            .move_budget(self.time_remaining, self.moves_made)
+    }
+
+    // stop the clock on a move that took `took`, false if the flag fell before it was made
+    pub fn finish_move(&mut self, took: Duration) -> bool {
+        self.history.push((self.time_used, self.time_remaining));
+        self.time_used += took;
+        if !self.is_timed() {
+            self.moves_made += 1;
+            return true;
+        }
+        let charged = match self.time_control {
+            TimeControl::SimpleDelay { delay, .. } => took.saturating_sub(delay),
+            _ => took,
+        };
+        if charged > self.time_remaining {
+            self.time_remaining = Duration::from_secs(0);
+            return false;
+        }
+        self.time_remaining -= charged;
+        self.moves_made += 1;
+        match self.time_control {
+            TimeControl::Fischer { increment, .. } => self.time_remaining += increment,
+            TimeControl::Bronstein { delay, .. } => self.time_remaining += min(took, delay),
+            TimeControl::MovesPerSession { moves, session }
+                if self.moves_made.is_multiple_of(moves) =>
+            {
+                self.time_remaining += session
+            }
+            _ => {}
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seconds(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(TimeControl::parse("-"), Ok(TimeControl::Unlimited));
+        assert_eq!(
+            TimeControl::parse("300"),
+            Ok(TimeControl::SuddenDeath { base: seconds(300) })
+        );
+        assert_eq!(
+            TimeControl::parse("180+2"),
+            Ok(TimeControl::Fischer {
+                base: seconds(180),
+                increment: seconds(2)
+            })
+        );
+        assert_eq!(
+            TimeControl::parse("60b5"),
+            Ok(TimeControl::Bronstein {
+                base: seconds(60),
+                delay: seconds(5)
+            })
+        );
+        assert_eq!(
+            TimeControl::parse("60d5"),
+            Ok(TimeControl::SimpleDelay {
+                base: seconds(60),
+                delay: seconds(5)
+            })
+        );
+        assert_eq!(
+            TimeControl::parse("40/5400"),
+            Ok(TimeControl::MovesPerSession {
+                moves: 40,
+                session: seconds(5400)
+            })
+        );
+        for text in ["180+2", "60b5", "60d5", "40/5400", "300", "-"].iter() {
+            assert_eq!(
+                TimeControl::parse(text).unwrap().to_string(),
+                String::from(*text)
+            );
+        }
+        assert_eq!(TimeControl::parse("60d5").unwrap().to_pgn_tag(), "60");
+        assert!(TimeControl::parse("five minutes").is_err());
+        assert!(TimeControl::parse("0/300").is_err());
+    }
+
+    #[test]
+    fn test_clocks() {
+        let mut player = Player::new("?", TimeControl::parse("60").unwrap());
+        assert!(player.finish_move(seconds(20)));
+        assert_eq!(player.time_remaining(), seconds(40));
+        assert!(!player.finish_move(seconds(41)));
+        assert_eq!(player.time_remaining(), seconds(0));
+
+        let mut player = Player::new("?", TimeControl::parse("60+5").unwrap());
+        assert!(player.finish_move(seconds(20)));
+        assert_eq!(player.time_remaining(), seconds(45));
+
+        // bronstein gives back up to the delay, simple delay never starts the clock
+        let mut player = Player::new("?", TimeControl::parse("60b5").unwrap());
+        assert!(player.finish_move(seconds(3)));
+        assert_eq!(player.time_remaining(), seconds(60));
+        assert!(player.finish_move(seconds(20)));
+        assert_eq!(player.time_remaining(), seconds(45));
+        assert!(!player.finish_move(seconds(46)));
+
+        let mut player = Player::new("?", TimeControl::parse("60d5").unwrap());
+        assert_eq!(player.time_left_while_thinking(seconds(4)), seconds(60));
+        assert!(player.finish_move(seconds(20)));
+        assert_eq!(player.time_remaining(), seconds(45));
+        assert!(player.finish_move(seconds(50)));
+        assert_eq!(player.time_remaining(), seconds(0));
+        assert_eq!(player.time_used(), seconds(70));
+
+        let mut player = Player::new("?", TimeControl::parse("2/100").unwrap());
+        assert!(player.finish_move(seconds(30)));
+        assert!(player.finish_move(seconds(30)));
+        assert_eq!(player.time_remaining(), seconds(140));
+        assert_eq!(player.moves_made(), 2);
+        player.take_back_move();
+        assert_eq!(player.time_remaining(), seconds(70));
+        assert_eq!(player.time_used(), seconds(30));
+        assert_eq!(player.moves_made(), 1);
+
+        let mut player = Player::new("?", TimeControl::Unlimited);
+        assert!(player.finish_move(seconds(3600)));
+        assert_eq!(player.move_budget(), None);
+    }
+
+    #[test]
+    fn test_move_budget() {
+        let player = Player::new("?", TimeControl::parse("300").unwrap());
+        assert_eq!(player.move_budget(), Some(seconds(10)));
+        let player = Player::new("?", TimeControl::parse("300+4").unwrap());
+        assert_eq!(player.move_budget(), Some(seconds(13)));
+        // the last move of the session gets everything that's left, less the safety margin
+        let mut player = Player::new("?", TimeControl::parse("2/100").unwrap());
+        player.finish_move(seconds(40));
+        assert_eq!(player.move_budget(), Some(seconds(30)));
+    }
+}
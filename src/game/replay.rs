@@ -0,0 +1,430 @@
+use super::describe_score;
+use crate::ai::evaluator::{Evaluation, Evaluator};
+use crate::ai::Ai;
+use crate::board::*;
+use crate::board_console_printer::BoardRenderer;
+use crate::chess_notation::pgn_parser::PgnGame;
+use crate::chess_notation::pgn_reader::PgnReader;
+use crate::chess_notation::{move_to_san, parse_move_number};
+use crate::game_state::GameState;
+use crate::move_generator::Move;
+use console::{Key, Term};
+use std::io;
+use std::io::prelude::*;
+
+/*
+going back over a game from a pgn, with what the engine would have played in each position
+
+    next, n or just enter   the next move
+    back, b                 take a move back
+    go 12, go 12...         to white's 12th move, or black's
+    start, end              to the start or the end of the game
+    quit, q
+
+in a terminal the arrow keys work too, right and left step through the game, up and down go
+to the start and the end, anything else starts typing a command
+*/
+
+const HELP: &str =
+    "next (n, enter), back (b), go <move number> (go 12, go 12...), start, end, quit (q)";
+
+// what the engine thought of a position
+struct Analysis {
+    evaluation: Evaluation,
+    best: Option<Move>,
+    depth: u8,
+}
+
+pub struct Replay {
+    title: String,
+    result: String,
+    start: GameState,
+    moves: Vec<Move>,
+    // the moves written out in san, in the position they were played in
+    sans: Vec<String>,
+    board: GameState,
+    // how many of the moves are on the board
+    ply: usize,
+    ai: Ai,
+    // each position's analysis once it's done, so going back doesn't search again
+    analyses: Vec<Option<Analysis>>,
+    renderer: BoardRenderer,
+}
+
+impl Replay {
+    pub fn new(pgn_game: &PgnGame) -> Result<Replay, String> {
+        let start = pgn_game
+            .starting_position()
+            .map_err(|err| err.to_string())?;
+        let moves = pgn_game.main_line();
+        let mut board = start.clone_to_game_state();
+        let mut sans = vec![];
+        for m in moves.iter() {
+            let mut m = *m;
+            sans.push(move_to_san(&m, &board));
+            board.make_move_mut(&mut m);
+        }
+        let tag = |name: &str| pgn_game.get_tag(name).unwrap_or("?").to_string();
+        Ok(Replay {
+            title: format!(
+                "{} vs {}, {} {}",
+                tag("White"),
+                tag("Black"),
+                tag("Event"),
+                tag("Date")
+            ),
+            result: pgn_game.result.clone(),
+            board: start.clone_to_game_state(),
+            start,
+            analyses: moves.iter().map(|_| None).chain(Some(None)).collect(),
+            moves,
+            sans,
+            ply: 0,
+            ai: Ai::new(Color::White),
+            renderer: BoardRenderer::new(),
+        })
+    }
+
+    // the game'th game in the pgn, counting from 1
+    pub fn load<R: Read>(pgn: R, game: usize) -> Result<Replay, String> {
+        let mut reader = PgnReader::new(pgn);
+        let skipped = reader
+            .skip_games(game.saturating_sub(1))
+            .map_err(|err| err.to_string())?;
+        match reader.next() {
+            Some(Ok(raw_game)) => match raw_game.parse() {
+                Ok(pgn_game) => Replay::new(&pgn_game),
+                Err(err) => Err(err.to_string()),
+            },
+            Some(Err(err)) => Err(err.to_string()),
+            None => Err(format!("there are only {} games in the pgn", skipped)),
+        }
+    }
+
+    // how the engine searches, like depth=6 or movetime=500
+    pub fn with_ai_settings(mut self, settings: &str) -> Result<Replay, String> {
+        self.ai.apply_settings(settings)?;
+        Ok(self)
+    }
+
+    pub fn with_evaluator(mut self, evaluator: Box<dyn Evaluator>) -> Replay {
+        self.ai.set_evaluator(evaluator);
+        self
+    }
+
+    pub fn with_board_renderer(mut self, renderer: BoardRenderer) -> Replay {
+        self.renderer = renderer;
+        self
+    }
+
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    pub fn board(&self) -> &GameState {
+        &self.board
+    }
+
+    // replays the first ply moves from the start
+    pub fn go_to(&mut self, ply: usize) {
+        let ply = ply.min(self.moves.len());
+        self.board = self.start.clone_to_game_state();
+        for m in self.moves[..ply].iter() {
+            let mut m = *m;
+            self.board.make_move_mut(&mut m);
+        }
+        self.ply = ply;
+    }
+
+    // the ply white's (or black's) move_number is played at
+    fn ply_for(&self, move_number: u16, color: Color) -> Option<usize> {
+        let first = self.start.get_full_move_number() as i32 * 2
+            + (self.start.get_player_to_move() == Color::Black) as i32;
+        let ply = move_number as i32 * 2 + (color == Color::Black) as i32 - first;
+        if ply < 0 || ply as usize > self.moves.len() {
+            None
+        } else {
+            Some(ply as usize)
+        }
+    }
+
+    // the move at ply with its number, 12. Nf3 or 12... Nf6
+    fn numbered(&self, ply: usize, san: &str) -> String {
+        let board = if ply == self.ply {
+            self.board.clone_to_game_state()
+        } else {
+            let mut board = self.start.clone_to_game_state();
+            for m in self.moves[..ply].iter() {
+                let mut m = *m;
+                board.make_move_mut(&mut m);
+            }
+            board
+        };
+        let dots = match board.get_player_to_move() {
+            Color::White => ".",
+            Color::Black => "...",
+        };
+        format!("{}{} {}", board.get_full_move_number(), dots, san)
+    }
+
+    fn analysis(&mut self) -> Option<&Analysis> {
+        if self.analyses[self.ply].is_none() {
+            let mut board = self.board.clone_to_game_state();
+            if let Some((evaluation, best)) = self.ai.analyse(&mut board) {
+                self.analyses[self.ply] = Some(Analysis {
+                    evaluation,
+                    best,
+                    depth: self.ai.last_search_depth(),
+                });
+            }
+        }
+        self.analyses[self.ply].as_ref()
+    }
+
+    // the move played here next to the one the engine likes
+    fn describe(&mut self) -> Vec<String> {
+        let mut lines = vec![self.title.clone()];
+        let played = self
+            .moves
+            .get(self.ply)
+            .map(|m| (*m, self.numbered(self.ply, self.sans[self.ply].as_str())));
+        match &played {
+            Some((_, numbered)) => lines.push(format!("played  {}", numbered)),
+            None => lines.push(format!("end of the game  {}", self.result)),
+        }
+        let board = self.board.clone_to_game_state();
+        let engine = match self.analysis() {
+            None => String::from("engine  no moves"),
+            Some(analysis) => {
+                let score = describe_score(&analysis.evaluation);
+                match (analysis.best, &played) {
+                    (Some(best), Some((m, _))) if best.same_move(m) => {
+                        format!("engine  agrees, {} at depth {}", score, analysis.depth)
+                    }
+                    (Some(best), _) => {
+                        let san = move_to_san(&best, &board);
+                        format!("engine  {}, {} at depth {}", san, score, analysis.depth)
+                    }
+                    (None, _) => format!("engine  {}", score),
+                }
+            }
+        };
+        lines.push(engine);
+        lines
+    }
+
+    fn show(&mut self) {
+        let highlights = match self.ply.checked_sub(1).and_then(|ply| self.moves.get(ply)) {
+            Some(m) => vec![m.from, m.to],
+            None => vec![],
+        };
+        self.renderer
+            .clone()
+            .with_highlights(highlights)
+            .print(&self.board);
+        for line in self.describe() {
+            println!("{}", line);
+        }
+    }
+
+    // Ok(false) to stop
+    fn command(&mut self, text: &str) -> Result<bool, String> {
+        let mut words = text.split_whitespace();
+        match words.next().unwrap_or("next") {
+            "next" | "n" => {
+                if self.ply == self.moves.len() {
+                    return Err(String::from("that's the end of the game"));
+                }
+                self.go_to(self.ply + 1);
+            }
+            "back" | "b" => {
+                if self.ply == 0 {
+                    return Err(String::from("that's the start of the game"));
+                }
+                self.go_to(self.ply - 1);
+            }
+            "go" | "g" => {
+                let (number, color) = parse_move_number(words.next().unwrap_or(""))?;
+                match self.ply_for(number, color) {
+                    Some(ply) => self.go_to(ply),
+                    None => return Err(format!("the game doesn't have move {}", number)),
+                }
+            }
+            "start" => self.go_to(0),
+            "end" => self.go_to(self.moves.len()),
+            "quit" | "q" => return Ok(false),
+            _ => return Err(String::from(HELP)),
+        }
+        Ok(true)
+    }
+
+    // commands a line at a time
+    pub fn run<R: BufRead>(&mut self, input: R) {
+        self.show();
+        for line in input.lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            match self.command(line.as_str()) {
+                Ok(true) => self.show(),
+                Ok(false) => break,
+                Err(message) => println!("{}", message),
+            }
+        }
+    }
+
+    // the arrow keys step through the game, falls back to run when there's no terminal
+    pub fn run_in_terminal(&mut self) {
+        let term = Term::stdout();
+        if !term.is_term() {
+            let stdin = io::stdin();
+            self.run(stdin.lock());
+            return;
+        }
+        println!("{}", HELP);
+        self.show();
+        loop {
+            let command = match term.read_key() {
+                Ok(Key::ArrowRight) | Ok(Key::Enter) => String::from("next"),
+                Ok(Key::ArrowLeft) | Ok(Key::Backspace) => String::from("back"),
+                Ok(Key::ArrowUp) | Ok(Key::Home) => String::from("start"),
+                Ok(Key::ArrowDown) | Ok(Key::End) => String::from("end"),
+                Ok(Key::Escape) => String::from("quit"),
+                Ok(Key::Char(c)) => {
+                    print!("> ");
+                    let _ = io::stdout().flush();
+                    match term.read_line_initial_text(c.to_string().as_str()) {
+                        Ok(line) => line,
+                        Err(_) => break,
+                    }
+                }
+                Ok(_) => continue,
+                Err(_) => break,
+            };
+            match self.command(command.as_str()) {
+                Ok(true) => self.show(),
+                Ok(false) => break,
+                Err(message) => println!("{}", message),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_notation::fen_reader;
+    use crate::move_generator::{gen_legal_moves, MoveType};
+
+    const PGN: &str = r#"[Event "One"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0
+
+[Event "Two"]
+[White "C"]
+[Black "D"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 b - - 0 20"]
+[SetUp "1"]
+[Result "*"]
+
+20... Kd7 21. e4 *
+"#;
+
+    fn load(game: usize) -> Replay {
+        Replay::load(PGN.as_bytes(), game)
+            .unwrap()
+            .with_ai_settings("depth=2")
+            .unwrap()
+            .with_board_renderer(BoardRenderer::new().with_ansi(false))
+    }
+
+    #[test]
+    fn test_load() {
+        let replay = load(1);
+        assert_eq!(replay.title, "A vs B, One ?");
+        assert_eq!(
+            replay.sans,
+            vec!["e4", "e5", "Qh5", "Nc6", "Bc4", "Nf6", "Qxf7#"]
+        );
+        let replay = load(2);
+        assert_eq!(replay.sans, vec!["Kd7", "e4"]);
+        assert_eq!(replay.result, "*");
+        assert!(Replay::load(PGN.as_bytes(), 3).is_err());
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut replay = load(1);
+        assert!(replay.command("back").is_err());
+        assert_eq!(replay.command(""), Ok(true));
+        assert_eq!(replay.command("n"), Ok(true));
+        assert_eq!(replay.ply(), 2);
+        assert_eq!(replay.command("b"), Ok(true));
+        assert_eq!(replay.ply(), 1);
+        assert_eq!(replay.command("go 3"), Ok(true));
+        assert_eq!(replay.ply(), 4);
+        assert_eq!(replay.command("go 2..."), Ok(true));
+        assert_eq!(replay.ply(), 3);
+        assert!(replay.command("go 9").is_err());
+        assert!(replay.command("go x").is_err());
+        assert_eq!(replay.command("end"), Ok(true));
+        assert_eq!(replay.ply(), 7);
+        assert!(replay.command("next").is_err());
+        assert_eq!(replay.command("start"), Ok(true));
+        assert_eq!(
+            fen_reader::make_fen(replay.board()),
+            fen_reader::make_fen(&GameState::starting_game())
+        );
+        assert_eq!(replay.command("q"), Ok(false));
+
+        // a game from a fen starting with black to move
+        let mut replay = load(2);
+        assert_eq!(replay.ply_for(20, Color::Black), Some(0));
+        assert_eq!(replay.ply_for(21, Color::White), Some(1));
+        assert_eq!(replay.ply_for(20, Color::White), None);
+        assert_eq!(replay.command("go 21"), Ok(true));
+        assert_eq!(replay.numbered(1, "e4"), "21. e4");
+        assert_eq!(replay.numbered(0, "Kd7"), "20... Kd7");
+    }
+
+    #[test]
+    fn test_describe() {
+        let mut replay = load(1);
+        replay.go_to(6);
+        let lines = replay.describe();
+        assert_eq!(lines[1], "played  4. Qxf7#");
+        assert_eq!(lines[2], "engine  agrees, black gets mated at depth 2");
+        replay.go_to(1);
+        let lines = replay.describe();
+        assert_eq!(lines[1], "played  1... e5");
+        assert!(lines[2].starts_with("engine  "));
+        replay.go_to(7);
+        assert_eq!(replay.describe()[1], "end of the game  1-0");
+    }
+
+    #[test]
+    fn test_describe_underpromotion() {
+        let pgn = "[FEN \"8/P7/8/8/8/8/k7/4K3 w - - 0 1\"]\n[SetUp \"1\"]\n\n1. a8=N *";
+        let mut replay = Replay::load(pgn.as_bytes(), 1).unwrap();
+        // the engine queens, same squares as the knight but not the same move
+        let queen = gen_legal_moves(replay.board(), Color::White)
+            .into_iter()
+            .find(|m| m.move_type() == &MoveType::Promotion(PieceType::Queen))
+            .unwrap();
+        replay.analyses[0] = Some(Analysis {
+            evaluation: Evaluation {
+                score: 900.0,
+                mated_player: None,
+            },
+            best: Some(queen),
+            depth: 2,
+        });
+        let lines = replay.describe();
+        assert_eq!(lines[1], "played  1. a8=N");
+        assert!(lines[2].starts_with("engine  a8=Q"), "{}", lines[2]);
+    }
+}
@@ -0,0 +1,554 @@
+use super::{Command, Game, Reply, Turn};
+use crate::ai::SearchInfo;
+use crate::board::*;
+use crate::chess_notation::pgn::{format_clock, wrap_move_text};
+use crate::chess_notation::{move_to_san, san};
+use crate::game::clock::Player;
+use crate::game_state::GameState;
+use crate::move_generator::Move;
+use console::{measure_text_width, truncate_str, Key, Term};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/*
+full screen terminal ui, cargo run -- --tui
+
+    0       title
+    1-9     the board                  the players and clocks, then the moves
+    11-12   what the engine's thinking, depth score nodes time and the line it expects
+    14      the move being typed
+    15      the legal moves it could be, tab fills in as much as they agree on
+    17-     whatever the last command had to say
+
+the screen's redrawn in place, the engine's panel while it searches and the clocks
+every so often while they run. plain ansi, so a linux console works too
+*/
+
+const BOARD_ROW: usize = 1;
+const PANEL_COLUMN: usize = 32;
+const MOVE_LIST_ROW: usize = 4;
+const MOVE_LIST_ROWS: usize = 6;
+const ANALYSIS_ROW: usize = 11;
+const INPUT_ROW: usize = 14;
+const CANDIDATES_ROW: usize = 15;
+const MESSAGE_ROW: usize = 17;
+const WIDTH: usize = 79;
+const COMMANDS: [&str; 11] = [
+    "undo", "hint", "resign", "draw", "flip", "fen", "pgn", "moves", "eval", "save", "help",
+];
+// how often the running clock is redrawn
+const TICK: Duration = Duration::from_millis(200);
+
+// the bits of the screen that get drawn from more than one thread
+struct Screen {
+    term: Term,
+    // held while drawing so the clocks and the engine don't write over each other
+    lock: Arc<Mutex<()>>,
+    // the engine panel, kept so a full redraw can put it back
+    analysis: Arc<Mutex<Vec<String>>>,
+    message: Vec<String>,
+}
+
+impl Screen {
+    fn new(term: Term) -> Screen {
+        Screen {
+            term,
+            lock: Arc::new(Mutex::new(())),
+            analysis: Arc::new(Mutex::new(vec![])),
+            message: vec![],
+        }
+    }
+
+    fn say(&mut self, message: &str) {
+        self.message = wrap(message, WIDTH);
+    }
+}
+
+// writes lines starting at row without moving the cursor, for the other threads
+fn draw_rows(term: &Term, lock: &Mutex<()>, row: usize, column: usize, lines: &[String]) {
+    let _guard = lock.lock().unwrap();
+    // save and restore the cursor, it's sitting at the end of what's being typed
+    let _ = term.write_str("\x1b7");
+    for (i, line) in lines.iter().enumerate() {
+        let _ = term.move_cursor_to(column, row + i);
+        let _ = term.write_str(format!("{}\x1b[K", line).as_str());
+    }
+    let _ = term.write_str("\x1b8");
+    let _ = term.flush();
+}
+
+// keeps the clocks on the screen going while someone thinks, stops when it's dropped
+struct Ticker {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl Ticker {
+    fn start(
+        screen: &Screen,
+        white: Player,
+        black: Player,
+        to_move: Color,
+        started: Instant,
+    ) -> Ticker {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let term = screen.term.clone();
+        let lock = screen.lock.clone();
+        let handle = thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                let lines = clock_lines(&white, &black, to_move, started.elapsed());
+                draw_rows(&term, &lock, BOARD_ROW, PANEL_COLUMN, &lines);
+                thread::sleep(TICK);
+            }
+        });
+        Ticker {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+// breaks lines longer than width on spaces, shorter ones are left alone
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    for line in text.lines() {
+        if measure_text_width(line) <= width {
+            lines.push(String::from(line));
+        } else {
+            lines.extend(wrap_move_text(line, width).lines().map(String::from));
+        }
+    }
+    lines
+}
+
+// the players and their clocks, > marks whoever's to move
+fn clock_lines(white: &Player, black: &Player, to_move: Color, thinking: Duration) -> Vec<String> {
+    [(Color::White, white), (Color::Black, black)]
+        .iter()
+        .map(|&(color, player)| {
+            let marker = if color == to_move { '>' } else { ' ' };
+            let clock = if !player.is_timed() {
+                String::new()
+            } else if color == to_move {
+                format_clock(player.time_left_while_thinking(thinking))
+            } else {
+                format_clock(player.time_remaining())
+            };
+            let side = format!("{}", color);
+            format!("{} {:<6} {:<14} {}", marker, side, player.name(), clock)
+        })
+        .collect()
+}
+
+// the last rows full moves, 12. e4 e5 a line, numbered from the fen
+pub fn move_list_lines(moves: &[String], start_fen: &str, rows: usize) -> Vec<String> {
+    let fields: Vec<&str> = start_fen.split_whitespace().collect();
+    let mut move_number = fields
+        .get(5)
+        .and_then(|n| n.parse::<u32>().ok())
+        .unwrap_or(1);
+    let mut lines = vec![];
+    let mut moves = moves.iter();
+    if fields.get(1) == Some(&"b") {
+        if let Some(m) = moves.next() {
+            lines.push(format!("{}... {}", move_number, m));
+            move_number += 1;
+        }
+    }
+    while let Some(white) = moves.next() {
+        match moves.next() {
+            Some(black) => lines.push(format!("{}. {} {}", move_number, white, black)),
+            None => lines.push(format!("{}. {}", move_number, white)),
+        }
+        move_number += 1;
+    }
+    let skip = lines.len().saturating_sub(rows);
+    lines.split_off(skip)
+}
+
+// the moves of a line in san, played out from board
+pub fn line_san(board: &GameState, line: &[Move]) -> Vec<String> {
+    let mut board = board.clone_to_game_state();
+    line.iter()
+        .map(|&m| {
+            let mut m = m;
+            let san = move_to_san(&m, &board);
+            board.make_move_mut(&mut m);
+            san
+        })
+        .collect()
+}
+
+pub fn analysis_lines(info: &SearchInfo, board: &GameState) -> Vec<String> {
+    let summary = format!(
+        "engine  depth {}  {}  {} nodes  {:.1}s",
+        info.depth,
+        super::describe_score(&info.evaluation),
+        info.nodes,
+        info.elapsed.as_secs_f32()
+    );
+    let line = line_san(board, info.pv.as_slice()).join(" ");
+    vec![
+        summary,
+        truncate_str(format!("        {}", line).as_str(), WIDTH, "..").to_string(),
+    ]
+}
+
+// what tab does with what's been typed, fills in as much as the candidates that match
+// agree on, returns that and the matches. an exact case match wins over ignoring case
+pub fn complete(typed: &str, candidates: &[String]) -> (String, Vec<String>) {
+    let mut matches: Vec<String> = candidates
+        .iter()
+        .filter(|c| c.starts_with(typed))
+        .cloned()
+        .collect();
+    if matches.is_empty() {
+        let lower = typed.to_lowercase();
+        matches = candidates
+            .iter()
+            .filter(|c| c.to_lowercase().starts_with(lower.as_str()))
+            .cloned()
+            .collect();
+    }
+    let first = match matches.first() {
+        Some(first) => first.clone(),
+        None => return (String::from(typed), matches),
+    };
+    let common = matches.iter().fold(first, |common, m| {
+        common
+            .chars()
+            .zip(m.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a)
+            .collect()
+    });
+    if common.len() < typed.len() {
+        return (String::from(typed), matches);
+    }
+    (common, matches)
+}
+
+impl Game {
+    // plays the game full screen, see the top of the file
+    // falls back on the plain console when there's no terminal to draw on
+    pub fn run_tui(mut self, humans: &[Color]) {
+        let term = Term::stdout();
+        if !term.is_term() {
+            println!("the tui needs a terminal, playing in the console instead");
+            let stdin = io::stdin();
+            self.play(stdin.lock(), humans);
+            return;
+        }
+        for &color in [Color::White, Color::Black].iter() {
+            let name = if humans.contains(&color) {
+                "Human"
+            } else {
+                "chess_engine"
+            };
+            self.player_mut(color).set_name(name);
+        }
+        self.enable_logging = humans.len() == 2;
+        self.humans = humans.to_vec();
+        self.flipped = humans == [Color::Black];
+        let mut screen = Screen::new(term);
+        screen.say("type help for the commands, tab fills in moves");
+        if let Err(err) = self.tui_loop(&mut screen, humans) {
+            let _ = screen.term.clear_screen();
+            println!("the terminal went away: {}", err);
+        }
+    }
+
+    fn tui_loop(&mut self, screen: &mut Screen, humans: &[Color]) -> io::Result<()> {
+        screen.term.clear_screen()?;
+        self.turn_started = Instant::now();
+        loop {
+            if let Some((result, reason)) = self.find_game_end() {
+                screen.term.clear_screen()?;
+                self.finish_game(result, reason.as_str());
+                return Ok(());
+            }
+            self.draw(screen)?;
+            let color = self.board.get_player_to_move();
+            let turn = {
+                let _ticker = if self.time_control != super::TimeControl::Unlimited {
+                    Some(Ticker::start(
+                        screen,
+                        self.white.clone(),
+                        self.black.clone(),
+                        color,
+                        self.turn_started,
+                    ))
+                } else {
+                    None
+                };
+                if humans.contains(&color) {
+                    self.tui_human_turn(screen, color, humans)?
+                } else {
+                    self.tui_ai_turn(screen, color)
+                }
+            };
+            match turn {
+                Turn::Moved => self.write_log(),
+                Turn::OutOfTime => {
+                    screen.term.clear_screen()?;
+                    self.flag_fell(color);
+                    return Ok(());
+                }
+                Turn::Ended(result, reason) => {
+                    screen.term.clear_screen()?;
+                    self.finish_game(result, reason.as_str());
+                    return Ok(());
+                }
+                Turn::Stopped => {
+                    screen.term.clear_screen()?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn draw(&self, screen: &Screen) -> io::Result<()> {
+        let _guard = screen.lock.lock().unwrap();
+        let term = &screen.term;
+        let to_move = self.board.get_player_to_move();
+        let mut rows: Vec<(usize, usize, String)> = vec![(0, 0, String::from("chess_engine"))];
+        let board = self.board_renderer().with_ansi(true).lines(&self.board);
+        rows.extend(
+            board
+                .into_iter()
+                .enumerate()
+                .map(|(i, line)| (BOARD_ROW + i, 0, line)),
+        );
+        let thinking = self.turn_started.elapsed();
+        let clocks = clock_lines(&self.white, &self.black, to_move, thinking);
+        rows.extend(
+            clocks
+                .into_iter()
+                .enumerate()
+                .map(|(i, line)| (BOARD_ROW + i, PANEL_COLUMN, line)),
+        );
+        rows.push((MOVE_LIST_ROW - 1, PANEL_COLUMN, String::from("moves")));
+        let moves = move_list_lines(&self.moves, self.start_fen.as_str(), MOVE_LIST_ROWS);
+        for i in 0..MOVE_LIST_ROWS {
+            let line = moves.get(i).cloned().unwrap_or_default();
+            rows.push((MOVE_LIST_ROW + i, PANEL_COLUMN, line));
+        }
+        let analysis = screen.analysis.lock().unwrap().clone();
+        for i in 0..2 {
+            let line = analysis.get(i).cloned().unwrap_or_default();
+            rows.push((ANALYSIS_ROW + i, 0, line));
+        }
+        for (row, column, line) in rows {
+            term.move_cursor_to(column, row)?;
+            if column == 0 {
+                term.clear_line()?;
+            }
+            term.write_str(format!("{}\x1b[K", line).as_str())?;
+        }
+        term.move_cursor_to(0, MESSAGE_ROW)?;
+        term.clear_to_end_of_screen()?;
+        let (height, _) = term.size();
+        let room = (height as usize).saturating_sub(MESSAGE_ROW + 1);
+        for line in screen.message.iter().take(room) {
+            term.write_line(line.as_str())?;
+        }
+        term.flush()
+    }
+
+    // draws the prompt and what's been typed, with the cursor after it
+    fn draw_input(
+        &self,
+        screen: &Screen,
+        color: Color,
+        typed: &str,
+        matches: &[String],
+    ) -> io::Result<()> {
+        let _guard = screen.lock.lock().unwrap();
+        let term = &screen.term;
+        let prompt = format!("{} to move > ", color);
+        term.move_cursor_to(0, CANDIDATES_ROW)?;
+        term.clear_line()?;
+        term.write_str(truncate_str(matches.join(" ").as_str(), WIDTH, "..").as_ref())?;
+        term.move_cursor_to(0, INPUT_ROW)?;
+        term.clear_line()?;
+        term.write_str(format!("{}{}", prompt, typed).as_str())?;
+        term.flush()
+    }
+
+    // what tab can fill in, the legal moves and the commands
+    fn candidates(&self, color: Color) -> Vec<String> {
+        let mut candidates = self.legal_moves_san(color);
+        candidates.extend(COMMANDS.iter().map(|command| String::from(*command)));
+        candidates
+    }
+
+    // reads keys till there's a move or a command that ends the turn
+    fn tui_human_turn(
+        &mut self,
+        screen: &mut Screen,
+        color: Color,
+        humans: &[Color],
+    ) -> io::Result<Turn> {
+        let mut candidates = self.candidates(color);
+        let mut typed = String::new();
+        let mut matches: Vec<String> = vec![];
+        loop {
+            self.draw_input(screen, color, typed.as_str(), &matches)?;
+            match screen.term.read_key()? {
+                Key::Char(c) => {
+                    typed.push(c);
+                    matches = complete(typed.trim(), &candidates).1;
+                }
+                Key::Backspace => {
+                    typed.pop();
+                    matches = if typed.is_empty() {
+                        vec![]
+                    } else {
+                        complete(typed.trim(), &candidates).1
+                    };
+                }
+                Key::Tab => {
+                    let (completed, found) = complete(typed.trim(), &candidates);
+                    typed = completed;
+                    matches = found;
+                }
+                Key::Escape => {
+                    typed.clear();
+                    matches.clear();
+                }
+                Key::Enter => {
+                    let text = String::from(typed.trim());
+                    typed.clear();
+                    matches.clear();
+                    if text.is_empty() {
+                        continue;
+                    }
+                    if let Some(command) = Command::parse(text.as_str()) {
+                        match self.run_command(command, color, humans) {
+                            Reply::Done(turn) => return Ok(turn),
+                            Reply::Say(message) => screen.say(message.as_str()),
+                        }
+                        // an undo changes what there is to play
+                        candidates = self.candidates(color);
+                        self.draw(screen)?;
+                        continue;
+                    }
+                    let mut m = match san::parse_san_for(text.as_str(), &self.board, color) {
+                        Ok(m) => m,
+                        Err(err) => {
+                            screen.say(err.to_string().as_str());
+                            self.draw(screen)?;
+                            continue;
+                        }
+                    };
+                    screen.say("");
+                    if !self.stop_clock() {
+                        return Ok(Turn::OutOfTime);
+                    }
+                    let annotation = self.annotation(color, None);
+                    self.play_move(&mut m, annotation);
+                    return Ok(Turn::Moved);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // the ai moves, the engine panel shows each depth as it finishes
+    fn tui_ai_turn(&mut self, screen: &Screen, color: Color) -> Turn {
+        let root = self.board.clone_to_game_state();
+        let term = screen.term.clone();
+        let lock = screen.lock.clone();
+        let analysis = screen.analysis.clone();
+        self.ai_mut(color)
+            .set_progress(Some(Box::new(move |info: &SearchInfo| {
+                let lines = analysis_lines(info, &root);
+                draw_rows(&term, &lock, ANALYSIS_ROW, 0, &lines);
+                *analysis.lock().unwrap() = lines;
+            })));
+        let thought = self.think(color);
+        self.ai_mut(color).set_progress(None);
+        let (mut m, eval) = match thought {
+            Some(thought) => thought,
+            None => return Turn::Stopped,
+        };
+        if !self.stop_clock() {
+            return Turn::OutOfTime;
+        }
+        let annotation = self.annotation(color, eval);
+        self.play_move(&mut m, annotation);
+        Turn::Moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_notation::fen_reader;
+    use crate::game::TimeControl;
+
+    #[test]
+    fn test_complete() {
+        let candidates: Vec<String> = ["Nf3", "Nc3", "Nh3", "e4", "e3", "Bb5", "help"]
+            .iter()
+            .map(|c| String::from(*c))
+            .collect();
+        assert_eq!(complete("N", &candidates).0, "N");
+        assert_eq!(complete("N", &candidates).1.len(), 3);
+        assert_eq!(
+            complete("Nf", &candidates),
+            (String::from("Nf3"), vec![String::from("Nf3")])
+        );
+        assert_eq!(complete("he", &candidates).0, "help");
+        // b is a pawn, there's no b pawn move so it's the bishop
+        assert_eq!(complete("b", &candidates).0, "Bb5");
+        assert!(complete("Qh5", &candidates).1.is_empty());
+    }
+
+    #[test]
+    fn test_move_list_lines() {
+        let moves: Vec<String> = ["e5", "Nf3", "Nc6", "Bb5"]
+            .iter()
+            .map(|m| String::from(*m))
+            .collect();
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        assert_eq!(
+            move_list_lines(&moves, fen, 10),
+            vec!["1... e5", "2. Nf3 Nc6", "3. Bb5"]
+        );
+        assert_eq!(move_list_lines(&moves, fen, 1), vec!["3. Bb5"]);
+    }
+
+    #[test]
+    fn test_panels() {
+        let board = fen_reader::make_game_state(fen_reader::INITIAL_BOARD).unwrap();
+        let line: Vec<Move> = ["e4", "e5", "Qh5"]
+            .iter()
+            .scan(board.clone_to_game_state(), |board, san| {
+                let mut m = san::parse_san(san, board).unwrap();
+                board.make_move_mut(&mut m);
+                Some(m)
+            })
+            .collect();
+        assert_eq!(line_san(&board, &line), vec!["e4", "e5", "Qh5"]);
+
+        let white = Player::new("Human", TimeControl::parse("300").unwrap());
+        let black = Player::new("chess_engine", TimeControl::parse("300").unwrap());
+        let lines = clock_lines(&white, &black, Color::Black, Duration::from_secs(61));
+        assert_eq!(lines[0], "  white  Human          0:05:00");
+        assert_eq!(lines[1], "> black  chess_engine   0:03:59");
+    }
+}
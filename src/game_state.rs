@@ -100,7 +100,8 @@ impl BoardTrait for GameState {
             // update white to move flag
             self.player_to_move = piece_to_move.color.opposite();
 
-            // update 50 move rule draw counter
+            // update 50 move rule draw counter, keep the old one around for unmake
+            m.old_half_move_clock = Some(self.half_move_clock);
             if m.captured.is_none() && piece_to_move.piece_type != PieceType::Pawn {
                 self.half_move_clock = self.half_move_clock + 1;
             } else {
@@ -141,9 +142,6 @@ impl BoardTrait for GameState {
                 self.full_move_number = self.full_move_number + 1;
             }
 
-            // update draw clock
-            m.old_half_move_clock = Some(self.half_move_clock);
-
             // do any special logic,
             match m.move_type() {
                 MoveType::Castling { rook_from, rook_to } => {
@@ -1106,7 +1104,7 @@ mod tests {
     #[test]
     fn test_make_unmake_castles() {
         let fen = "r3k2r/pppbbp2/2np1q1p/1N2p1p1/2BPP2n/2P2N2/PP2QPPP/R1B1K2R w KQkq - 0 1";
-        let mut game_state = fen_reader::make_game_state(fen);
+        let mut game_state = fen_reader::make_game_state(fen).unwrap();
         let mut moves = gen_legal_moves(&game_state, Color::White);
         // try to manually castle
         let castle_move_opt = moves
@@ -1135,7 +1133,7 @@ mod tests {
     fn test_make_unmake_en_passant() {
         // black can en passant
         let fen = "rnbqkbnr/1pp1pp1p/8/P2P2P1/pP1p2p1/8/2P1PP1P/RNBQKBNR b KQkq b3 0 1";
-        let mut game_state = fen_reader::make_game_state(fen);
+        let mut game_state = fen_reader::make_game_state(fen).unwrap();
         println!("{}", game_state.en_passant_target.unwrap());
         assert_eq!(game_state.en_passant_target.is_some(), true);
         // let mut moves = gen_legal_moves(&game_state, Color::White);
@@ -1150,7 +1148,7 @@ mod tests {
         }
 
         let fen = "rnbqkbnr/1pp1pp1p/8/P2P2P1/p2p2p1/8/1PP1PP1P/RNBQKBNR w KQkq - 0 1";
-        let mut game_state = fen_reader::make_game_state(fen);
+        let mut game_state = fen_reader::make_game_state(fen).unwrap();
         assert_eq!(game_state.en_passant_target.is_some(), false);
         let mut moves = gen_legal_moves(&game_state, Color::White);
         println!("moves generated");
@@ -1167,7 +1165,7 @@ mod tests {
     fn test_unmake_move_mut_captures() {
         // fairly simple first postion
         let fen_scandi = "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2";
-        let mut game_state = fen_reader::make_game_state(fen_scandi);
+        let mut game_state = fen_reader::make_game_state(fen_scandi).unwrap();
         //exd5
         let from = Coordinate::new(5, 4);
         let to = from.add(-1, 1);
@@ -1191,7 +1189,7 @@ mod tests {
         // weird position with lots of captures
         // half move is broken btw, so we manually set it to 0 for the moment
         let captures_fen = "4kb1r/1pp2ppp/4pn2/2rp4/q1BPP1b1/p1Pn1N1P/4KPP1/RNBQ3R b k - 0 15";
-        let mut game_state = fen_reader::make_game_state(captures_fen);
+        let mut game_state = fen_reader::make_game_state(captures_fen).unwrap();
         let a4 = Coordinate::new(1, 4);
         let d1 = Coordinate::new(4, 1);
         let mut takes_move = Move::new(
@@ -1213,7 +1211,7 @@ mod tests {
     #[test]
     fn test_make_move_captures_pawn_promotion() {
         let fen = "r3k1r1/1b1p1p2/p3pp2/B1b4p/P3P3/1B3P2/1pP4P/R2K1R2 b q - 1 22";
-        let mut game_state = fen_reader::make_game_state(fen);
+        let mut game_state = fen_reader::make_game_state(fen).unwrap();
         let b2 = Coordinate::new(2, 2);
         let a1 = Coordinate::new(1, 1);
         let mut m = Move::new(
@@ -1234,7 +1232,7 @@ mod tests {
 
     fn test_make_unmake_captures_pawn_promotion() {
         let fen = "r3k1r1/1b1p1p2/pB2pp2/P1b4p/4P3/1B3P2/1pP4P/R2K1R2 b q - 0 22";
-        let mut game_state = fen_reader::make_game_state(fen);
+        let mut game_state = fen_reader::make_game_state(fen).unwrap();
         let b2 = Coordinate::new(2, 2);
         let a1 = Coordinate::new(1, 1);
         let mut m = Move::new(
@@ -1276,7 +1274,7 @@ mod tests {
         // fairly simple first postion
         let fen_scandi = "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2";
         let fen_after = "rnbqkbnr/ppp1pppp/8/3P4/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2";
-        let mut game_state = fen_reader::make_game_state(fen_scandi);
+        let mut game_state = fen_reader::make_game_state(fen_scandi).unwrap();
         //exd5
         let from = Coordinate::new(5, 4);
         let to = from.add(-1, 1);
@@ -1298,7 +1296,7 @@ mod tests {
 
         // weird position with lots of captures
         let captures_fen = "4kb1r/1pp2ppp/4pn2/2rp4/q1BPP1b1/p1Pn1N1P/4KPP1/RNBQ3R b k - 14 15";
-        let mut game_state = fen_reader::make_game_state(captures_fen);
+        let mut game_state = fen_reader::make_game_state(captures_fen).unwrap();
         let a4 = Coordinate::new(1, 4);
         let d1 = Coordinate::new(4, 1);
         let mut takes_move = Move::new(
@@ -1522,7 +1520,7 @@ mod tests {
     #[test]
     fn test_remove_piece() {
         let added_fen = "rnbqkbnr/pppppppp/8/8/4N3/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
-        let mut game_state = fen_reader::make_game_state(added_fen);
+        let mut game_state = fen_reader::make_game_state(added_fen).unwrap();
         assert_eq!(
             fen_reader::make_fen(&game_state),
             added_fen,
@@ -1708,7 +1706,7 @@ mod tests {
     #[test]
     fn test_en_passant() {
         let fen = "r2q1rk1/p2p1ppp/1pn1pn2/2p1P1B1/1b1P4/2N2N1P/PPP2PP1/R2Q1RK1 b - - 0 6";
-        let mut game_state = fen_reader::make_game_state(fen);
+        let mut game_state = fen_reader::make_game_state(fen).unwrap();
         let d7 = Coordinate::new(4, 7);
         let d5 = d7.add(0, -2);
         let b_moves = gen_legal_moves(&game_state, Color::Black);
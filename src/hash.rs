@@ -1,3 +1,5 @@
+pub mod polyglot;
+
 use crate::board::{BoardTrait, Color, Coordinate, PieceType};
 use rand::Rng;
 
@@ -241,16 +243,16 @@ mod test {
         let hash = hasher.hash_board(&board);
         let hash2 = hasher.hash_board(&board);
         assert_eq!(hash, hash2, "same board, same hash");
-        let board2 = fen_reader::make_board(fen_reader::TEST_BOARD_1);
+        let board2 = fen_reader::make_board(fen_reader::TEST_BOARD_1).unwrap();
         let hash2 = hasher.hash_board(&board2);
         assert_ne!(hash, hash2, "different board, different hash");
-        let board2 = fen_reader::make_board(fen_reader::BLACK_IN_CHECK);
+        let board2 = fen_reader::make_board(fen_reader::BLACK_IN_CHECK).unwrap();
         let hash2 = hasher.hash_board(&board2);
         assert_ne!(hash, hash2, "different board, different hash");
-        let board2 = fen_reader::make_board(fen_reader::WHITE_IN_CHECK);
+        let board2 = fen_reader::make_board(fen_reader::WHITE_IN_CHECK).unwrap();
         let hash2 = hasher.hash_board(&board2);
         assert_ne!(hash, hash2, "different board, different hash");
-        let board2 = fen_reader::make_board(fen_reader::TEST_BOARD_2);
+        let board2 = fen_reader::make_board(fen_reader::TEST_BOARD_2).unwrap();
         let hash2 = hasher.hash_board(&board2);
         assert_ne!(hash, hash2, "different board, different hash");
     }
@@ -317,8 +319,10 @@ mod test {
             &Color::White,
             &Coordinate::new(1, 1),
         );
-        let mut board =
-            fen_reader::make_board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/1NBQKBNR w KQkq - 0 1");
+        let mut board = fen_reader::make_board(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/1NBQKBNR w KQkq - 0 1",
+        )
+        .unwrap();
         let hash = hasher.hash_board(&board);
         assert_eq!(hash, hash2);
     }
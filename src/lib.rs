@@ -6,6 +6,7 @@ extern crate test;
 pub mod ai;
 pub mod board;
 pub mod board_console_printer;
+pub mod diagram;
 pub mod chess_notation;
 pub mod game;
 pub mod hash;
@@ -4,18 +4,27 @@ use chess_engine::{bit_board, search};
 use chess_engine::bit_board::BitBoard;
 use chess_engine::board::{Board, BoardTrait};
 use chess_engine::board::{Color, Coordinate, Piece, PieceType};
-use chess_engine::board_console_printer::print_bit_board;
+use chess_engine::ai::evaluator::{self, ClassicEvaluator, EvalParams, Evaluator};
+use chess_engine::ai::book::{Book, BookBuilder, BookSelection};
+use chess_engine::ai::tablebase::Tablebases;
+use chess_engine::ai::{annotate, nnue, test_suite, tuner, Ai};
+use chess_engine::board_console_printer::{print_bit_board, print_board, BoardRenderer};
+use chess_engine::diagram::Diagram;
 use chess_engine::chess_notation::{self, fen_reader};
 use chess_engine::game_state::GameState;
-use chess_engine::move_generator::{Move, MoveType, plmg};
+use chess_engine::move_generator::{find_pinned_pieces, Move, MoveType, plmg};
 use chess_engine::move_generator::pseudo_legal_move_generator;
 use chess_engine::{chess_notation::pgn, game, game_state};
+use chess_engine::game::TimeControl;
+use chess_engine::chess_notation::epd;
+use chess_engine::chess_notation::pgn_reader::PgnReader;
 use getopts::Options;
 use pgn::Game as notated_game;
 use regex::*;
 use std::fs::{self, File, Metadata};
-use std::io::Read;
+use std::io::{Read, Write};
 use std::path::Path;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use std::{env, path};
 
@@ -27,11 +36,382 @@ use std::{env, path};
 41 micro seconds, 52x's faster
  */
 
+// bad input on the command line is the user's typo, not a bug, so say what was wrong and exit
+// instead of panicking with a backtrace
+fn exit_with_error(message: String) -> ! {
+    eprintln!("{}\nfor the options run \ncargo run -- --help", message);
+    std::process::exit(1)
+}
+
 fn print_help_menu() {
     println!("For ai vs ai game \ncargo run -- --ai\n");
-    println!("To read a pgn from /Games and have the AI consider it \ncargo run -- --sim\n");
+    println!("To go over Games/1.pgn with the engine, like --replay \ncargo run -- --sim\n");
     println!("For help menu run \ncargo run -- --help\n");
     println!("For human vs ai game \ncargo run\n");
+    println!("To step through a game with the engine's best move and eval next to the move played \ncargo run -- --replay <file.pgn>\n");
+    println!("To pick the Nth game in the --replay or annotate pgn (default 1) \ncargo run -- --game N\n");
+    println!("To set how the engine going over a --replay or annotate game searches \ncargo run -- --engine depth=6,movetime=500\n");
+    println!("To play full screen, works with -p, --ai and --resume too \ncargo run -- --tui\n");
+    println!("To carry on with a game written with the save command, people play the same sides \ncargo run -- --resume <file>\n");
+    println!("To play black \ncargo run -- --play black\n");
+    println!("To start from a position \ncargo run -- --fen \"<fen>\"\n");
+    println!("To start from a pgn game right before white's move N, N... for black's \ncargo run -- --pgn <file> --pgn-move N\n");
+    println!("To set how the ai playing white plays, search is alphabeta, minimax or random \ncargo run -- --white-ai depth=6,movetime=500,search=minimax\n");
+    println!("To set how the ai playing black plays \ncargo run -- --black-ai depth=6,movetime=500,search=minimax\n");
+    println!("For two people playing each other, moves in san (Nf3) or coordinates (g1f3), saved to /GameLogs \ncargo run -- --pvp\n");
+    println!("To tune the evaluation against a .pgn or \"fen;result\" lines \ncargo run -- tune <input> [params output file]\n");
+    println!("To see how the evaluation scores a position \ncargo run -- --eval \"<fen>\"\n");
+    println!("To have the ai evaluate with a neural network instead \ncargo run -- --nnue <weights file>\n");
+    println!("To have the ai (and --eval) use evaluation weights written by tune \ncargo run -- --params <params file>\n");
+    println!("To have the ai play out of a polyglot book \ncargo run -- --book <book.bin>\n");
+    println!("To use the book for the first N plies (default 16), or build it N plies deep \ncargo run -- --book-depth N\n");
+    println!("To always play the book move played the most \ncargo run -- --book-best\n");
+    println!("To build a polyglot opening book out of pgn files \ncargo run -- book build <book.bin> <games.pgn>...\n");
+    println!("To leave moves played in fewer than N games out of the book being built \ncargo run -- --min-games N\n");
+    println!("To only build the book from games where both players are rated at least N \ncargo run -- --min-elo N\n");
+    println!("To build endgame tablebases (3 or 4 pieces, ex. KQK KRKP KBNK) \ncargo run -- tablebase generate <dir> <material>...\n");
+    println!("To have the ai play endgames perfectly out of the tablebases in a directory \ncargo run -- --tablebases <dir>\n");
+    println!("To play on the clock, in seconds: 300, 300+2 increment, 300b2 bronstein, 300d2 delay, 40/5400 \ncargo run -- --time-control <tc>\n");
+    println!("To draw the pieces as unicode figurines \ncargo run -- --unicode\n");
+    println!("To have black at the bottom of the board \ncargo run -- --orientation black\n");
+    println!("To leave the rank and file labels off the board \ncargo run -- --no-labels\n");
+    println!("To print the board without colours \ncargo run -- --plain\n");
+    println!("To draw a position (--fen, --pgn or the starting one) as an svg diagram \ncargo run -- diagram <file.svg>\n");
+    println!("To draw an arrow on the diagram \ncargo run -- --arrow e2e4\n");
+    println!("To highlight a square on the diagram \ncargo run -- --highlight e4\n");
+    println!("To draw the ai's best move on the diagram \ncargo run -- --best\n");
+    println!("To show the pins on the diagram \ncargo run -- --pins\n");
+    println!("To mark the blunders and good moves in a pgn, into <file>_annotated.pgn by default \ncargo run -- annotate <file.pgn> [output.pgn]\n");
+    println!("To run the ai over an epd test suite \ncargo run -- --epd <file>\n");
+    println!("To set how long the ai thinks per epd position, in milliseconds \ncargo run -- --movetime N\n");
+}
+
+// what the ai evaluates with, from --nnue and --params
+struct EvalConfig {
+    network: Option<Arc<nnue::Network>>,
+    params: EvalParams,
+}
+
+impl EvalConfig {
+    // the network if there is one, otherwise the hand written evaluation with the params
+    fn new_evaluator(&self) -> Box<dyn Evaluator> {
+        match &self.network {
+            Some(network) => Box::new(nnue::NnueEvaluator::new(network.clone())),
+            None => Box::new(ClassicEvaluator::new(self.params)),
+        }
+    }
+}
+
+fn run_eval(fen: &str, renderer: &BoardRenderer, params: &EvalParams) {
+    let game_state = match fen_reader::make_game_state(fen) {
+        Ok(game_state) => game_state,
+        Err(err) => {
+            println!("couldn't read {}: {}", fen, err);
+            return;
+        }
+    };
+    renderer.print(&game_state);
+    println!("{}", evaluator::evaluate_with_trace(&game_state, params));
+}
+
+// --epd <file> --movetime N
+fn run_epd(file: &str, move_time: Duration, eval_config: &EvalConfig) {
+    let contents = match fs::read_to_string(file) {
+        Err(err) => exit_with_error(format!("couldn't read {}: {}", file, err)),
+        Ok(contents) => contents,
+    };
+    let epds = match epd::read_epd_file(contents.as_str()) {
+        Err(err) => exit_with_error(format!("couldn't read {}: {}", file, err)),
+        Ok(epds) => epds,
+    };
+    println!(
+        "running {} positions at {} ms each",
+        epds.len(),
+        move_time.as_millis()
+    );
+    let new_ai = |color: Color| {
+        let mut ai = Ai::new(color);
+        ai.set_evaluator(eval_config.new_evaluator());
+        ai
+    };
+    let total = epds.len();
+    let result = test_suite::run_suite(
+        &epds,
+        move_time,
+        &new_ai,
+        &mut |i, position| match position {
+            Ok(position) => println!("{}/{} {}", i + 1, total, position),
+            Err(err) => println!(
+                "{}/{} skipped {}: {}",
+                i + 1,
+                total,
+                epds[i].id().unwrap_or(""),
+                err
+            ),
+        },
+    );
+    println!("{}", result);
+}
+
+// tune <input> [output]
+// input is either a pgn file or a dataset with one "fen;result" per line
+fn run_tune(args: &[String]) {
+    if args.is_empty() {
+        print_help_menu();
+        return;
+    }
+    let input = Path::new(args[0].as_str());
+    let output = Path::new(args.get(1).map_or("./eval_params.txt", |s| s.as_str()));
+    let contents = match fs::read_to_string(input) {
+        Err(err) => exit_with_error(format!("couldn't read {}: {}", input.display(), err)),
+        Ok(contents) => contents
+            .trim_start_matches('\u{feff}')
+            .replace("\r\n", "\n"),
+    };
+    let positions = if input.extension().map_or(false, |e| e == "pgn") {
+        tuner::positions_from_pgn(contents.as_str())
+    } else {
+        tuner::read_dataset(contents.as_str())
+    };
+    println!("tuning on {} positions", positions.len());
+    // carry on from the last run, a file that doesn't read is left alone rather than overwritten
+    let start = if output.exists() {
+        match EvalParams::read_from_file(output) {
+            Ok(params) => params,
+            Err(err) => exit_with_error(format!("{}, fix or move it before tuning into it", err)),
+        }
+    } else {
+        EvalParams::default()
+    };
+    let k = tuner::fit_k(&positions, &start);
+    println!("fitted k {}", k);
+    let tuned = tuner::tune(
+        &positions,
+        &start,
+        k,
+        100,
+        &mut |iteration, error| match iteration {
+            0 => println!("starting error {}", error),
+            _ => println!("iteration {} error {}", iteration, error),
+        },
+    );
+    match tuned.write_to_file(output) {
+        Err(why) => exit_with_error(format!("couldn't write to {}: {}", output.display(), why)),
+        Ok(_) => println!("successfully wrote to {}\n{}", output.display(), tuned),
+    }
+}
+
+// book build <output> <pgn files...>
+fn run_book_build(args: &[String], max_plies: u16, min_games: u32, min_elo: Option<u32>) {
+    if args.len() < 2 {
+        print_help_menu();
+        return;
+    }
+    let output = Path::new(args[0].as_str());
+    let mut builder = BookBuilder::new(max_plies).with_min_games(min_games);
+    if let Some(min_elo) = min_elo {
+        builder = builder.with_min_elo(min_elo);
+    }
+    for input in args[1..].iter() {
+        let file = match File::open(input) {
+            Err(err) => exit_with_error(format!("couldn't read {}: {}", input, err)),
+            Ok(file) => file,
+        };
+        for err in builder.add_pgn(file) {
+            println!("{}: skipped game at {}", input, err);
+        }
+    }
+    let book = builder.build();
+    match fs::write(output, book.to_bytes()) {
+        Err(why) => exit_with_error(format!("couldn't write to {}: {}", output.display(), why)),
+        Ok(_) => println!(
+            "wrote {} entries from {} games to {}",
+            book.len(),
+            builder.games_used(),
+            output.display()
+        ),
+    }
+}
+
+// tablebase generate <dir> <material...>
+fn run_tablebase_generate(args: &[String]) {
+    if args.len() < 2 {
+        print_help_menu();
+        return;
+    }
+    let dir = Path::new(args[0].as_str());
+    // keep what's already there so it doesn't get built again
+    let mut tablebases = if dir.exists() {
+        Tablebases::load_dir(dir).unwrap_or_else(|err| exit_with_error(err.to_string()))
+    } else {
+        Tablebases::new()
+    };
+    for name in args[1..].iter() {
+        let started = Instant::now();
+        match tablebases.generate(name) {
+            Err(err) => exit_with_error(format!("couldn't build {}: {}", name, err)),
+            Ok(built) if built.is_empty() => println!("already have {}", name),
+            Ok(built) => println!("built {} in {:?}", built.join(", "), started.elapsed()),
+        }
+    }
+    for name in tablebases.names() {
+        let table = tablebases.get(name).unwrap();
+        println!("{} longest mate {} plies", name, table.longest_mate());
+    }
+    match tablebases.save_dir(dir) {
+        Err(why) => exit_with_error(why.to_string()),
+        Ok(_) => println!("wrote {}", dir.display()),
+    }
+}
+
+// diagram <file.svg>, of the --fen / --pgn position or the starting one
+fn run_diagram(
+    args: &[String],
+    mut game: game::Game,
+    matches: &getopts::Matches,
+    orientation: Color,
+) {
+    let file = match args.first() {
+        Some(file) => file,
+        None => {
+            println!("diagram needs an svg file to write to");
+            return;
+        }
+    };
+    let mut diagram = Diagram::new()
+        .with_orientation(orientation)
+        .with_coordinates(!matches.opt_present("no-labels"));
+    for arrow in matches.opt_strs("arrow") {
+        match (
+            arrow.get(0..2).and_then(Coordinate::from),
+            arrow.get(2..).and_then(Coordinate::from),
+        ) {
+            (Some(from), Some(to)) => diagram = diagram.with_arrow(from, to),
+            _ => exit_with_error(format!(
+                "bad --arrow: {}, it's two squares like e2e4",
+                arrow
+            )),
+        }
+    }
+    let highlights = matches
+        .opt_strs("highlight")
+        .iter()
+        .map(|square| {
+            Coordinate::from(square).unwrap_or_else(|| {
+                exit_with_error(format!(
+                    "bad --highlight: {}, it's a square like e4",
+                    square
+                ))
+            })
+        })
+        .collect();
+    diagram = diagram.with_highlights(highlights);
+    if matches.opt_present("pins") {
+        for &color in [Color::White, Color::Black].iter() {
+            diagram = diagram.with_pins(&find_pinned_pieces(game.board(), color));
+        }
+    }
+    if matches.opt_present("best") {
+        let to_move = game.board().get_player_to_move();
+        match game.analyse(to_move).and_then(|(_, best)| best) {
+            Some(best) => diagram = diagram.with_move(&best),
+            None => println!("there's no best move to draw, the game is over"),
+        }
+    }
+    match diagram.write(game.board(), Path::new(file.as_str())) {
+        Ok(()) => println!("wrote {}", file),
+        Err(err) => println!("{}", err),
+    }
+}
+
+// annotate <file.pgn> [output], every game or just the --game one
+fn run_annotate(args: &[String], matches: &getopts::Matches, eval_config: &EvalConfig) {
+    let input = match args.first() {
+        Some(input) => input,
+        None => {
+            println!("annotate needs a pgn file to go over");
+            return;
+        }
+    };
+    let output = match args.get(1) {
+        Some(output) => output.clone(),
+        None => format!("{}_annotated.pgn", input.trim_end_matches(".pgn")),
+    };
+    let game_number = match matches.opt_str("game").map(|n| n.parse::<usize>()) {
+        None => None,
+        Some(Ok(n)) if n > 0 => Some(n),
+        _ => exit_with_error(String::from(
+            "bad --game, it's 1 for the first game in the file",
+        )),
+    };
+    let settings = matches.opt_str("engine").unwrap_or_default();
+    let mut ai = Ai::new(Color::White);
+    if let Err(err) = ai.apply_settings(settings.as_str()) {
+        exit_with_error(format!("bad --engine: {}", err));
+    }
+    ai.set_evaluator(eval_config.new_evaluator());
+    let annotator = match ai.move_time() {
+        Some(move_time) => format!("chess_engine movetime={}", move_time.as_millis()),
+        None => format!("chess_engine depth={}", ai.default_search_depth),
+    };
+    let pgn = File::open(input.as_str())
+        .unwrap_or_else(|err| exit_with_error(format!("couldn't read {}: {}", input, err)));
+    let mut reader = PgnReader::new(pgn);
+    let mut number = 1;
+    if let Some(game_number) = game_number {
+        number += reader
+            .skip_games(game_number - 1)
+            .unwrap_or_else(|err| exit_with_error(format!("couldn't read {}: {}", input, err)));
+    }
+    let mut annotated = String::new();
+    for raw_game in reader {
+        let pgn_game = match raw_game
+            .map_err(|err| err.to_string())
+            .and_then(|raw_game| raw_game.parse().map_err(|err| err.to_string()))
+        {
+            Ok(pgn_game) => pgn_game,
+            Err(err) => {
+                println!("game {}: skipped, {}", number, err);
+                number += 1;
+                continue;
+            }
+        };
+        let reviewed = annotate::review(&pgn_game, &mut ai, &mut |done, total| {
+            print!("\rgame {}: {}/{} moves", number, done, total);
+            std::io::stdout().flush().unwrap();
+        })
+        .and_then(|review| {
+            Ok((
+                annotate::annotated_pgn(&pgn_game, &review, annotator.as_str())?,
+                review,
+            ))
+        });
+        let (pgn, review) = match reviewed {
+            Ok(reviewed) => reviewed,
+            Err(err) => {
+                println!("game {}: skipped, bad FEN tag: {}", number, err);
+                number += 1;
+                continue;
+            }
+        };
+        println!("\n{}", review);
+        if !annotated.is_empty() {
+            annotated.push('\n');
+        }
+        annotated.push_str(pgn.as_str());
+        number += 1;
+        if game_number.is_some() {
+            break;
+        }
+    }
+    if annotated.is_empty() {
+        println!("no games to annotate in {}", input);
+        return;
+    }
+    match fs::write(output.as_str(), annotated) {
+        Err(why) => exit_with_error(format!("couldn't write to {}: {}", output, why)),
+        Ok(_) => println!("wrote {}", output),
+    }
 }
 
 fn main() {
@@ -49,54 +429,357 @@ fn main() {
     let mut opts = Options::new();
     opts.optflag("a", "ai", "run ai versus ai game");
     opts.optflag("h", "help", "print this help menu");
-    opts.optflag("s", "sim", "run sim game to test engine");
+    opts.optflag("s", "sim", "go over Games/1.pgn with the engine");
     opts.optflag("p", "pvp", "run player vs player");
+    opts.optopt(
+        "e",
+        "eval",
+        "print the evaluation breakdown for a position",
+        "FEN",
+    );
+    opts.optopt(
+        "n",
+        "nnue",
+        "have the ai evaluate with the network in this weights file",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "params",
+        "have the ai evaluate with the weights in this params file, like the one tune writes",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "book",
+        "have the ai play out of this polyglot opening book",
+        "FILE",
+    );
+    opts.optopt("", "book-depth", "how many plies to use the book for", "N");
+    opts.optflag(
+        "",
+        "book-best",
+        "always play the book move with the most weight",
+    );
+    opts.optopt(
+        "",
+        "min-games",
+        "leave moves played in fewer games out of the book being built",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "min-elo",
+        "only build the book from games where both players are rated at least this",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "tablebases",
+        "have the ai play endgames out of the tablebases in this directory",
+        "DIR",
+    );
+    opts.optopt("", "time-control", "play on the clock, 300 sudden death, 300+2 fischer, 300b2 bronstein, 300d2 simple delay, 40/5400 moves per session (seconds)", "TC");
+    opts.optopt(
+        "",
+        "play",
+        "which side you play against the ai",
+        "white|black",
+    );
+    opts.optopt("", "fen", "start the game from this position", "FEN");
+    opts.optopt(
+        "",
+        "pgn",
+        "start the game from a position in the first game of this pgn, see --pgn-move",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "pgn-move",
+        "the move in the --pgn game to start from, 12 for white's 12th move, 12... for black's",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "white-ai",
+        "settings for the ai playing white, ex. depth=6,movetime=500,search=minimax",
+        "SETTINGS",
+    );
+    opts.optopt(
+        "",
+        "black-ai",
+        "settings for the ai playing black, ex. depth=6,movetime=500,search=minimax",
+        "SETTINGS",
+    );
+    opts.optflag("", "tui", "play full screen in the terminal");
+    opts.optopt(
+        "",
+        "resume",
+        "carry on with a game written by the save command",
+        "FILE",
+    );
+    opts.optflag("", "unicode", "draw the pieces as unicode figurines");
+    opts.optopt(
+        "",
+        "orientation",
+        "which side is at the bottom of the board",
+        "white|black",
+    );
+    opts.optflag(
+        "",
+        "no-labels",
+        "leave the rank numbers and file letters off the board",
+    );
+    opts.optflag("", "plain", "print the board without colours, for logs");
+    opts.optmulti("", "arrow", "draw an arrow on the diagram", "e2e4");
+    opts.optmulti("", "highlight", "highlight a square on the diagram", "e4");
+    opts.optflag("", "best", "draw the ai's best move on the diagram");
+    opts.optflag("", "pins", "show the pins on the diagram");
+    opts.optopt(
+        "",
+        "replay",
+        "go over a game from this pgn with the engine",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "game",
+        "which game in the --replay or annotate pgn, 1 for the first",
+        "N",
+    );
+    opts.optopt("", "engine", "settings for the engine going over a --replay or annotate game, ex. depth=6 or movetime=500", "SETTINGS");
+    opts.optopt(
+        "",
+        "epd",
+        "run the ai over the positions in an epd test suite",
+        "FILE",
+    );
+    opts.optopt(
+        "",
+        "movetime",
+        "how long the ai thinks per position, in milliseconds",
+        "N",
+    );
 
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
-        Err(f) => {
-            panic!("{}", f.to_string())
+        Err(f) => exit_with_error(f.to_string()),
+    };
+    let orientation = match matches.opt_str("orientation").as_deref() {
+        None | Some("white") => Color::White,
+        Some("black") => Color::Black,
+        Some(other) => {
+            exit_with_error(format!("bad --orientation: {}, it's white or black", other))
         }
     };
+    let mut renderer = BoardRenderer::new()
+        .with_unicode(matches.opt_present("unicode"))
+        .with_orientation(orientation)
+        .with_labels(!matches.opt_present("no-labels"));
+    if matches.opt_present("plain") {
+        renderer = renderer.with_ansi(false);
+    }
+    let book_depth = match matches.opt_str("book-depth").map(|n| n.parse::<u16>()) {
+        None => 16,
+        Some(Ok(n)) => n,
+        Some(Err(err)) => exit_with_error(format!("bad --book-depth: {}", err)),
+    };
+    if matches.free.first().map_or(false, |s| s == "book")
+        && matches.free.get(1).map_or(false, |s| s == "build")
+    {
+        let min_games = match matches.opt_str("min-games").map(|n| n.parse::<u32>()) {
+            None => 1,
+            Some(Ok(n)) => n,
+            Some(Err(err)) => exit_with_error(format!("bad --min-games: {}", err)),
+        };
+        let min_elo = match matches.opt_str("min-elo").map(|n| n.parse::<u32>()) {
+            None => None,
+            Some(Ok(n)) => Some(n),
+            Some(Err(err)) => exit_with_error(format!("bad --min-elo: {}", err)),
+        };
+        run_book_build(&matches.free[2..], book_depth, min_games, min_elo);
+        return;
+    }
+    if matches.free.first().map_or(false, |s| s == "tablebase")
+        && matches.free.get(1).map_or(false, |s| s == "generate")
+    {
+        run_tablebase_generate(&matches.free[2..]);
+        return;
+    }
+    if matches.free.first().map_or(false, |s| s == "tune") {
+        run_tune(&matches.free[1..]);
+        return;
+    }
+    let params = match matches
+        .opt_str("params")
+        .map(|file| EvalParams::read_from_file(Path::new(file.as_str())))
+    {
+        None => EvalParams::default(),
+        Some(Ok(params)) => params,
+        Some(Err(err)) => exit_with_error(format!("bad --params: {}", err)),
+    };
+    if let Some(fen) = matches.opt_str("eval") {
+        run_eval(fen.as_str(), &renderer, &params);
+        return;
+    }
     if matches.opt_present("h") {
         // println!("in help mode ");
         print_help_menu();
         return;
     }
-    if matches.opt_present("p") {
-        // @todo:
+    let network = matches.opt_str("nnue").map(|file| {
+        match nnue::Network::load_from_file(Path::new(file.as_str())) {
+            Err(err) => exit_with_error(err.to_string()),
+            Ok(network) => Arc::new(network),
+        }
+    });
+    let eval_config = EvalConfig { network, params };
+    if matches.free.first().map_or(false, |s| s == "annotate") {
+        run_annotate(&matches.free[1..], &matches, &eval_config);
         return;
     }
-    if matches.opt_present("s") {
-        // println!("in sim mode ");
-        let game = game::Game::new();
-        // read pgn file
-        //check for Games directory
-        // fix this pathing
-        let res = fs::metadata("./Games");
-        let is_dir: bool = match res {
-            Ok(f) => f.is_dir(),
-            Err(err) => false,
+    if let Some(file) = matches.opt_str("epd") {
+        let move_time = match matches.opt_str("movetime").map(|ms| ms.parse::<u64>()) {
+            None => Duration::from_millis(1000),
+            Some(Ok(ms)) => Duration::from_millis(ms),
+            Some(Err(err)) => exit_with_error(format!("bad --movetime: {}", err)),
         };
-        if is_dir {
-            let mut path_str = format!("./Games/");
-            path_str.push_str("1.pgn");
-            let path = Path::new(path_str.as_str());
-            let display = path.display();
-            let mut contents: String = match std::fs::read_to_string(&path) {
-                Err(err) => panic!("couldn't read {}: {}", display, err),
-                Ok(file) => file,
-            };
-            contents = contents
-                .trim_start_matches('\u{feff}')
-                .replace("\r\n", "\n");
-            let moves = pgn::moves_from_pgn(contents.as_str());
-            game.run_sim_game(moves);
-            return;
+        run_epd(file.as_str(), move_time, &eval_config);
+        return;
+    }
+    let book =
+        matches.opt_str("book").map(
+            |file| match Book::load_from_file(Path::new(file.as_str())) {
+                Err(err) => exit_with_error(err.to_string()),
+                Ok(book) => Arc::new(book),
+            },
+        );
+    let book_selection = if matches.opt_present("book-best") {
+        BookSelection::Best
+    } else {
+        BookSelection::Weighted
+    };
+    let tablebases = matches.opt_str("tablebases").map(|dir| {
+        match Tablebases::load_dir(Path::new(dir.as_str())) {
+            Err(err) => exit_with_error(err.to_string()),
+            Ok(tablebases) => Arc::new(tablebases),
         }
+    });
+    let time_control = match matches
+        .opt_str("time-control")
+        .map(|text| TimeControl::parse(text.as_str()))
+    {
+        None => TimeControl::Unlimited,
+        Some(Ok(time_control)) => time_control,
+        Some(Err(err)) => exit_with_error(format!("bad --time-control: {}", err)),
+    };
+    let human_color = match matches.opt_str("play").as_deref() {
+        None | Some("white") => Color::White,
+        Some("black") => Color::Black,
+        Some(other) => exit_with_error(format!("bad --play: {}, it's white or black", other)),
+    };
+    let pgn_start = matches.opt_str("pgn").map(|file| {
+        let pgn = fs::read_to_string(file.as_str())
+            .unwrap_or_else(|err| exit_with_error(format!("couldn't read {}: {}", file, err)));
+        let (number, color) = match matches
+            .opt_str("pgn-move")
+            .map(|text| chess_notation::parse_move_number(text.as_str()))
+        {
+            None => exit_with_error(String::from("--pgn needs --pgn-move to say where to start")),
+            Some(Ok(move_number)) => move_number,
+            Some(Err(err)) => exit_with_error(format!("bad --pgn-move: {}", err)),
+        };
+        (pgn, number, color)
+    });
+    let resume = matches.opt_str("resume").map(|file| {
+        fs::read_to_string(file.as_str())
+            .unwrap_or_else(|err| exit_with_error(format!("couldn't read {}: {}", file, err)))
+    });
+    let new_game = || {
+        let mut game = match &resume {
+            // everything comes back from the save, the --white-ai / --black-ai options still apply
+            Some(pgn) => game::Game::resume_from_pgn(pgn.as_str())
+                .unwrap_or_else(|err| exit_with_error(format!("bad --resume: {}", err))),
+            None => {
+                let game = match (matches.opt_str("fen"), &pgn_start) {
+                    (Some(fen), _) => game::Game::new_from_fen(fen.as_str())
+                        .unwrap_or_else(|err| exit_with_error(format!("bad --fen: {}", err))),
+                    (None, Some((pgn, number, color))) => {
+                        game::Game::new_from_pgn_at(pgn.as_str(), *number, *color)
+                            .unwrap_or_else(|err| exit_with_error(format!("bad --pgn: {}", err)))
+                    }
+                    (None, None) => game::Game::new(),
+                };
+                let mut game = game.with_human_playing(human_color);
+                game.set_time_control(time_control);
+                game
+            }
+        };
+        for (color, option) in [(Color::White, "white-ai"), (Color::Black, "black-ai")].iter() {
+            if let Some(settings) = matches.opt_str(option) {
+                game = game
+                    .with_ai_settings(*color, settings.as_str())
+                    .unwrap_or_else(|err| exit_with_error(format!("bad --{}: {}", option, err)));
+            }
+        }
+        game = game.with_board_renderer(renderer.clone());
+        if let Some(tablebases) = &tablebases {
+            game.set_ai_tablebases(tablebases.clone());
+        }
+        if let Some(book) = &book {
+            game.set_ai_book(book.clone(), book_depth, book_selection);
+        }
+        for &color in [Color::White, Color::Black].iter() {
+            game.set_ai_evaluator(color, eval_config.new_evaluator());
+        }
+        game
+    };
+    if matches.free.first().map_or(false, |s| s == "diagram") {
+        run_diagram(&matches.free[1..], new_game(), &matches, orientation);
+        return;
     }
-    let game = game::Game::new();
-    if matches.opt_present("ai") {
+    // --sim goes over Games/1.pgn
+    let replay_file = match matches.opt_str("replay") {
+        Some(file) => Some(file),
+        None if matches.opt_present("s") => Some(String::from("./Games/1.pgn")),
+        None => None,
+    };
+    if let Some(file) = replay_file {
+        let game_number = match matches.opt_str("game").map(|n| n.parse::<usize>()) {
+            None => 1,
+            Some(Ok(n)) if n > 0 => n,
+            _ => exit_with_error(String::from(
+                "bad --game, it's 1 for the first game in the file",
+            )),
+        };
+        let pgn = File::open(file.as_str())
+            .unwrap_or_else(|err| exit_with_error(format!("couldn't read {}: {}", file, err)));
+        let mut replay = game::replay::Replay::load(pgn, game_number)
+            .unwrap_or_else(|err| exit_with_error(format!("couldn't read {}: {}", file, err)))
+            .with_ai_settings(matches.opt_str("engine").unwrap_or_default().as_str())
+            .unwrap_or_else(|err| exit_with_error(format!("bad --engine: {}", err)))
+            .with_evaluator(eval_config.new_evaluator())
+            .with_board_renderer(renderer.clone());
+        replay.run_in_terminal();
+        return;
+    }
+    let game = new_game();
+    if matches.opt_present("tui") {
+        let humans = if resume.is_some() {
+            game.humans().to_vec()
+        } else if matches.opt_present("p") {
+            vec![Color::White, Color::Black]
+        } else if matches.opt_present("ai") {
+            vec![]
+        } else {
+            vec![game.human_color()]
+        };
+        game.run_tui(&humans);
+    } else if resume.is_some() {
+        game.run_resumed();
+    } else if matches.opt_present("p") {
+        game.run_player_versus_player();
+    } else if matches.opt_present("ai") {
         game.run_ai_versus_ai();
     } else {
         game.run_human_versus_ai();
@@ -134,7 +134,7 @@ pub fn get_attack_path_from_to<'a>(
     }
 }
 
-fn find_pinned_pieces(game_state: &GameState, defender_color: Color) -> Vec<Pin> {
+pub fn find_pinned_pieces(game_state: &GameState, defender_color: Color) -> Vec<Pin<'_>> {
     let attacker_color = defender_color.opposite();
     //@todo generate legal? moves
 
@@ -540,14 +540,14 @@ mod bench {
             let checks = find_checks(&game_state, Color::Black, w_moves.iter());
             let checks = find_checks(&game_state, Color::White, b_moves.iter());
         });
-        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK).unwrap();
         let w_moves = gen_pseudo_legal_moves(&game_state, Color::White);
         let b_moves = gen_pseudo_legal_moves(&game_state, Color::Black);
         b.iter(|| {
             let checks = find_checks(&game_state, Color::Black, w_moves.iter());
             let checks = find_checks(&game_state, Color::White, b_moves.iter());
         });
-        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK).unwrap();
         let w_moves = gen_pseudo_legal_moves(&game_state, Color::White);
         let b_moves = gen_pseudo_legal_moves(&game_state, Color::Black);
         b.iter(|| {
@@ -563,12 +563,12 @@ mod bench {
             let checks = get_checks(&game_state, Color::Black);
             let checks = get_checks(&game_state, Color::White);
         });
-        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK).unwrap();
         b.iter(|| {
             let checks = get_checks(&game_state, Color::Black);
             let checks = get_checks(&game_state, Color::White);
         });
-        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK).unwrap();
         b.iter(|| {
             let checks = get_checks(&game_state, Color::Black);
             let checks = get_checks(&game_state, Color::White);
@@ -583,13 +583,13 @@ mod bench {
             let checks = generate_checks(&game_state, Color::White);
         });
 
-        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK).unwrap();
         b.iter(|| {
             let checks = generate_checks(&game_state, Color::Black);
             let checks = generate_checks(&game_state, Color::White);
         });
 
-        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK).unwrap();
         b.iter(|| {
             let checks = generate_checks(&game_state, Color::Black);
             let checks = generate_checks(&game_state, Color::White);
@@ -622,7 +622,7 @@ mod bench {
             );
         });
 
-        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK).unwrap();
         let white_moves = gen_pseudo_legal_moves(&game_state, Color::White);
         let black_moves = gen_pseudo_legal_moves(&game_state, Color::Black);
         let white_checks = generate_checks(&game_state, Color::Black);
@@ -646,7 +646,7 @@ mod bench {
             );
         });
 
-        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK).unwrap();
         let white_moves = gen_pseudo_legal_moves(&game_state, Color::White);
         let black_moves = gen_pseudo_legal_moves(&game_state, Color::Black);
         let white_checks = generate_checks(&game_state, Color::Black);
@@ -693,7 +693,7 @@ mod bench {
             );
         });
 
-        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK).unwrap();
         let white_moves = gen_pseudo_legal_moves(&game_state, Color::White);
         let black_moves = gen_pseudo_legal_moves(&game_state, Color::Black);
         let white_checks = generate_checks(&game_state, Color::Black);
@@ -713,7 +713,7 @@ mod bench {
             );
         });
 
-        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK).unwrap();
         let white_moves = gen_pseudo_legal_moves(&game_state, Color::White);
         let black_moves = gen_pseudo_legal_moves(&game_state, Color::Black);
         let white_checks = generate_checks(&game_state, Color::Black);
@@ -738,7 +738,7 @@ mod bench {
     fn bench_gen_find_pinned_pieces(b: &mut Bencher) {
         let white_bishop_pinned =
             "rnbqk1nr/pppp1ppp/4p3/8/1b1P4/5N2/PPPBPPPP/RN1QKB1R b KQkq - 3 3";
-        let game_state = fen_reader::make_game_state(white_bishop_pinned);
+        let game_state = fen_reader::make_game_state(white_bishop_pinned).unwrap();
         b.iter(|| {
             let mut pins = find_pinned_pieces(&game_state, Color::White);
         })
@@ -747,7 +747,7 @@ mod bench {
     #[bench]
     fn bench_gen_attack_vectors(b: &mut Bencher) {
         let black_mates = "rnb1k1nr/pp2pp1p/Q5pb/2pp4/2PP4/N7/PP1qPPPP/R3KBNR w KQkq - 0 7";
-        let game_state = fen_reader::make_game_state(black_mates);
+        let game_state = fen_reader::make_game_state(black_mates).unwrap();
         b.iter(|| {
             let moves = gen_attack_vectors(&game_state, Color::White);
         })
@@ -765,7 +765,7 @@ mod bench {
         });
         let white_bishop_pinned =
             "rnbqk1nr/pppp1ppp/4p3/8/1b1P4/5N2/PPPBPPPP/RN1QKB1R b KQkq - 3 3";
-        let game_state = fen_reader::make_game_state(white_bishop_pinned);
+        let game_state = fen_reader::make_game_state(white_bishop_pinned).unwrap();
         b.iter(|| {
             for i in 0..100 {
                 black_box({
@@ -778,7 +778,7 @@ mod bench {
     #[bench]
     fn bench_gen_pseudo_legal_moves(b: &mut Bencher) {
         let black_mates = "rnb1k1nr/pp2pp1p/Q5pb/2pp4/2PP4/N7/PP1qPPPP/R3KBNR w KQkq - 0 7";
-        let game_state = fen_reader::make_game_state(black_mates);
+        let game_state = fen_reader::make_game_state(black_mates).unwrap();
         let init_state = GameState::starting_game();
         b.iter(|| {
             let moves = gen_pseudo_legal_moves(&game_state, Color::White);
@@ -799,7 +799,7 @@ mod bench {
     #[bench]
     fn bench_gen_legal_moves_mate(b: &mut Bencher) {
         let black_mates = "rnb1k1nr/pp2pp1p/Q5pb/2pp4/2PP4/N7/PP1qPPPP/R3KBNR w KQkq - 0 7";
-        let game_state = fen_reader::make_game_state(black_mates);
+        let game_state = fen_reader::make_game_state(black_mates).unwrap();
         let init_state = GameState::starting_game();
         b.iter(|| {
             let moves = gen_legal_moves(&game_state, Color::White);
@@ -817,7 +817,7 @@ mod bench {
     #[bench]
     fn bench_gen_legal_moves_open_pos(b: &mut Bencher) {
         let open_pos = "2r3k1/pp3ppp/1qrb4/3n1bB1/3QN1n1/1B3N1P/PP3PP1/2R1R1K1 b - - 2 20";
-        let game_state = fen_reader::make_game_state(open_pos);
+        let game_state = fen_reader::make_game_state(open_pos).unwrap();
         b.iter(|| {
             let moves = gen_legal_moves(&game_state, Color::White);
             let moves = gen_legal_moves(&game_state, Color::Black);
@@ -826,7 +826,7 @@ mod bench {
     #[bench]
     fn bench_gen_legal_moves_white_in_check(b: &mut Bencher) {
         let white_in_check = "2r3k1/pp3pp1/1qrb3p/3n1bB1/3QN3/1B3N1P/PP3PPn/2R1RK2 w - - 2 22";
-        let game_state = fen_reader::make_game_state(white_in_check);
+        let game_state = fen_reader::make_game_state(white_in_check).unwrap();
         b.iter(|| {
             let moves = gen_legal_moves(&game_state, Color::White);
             let moves = gen_legal_moves(&game_state, Color::Black);
@@ -836,7 +836,7 @@ mod bench {
     #[bench]
     fn bench_gen_legal_moves_original_benchmark(b: &mut Bencher) {
         let black_mates = "rnb1k1nr/pp2pp1p/Q5pb/2pp4/2PP4/N7/PP1qPPPP/R3KBNR w KQkq - 0 7";
-        let game_state = fen_reader::make_game_state(black_mates);
+        let game_state = fen_reader::make_game_state(black_mates).unwrap();
         let init_state = GameState::starting_game();
         b.iter(|| {
             let moves = gen_legal_moves(&game_state, Color::White);
@@ -862,7 +862,7 @@ mod tests {
     #[test]
     fn test_gen_pawn_promotion() {
         let fen = "rnbqkbnr/1ppppppp/8/8/2N5/2N5/PpPPPPPP/R1BQKB1R b KQkq - 1 6";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
 
         // pawn promotion with capture moves
         let m = Move::new(
@@ -880,12 +880,12 @@ mod tests {
     #[test]
     fn test_gen_legal_moves1() {
         let fen = "r3k2r/pppbbp2/2np3p/4p1p1/2BPPq1n/N1P2N2/PP1KQPPP/R1B4R w kq - 0 1";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let moves = gen_legal_moves(&game_state, Color::White);
         assert_eq!(moves.len(), 5);
         // test king running into a check
         let fen = "r3k2r/pppbbp2/2np3p/4p1p1/2BPPq1n/N1P2N2/PP2QPPP/R1B1K2R w KQkq - 0 1";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let moves = gen_legal_moves(&game_state, Color::White);
         let e1 = Coordinate::new(5, 1);
         let d2 = Coordinate::new(4, 2);
@@ -897,7 +897,7 @@ mod tests {
         // test all castling things
         // black can castle both sides
         let fen = "r3k2r/pppbbp2/2np1q1p/1N2p1p1/2BPP2n/2P2N2/PP2QPPP/R1B1K2R w KQkq - 0 1";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let moves = gen_legal_moves(&game_state, Color::Black);
         let king_side = moves.iter().find(|&m| m.is_king_side_castle());
         let queen_side = moves.iter().find(|&m| m.is_queen_side_castle());
@@ -909,7 +909,7 @@ mod tests {
         assert_eq!(king_side.is_some(), true);
         // white can not castle through check
         let fen = "4k2r/pppbbp2/2np1q1p/1N1rp1p1/B6n/2P2N2/PP2QPPP/R3K2R w KQk - 0 1";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let moves = gen_legal_moves(&game_state, Color::White);
         let queen_side = moves.iter().find(|&m| m.is_queen_side_castle());
         assert_eq!(king_side.is_some(), true);
@@ -917,7 +917,7 @@ mod tests {
 
         // castling through checks pos 2, no castling allowed either side
         let fen = "r3k2r/Nppbbp2/2n2q1B/3rp1p1/6Nn/2P4P/PP2Q1PP/R3K2R w KQkq - 0 1";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let moves = gen_legal_moves(&game_state, Color::White);
         let king_side = moves.iter().find(|&m| m.is_king_side_castle());
         let queen_side = moves.iter().find(|&m| m.is_queen_side_castle());
@@ -931,7 +931,7 @@ mod tests {
 
         // castling through checks pos 3, black no castle, white only queenside
         let fen = "r3k2r/1ppbbp2/2n1N2B/4pqp1/2r3Nn/2P4P/PP2Q1PP/R3K2R w KQkq - 0 1";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let moves = gen_legal_moves(&game_state, Color::White);
         let king_side = moves.iter().find(|&m| m.is_king_side_castle());
         let queen_side = moves.iter().find(|&m| m.is_queen_side_castle());
@@ -944,14 +944,14 @@ mod tests {
         assert_eq!(queen_side.is_some(), false);
         // same but pawn prevents castling
         let fen = "r3k2r/1ppbb3/2n1N2B/4pqp1/2r3Nn/2P4P/PPp1Q1PP/R3K2R w KQkq - 0 1";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let moves = gen_legal_moves(&game_state, Color::White);
         let king_side = moves.iter().find(|&m| m.is_king_side_castle());
         let queen_side = moves.iter().find(|&m| m.is_queen_side_castle());
         assert_eq!(king_side.is_some(), false);
         assert_eq!(queen_side.is_some(), false);
         let fen = "r3k2r/2pbb3/2n1N2B/4p1p1/2r3Nn/2P4P/PPp1Q1Pp/R3K2R w KQkq - 0 1";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let moves = gen_legal_moves(&game_state, Color::White);
         let king_side = moves.iter().find(|&m| m.is_king_side_castle());
         let queen_side = moves.iter().find(|&m| m.is_queen_side_castle());
@@ -993,7 +993,7 @@ mod tests {
     fn test_get_attack_path_from_to() {
         // black knight is pinned
         let bishop_fen = "r1bqkbnr/pppnpppp/3p4/1B6/3P4/4P3/PPP2PPP/RNBQK1NR b KQkq - 2 3";
-        let game_state = fen_reader::make_game_state(bishop_fen);
+        let game_state = fen_reader::make_game_state(bishop_fen).unwrap();
         let b5 = Coordinate::new(2, 5);
         let e8 = Coordinate::new(5, 8);
         let d8 = Coordinate::new(4, 7);
@@ -1005,7 +1005,7 @@ mod tests {
 
         // white knight is pinned
         let bishop_fen = "rnbqk1nr/pppp1ppp/8/4p3/1b1P4/8/PPPNPPPP/R1BQKBNR w KQkq - 2 3";
-        let game_state = fen_reader::make_game_state(bishop_fen);
+        let game_state = fen_reader::make_game_state(bishop_fen).unwrap();
         let b4 = Coordinate::new(2, 4);
         let e1 = Coordinate::new(5, 1);
         let d2 = Coordinate::new(4, 2);
@@ -1022,7 +1022,7 @@ mod tests {
         assert_eq!(u64::count_ones(path), 3);
         // white bishop pinned by rook
         let rook_fen = "rnbq2k1/pppp1ppp/5n2/6N1/1b2rP2/8/PPPNB1PP/R1BQK2R b KQ - 1 8";
-        let game_state = fen_reader::make_game_state(rook_fen);
+        let game_state = fen_reader::make_game_state(rook_fen).unwrap();
         let e4 = Coordinate::new(5, 4);
         let e2 = Coordinate::new(5, 2);
         let (is_empty, piece, path) = get_attack_path_from_to(&game_state, &e4, &e1);
@@ -1049,7 +1049,7 @@ mod tests {
         assert_eq!(checks.len(), 0, "black is not in check");
 
         // single check
-        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK).unwrap();
         let w_moves = gen_pseudo_legal_moves(&game_state, Color::White);
         let b_moves = gen_pseudo_legal_moves(&game_state, Color::Black);
         let checks = find_checks(&game_state, Color::Black, w_moves.iter());
@@ -1057,7 +1057,7 @@ mod tests {
         let checks = find_checks(&game_state, Color::White, b_moves.iter());
         assert_eq!(checks.len(), 0, "white is not in check");
 
-        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK).unwrap();
         let w_moves = gen_pseudo_legal_moves(&game_state, Color::White);
         let b_moves = gen_pseudo_legal_moves(&game_state, Color::Black);
         let checks = find_checks(&game_state, Color::Black, w_moves.iter());
@@ -1067,21 +1067,21 @@ mod tests {
 
         // double check
         let fen_double_check = "2R1k1nr/pp2pp1p/3nb1pb/1Qpp4/qPPP4/N7/P3PPPP/R1B1KBN1 b Qk - 2 7";
-        let game_state = fen_reader::make_game_state(fen_double_check);
+        let game_state = fen_reader::make_game_state(fen_double_check).unwrap();
         let w_moves = gen_pseudo_legal_moves(&game_state, Color::White);
         let checks = find_checks(&game_state, Color::Black, w_moves.iter());
         assert_eq!(checks.len(), 2, "black is in double check");
 
         // if triple check was legal then Nf7# is allowed (triple check loophole baby lol)
         let fen_double_check_2 = "4r1rk/b5pp/8/4N3/2n5/4K3/1Q6/8 w - - 3 8";
-        let game_state = fen_reader::make_game_state(fen_double_check_2);
+        let game_state = fen_reader::make_game_state(fen_double_check_2).unwrap();
         let b_moves = gen_pseudo_legal_moves(&game_state, Color::Black);
         let checks = find_checks(&game_state, Color::White, b_moves.iter());
         assert_eq!(checks.len(), 2, "white is in double check");
 
         // check with pinned piece
         let fen_pinned_check = "2R3nr/pp2pp1p/4b1pb/1n1p4/qp2k3/N7/P1Q1PPPP/R1BK1BN1 b - - 2 7";
-        let game_state = fen_reader::make_game_state(fen_pinned_check);
+        let game_state = fen_reader::make_game_state(fen_pinned_check).unwrap();
         let w_moves = gen_pseudo_legal_moves(&game_state, Color::White);
         let checks = find_checks(&game_state, Color::Black, w_moves.iter());
         assert_eq!(checks.len(), 1, "black is in check");
@@ -1096,7 +1096,7 @@ mod tests {
         assert_eq!(checks.len(), 0);
 
         //
-        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK).unwrap();
         let checks = generate_checks(&game_state, Color::Black);
         for m in checks.iter() {
             println!("{}", m);
@@ -1106,7 +1106,7 @@ mod tests {
         assert_eq!(checks.len(), 0);
 
         //
-        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK).unwrap();
         let checks = generate_checks(&game_state, Color::Black);
         assert_eq!(checks.len(), 0);
         let checks = generate_checks(&game_state, Color::White);
@@ -1142,7 +1142,7 @@ mod tests {
 
         // single check
         // 6 legal moves
-        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK).unwrap();
         let w_moves = gen_pseudo_legal_moves(&game_state, Color::White);
         let b_moves = gen_pseudo_legal_moves(&game_state, Color::Black);
         let checks = find_checks(&game_state, Color::Black, w_moves.iter());
@@ -1164,7 +1164,7 @@ mod tests {
 
         // single check
         // 1 legal move
-        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK).unwrap();
         let w_moves = gen_pseudo_legal_moves(&game_state, Color::White);
         let b_moves = gen_pseudo_legal_moves(&game_state, Color::Black);
         let checks = find_checks(&game_state, Color::White, b_moves.iter());
@@ -1179,7 +1179,7 @@ mod tests {
         // double check
         // no legal moves
         let fen_double_check = "2R1k1nr/pp2pp1p/3nb1pb/1Qpp4/qPPP4/N7/P3PPPP/R1B1KBN1 b Qk - 2 7";
-        let game_state = fen_reader::make_game_state(fen_double_check);
+        let game_state = fen_reader::make_game_state(fen_double_check).unwrap();
         let w_moves = gen_pseudo_legal_moves(&game_state, Color::White);
         let b_moves = gen_pseudo_legal_moves(&game_state, Color::Black);
         let checks = find_checks(&game_state, Color::Black, w_moves.iter());
@@ -1194,7 +1194,7 @@ mod tests {
         // if triple check was legal then Nf7# is allowed (triple check loophole baby lol)
         // five legal moves
         let fen_double_check_2 = "4r1rk/b5pp/8/4N3/2n5/4K3/1Q6/8 w - - 3 8";
-        let game_state = fen_reader::make_game_state(fen_double_check_2);
+        let game_state = fen_reader::make_game_state(fen_double_check_2).unwrap();
         let w_moves = gen_pseudo_legal_moves(&game_state, Color::White);
         let b_moves = gen_pseudo_legal_moves(&game_state, Color::Black);
         let checks = find_checks(&game_state, Color::White, b_moves.iter());
@@ -1209,7 +1209,7 @@ mod tests {
         // check with pinned piece
         // 3 legal moves
         let fen_pinned_check = "2R3nr/pp2pp1p/4b1pb/1n1p4/qp2k3/N7/P1Q1PPPP/R1BK1BN1 b - - 2 7";
-        let game_state = fen_reader::make_game_state(fen_pinned_check);
+        let game_state = fen_reader::make_game_state(fen_pinned_check).unwrap();
         let w_moves = gen_pseudo_legal_moves(&game_state, Color::White);
         let b_moves = gen_pseudo_legal_moves(&game_state, Color::Black);
         let checks = find_checks(&game_state, Color::Black, w_moves.iter());
@@ -1230,13 +1230,13 @@ mod tests {
         let checks = get_checks(&game_state, Color::White);
         assert!(checks.len() == 0, "black is not in check");
 
-        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::BLACK_IN_CHECK).unwrap();
         let checks = get_checks(&game_state, Color::Black);
         assert!(checks.len() > 0, "black is in check");
         let checks = get_checks(&game_state, Color::White);
         assert!(checks.len() == 0, "white is not in check");
 
-        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK);
+        let game_state = fen_reader::make_game_state(fen_reader::WHITE_IN_CHECK).unwrap();
         let checks = get_checks(&game_state, Color::Black);
         assert!(checks.len() == 0, "black is not in check");
         let checks = get_checks(&game_state, Color::White);
@@ -1248,7 +1248,7 @@ mod tests {
         let mut moves = vec![];
         let white_queen_checks = "rnb1k1nr/pp2pp1p/6pb/1Qpp4/qPPP4/N7/P3PPPP/R1B1KBNR b KQkq - 2 7";
 
-        let game_state = fen_reader::make_game_state(white_queen_checks);
+        let game_state = fen_reader::make_game_state(white_queen_checks).unwrap();
         // knight interpose
         let knight = game_state.get_piece_at(&Coordinate::new(2, 8)).unwrap();
         let knight_at = knight.at().unwrap();
@@ -1363,7 +1363,7 @@ mod tests {
     fn test_gen_attack_vectors() {
         let white_bishop_pinned =
             "rnbqk1nr/pppp1ppp/4p3/8/1b1P4/5N2/PPPBPPPP/RN1QKB1R b KQkq - 3 3";
-        let game_state = fen_reader::make_game_state(white_bishop_pinned);
+        let game_state = fen_reader::make_game_state(white_bishop_pinned).unwrap();
         let vector_moves = gen_attack_vectors(&game_state, Color::Black);
         assert_eq!(vector_moves.len(), 13);
     }
@@ -1372,7 +1372,7 @@ mod tests {
     fn test_find_attacking_pieces() {
         let white_bishop_pinned =
             "rnbqk1nr/pppp1ppp/4p3/8/1b1P4/5N2/PPPBPPPP/RN1QKB1R b KQkq - 3 3";
-        let game_state = fen_reader::make_game_state(white_bishop_pinned);
+        let game_state = fen_reader::make_game_state(white_bishop_pinned).unwrap();
         let mut king_pieces = game_state.get_pieces(Color::White, PieceType::King);
         assert!(king_pieces.get(0).is_some(), "king not found");
         let king = king_pieces.remove(0);
@@ -1398,7 +1398,7 @@ mod tests {
         // pinned by black bishop, can capture or move 1
         let white_bishop_pinned =
             "rnbqk1nr/pppp1ppp/4p3/8/1b1P4/5N2/PPPBPPPP/RN1QKB1R b KQkq - 3 3";
-        let game_state = fen_reader::make_game_state(white_bishop_pinned);
+        let game_state = fen_reader::make_game_state(white_bishop_pinned).unwrap();
         // diagonal from pinning piece to one space before the king
         // it'd be neat to make diagonal from / to function, and file from / to, and rank from / to
         let mut pins = find_pinned_pieces(&game_state, Color::White);
@@ -1426,13 +1426,13 @@ mod tests {
     fn test_get_checks() {
         let board = fen_reader::make_board(
             "rnb1kbnr/pppp1p1p/4pp2/8/8/3BP3/PPPP1PPP/RNB1K1NR b KQkq - 1 4",
-        );
+        ).unwrap();
     }
 
     #[test]
     fn test_king_escapes() {
         let white_queen_checks = "rnb1k1nr/pp2pp1p/6pb/1Qpp4/qPPP4/N7/P3PPPP/R1B1KBNR b KQkq - 2 7";
-        let game_state = fen_reader::make_game_state(white_queen_checks);
+        let game_state = fen_reader::make_game_state(white_queen_checks).unwrap();
         let king = game_state.get_king(Color::Black).unwrap();
         let king_moves = plmg::gen_king_moves(king, &game_state);
         let successful_moves: Vec<&Move> = king_moves
@@ -1442,7 +1442,7 @@ mod tests {
         assert_eq!(successful_moves.len(), 2);
 
         let fen = "rnb3nr/pp1kpp1p/6pb/1Qpp4/qPPP4/N7/P3PPPP/R1B1KBNR b KQ - 2 7";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let king = game_state.get_king(Color::Black).unwrap();
         let king_moves = plmg::gen_king_moves(king, &game_state);
         let successful_moves: Vec<&Move> = king_moves
@@ -1455,13 +1455,28 @@ mod tests {
     #[test]
     fn test_gen_legal_moves_checkmate() {
         let black_mates = "rnb1k1nr/pp2pp1p/Q5pb/2pp4/2PP4/N7/PP1qPPPP/R3KBNR w KQkq - 0 7";
-        let game_state = fen_reader::make_game_state(black_mates);
+        let game_state = fen_reader::make_game_state(black_mates).unwrap();
         let moves = gen_legal_moves(&game_state, Color::White);
         println!("{:?}", moves);
         assert_eq!(moves.len(), 0, "White has no moves");
         let white_mates = "2kQ4/pp3p2/4p1p1/7p/4P3/8/PP3PPP/3R2K1 b - - 0 21";
-        let game_state = fen_reader::make_game_state(white_mates);
+        let game_state = fen_reader::make_game_state(white_mates).unwrap();
         let moves = gen_legal_moves(&game_state, Color::Black);
         assert_eq!(moves.len(), 0, "Black has no moves");
     }
+
+    #[test]
+    fn test_interpose_along_rank() {
+        // rook checks along the first rank from the right, the queen can block on b1 or c1
+        let game_state = fen_reader::make_game_state("8/8/8/8/8/6k1/1Q6/K6r w - - 0 1").unwrap();
+        let moves = gen_legal_moves(&game_state, Color::White);
+        let blocks: Vec<&Move> = moves
+            .iter()
+            .filter(|m| m.piece == PieceType::Queen)
+            .collect();
+        assert_eq!(blocks.len(), 2, "{:?}", moves);
+        assert!(blocks.iter().any(|m| m.to == Coordinate::new(2, 1)));
+        assert!(blocks.iter().any(|m| m.to == Coordinate::new(3, 1)));
+        assert_eq!(moves.len(), 3);
+    }
 }
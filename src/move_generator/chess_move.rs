@@ -26,8 +26,8 @@ pub struct Move {
     // @todo : make this white && black castling rights removed because taking a rook removes castling rights
     castling_rights_removed: CastlingRights,
     castling_rights_removed_opponent: CastlingRights,
-    pub is_check: bool,     // @todo : set these in game when eval happens ?
-    pub is_checkmate: bool, // @todo : set these in game when eval happens ?
+    pub is_check: bool,     // set with chess_notation::set_check_flags
+    pub is_checkmate: bool, // set with chess_notation::set_check_flags
 }
 
 impl fmt::Display for Move {
@@ -80,6 +80,11 @@ impl Move {
         &self.move_type
     }
 
+    // the same move on the board, leaving out the check flags and what's kept for unmaking it
+    pub fn same_move(&self, other: &Move) -> bool {
+        self.from == other.from && self.to == other.to && self.move_type == other.move_type
+    }
+
     pub fn print_moves(moves: &Vec<Move>) {
         moves.iter().for_each(|m| {
             let str = m.to_string();
@@ -21,7 +21,7 @@ impl Direction {
         }
     }
     pub fn get_y_component(&self) -> Option<Direction> {
-        match self.x() {
+        match self.y() {
             -1 => Some(Direction::Down),
             0 => None,
             1 => Some(Direction::Up),
@@ -2104,7 +2104,7 @@ pub fn make_captures_move(
 
 pub fn test() {
     let black_in_check_fen = "6k1/ppp1p3/2qr3p/5K2/8/1B5P/PP3PP1/8 b - - 2 22";
-    let game_state = fen_reader::make_game_state(black_in_check_fen);
+    let game_state = fen_reader::make_game_state(black_in_check_fen).unwrap();
     let g8 = Coordinate::new(7, 8);
     let idx = BitBoard::coordinate_to_idx(g8);
     gen_attacks_for_square(game_state.get_board_ref(), idx, Color::White);
@@ -2128,7 +2128,7 @@ mod tests {
     #[test]
     fn test_gen_pawn_promotion() {
         let fen = "rnbqkbnr/1ppppppp/8/8/2N5/2N5/PpPPPPPP/R1BQKB1R b KQkq - 1 6";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
 
         // pawn promotion with capture moves
         let m = Move::new(
@@ -2146,7 +2146,7 @@ mod tests {
         assert_eq!(moves.len(), 12);
 
         let fen = "r1bqkbnr/1Ppppppp/2n5/8/2N5/2N5/Pp1PPPPP/R1BQKB1R w KQkq - 1 6";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
 
         // pawn promotion with capture moves, for white
         let m = Move::new(
@@ -2165,7 +2165,7 @@ mod tests {
 
         // blocked pawn, no captures 
         let fen = "r1bqkbnr/P1pppppp/2n5/8/2N5/2N5/p2PPPPP/R1BQKB1R w KQkq - 1 6";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let a2 = Coordinate::new(1, 2);
         let a7  = Coordinate::new(1, 7);
         let black_pawn = game_state.get_piece_at(&a2);
@@ -2179,7 +2179,7 @@ mod tests {
     #[test]
     fn test_gen_attacks_for_square() {
         let white_queen_checks = "rnb1k1nr/pp2pp1p/6pb/1Qpp4/qPPP4/N7/P3PPPP/R1B1KBNR b KQkq - 2 7";
-        let game_state = fen_reader::make_game_state(white_queen_checks);
+        let game_state = fen_reader::make_game_state(white_queen_checks).unwrap();
         let bit_board = game_state.get_board_ref();
         let king = game_state.get_king(Color::Black).unwrap();
         let e8 = Coordinate::new(5, 8);
@@ -2218,14 +2218,14 @@ mod tests {
     #[test]
     fn test_gen_bishop_moves() {
         let fen = "6k1/ppp1p3/2qr3p/5K2/8/1B5P/PP3PP1/8 b - - 2 22";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let b3 = Coordinate::new(2, 3);
         let white_bishop = game_state.get_piece_at(&b3).unwrap();
         let moves = gen_bishop_moves(white_bishop, &game_state);
         assert_eq!(moves.len(), 8);
 
         let fen = "1b4kb/ppp1p3/2qr3p/5K2/3Bb3/7P/PP3PP1/B5BB w - - 2 22";
-        let game_state = fen_reader::make_game_state(fen);
+        let game_state = fen_reader::make_game_state(fen).unwrap();
         let a1 = Coordinate::new(1, 1);
         let b8 = Coordinate::new(2, 8);
         let h8 = Coordinate::new(8, 8);
@@ -2244,7 +2244,7 @@ mod tests {
     fn test_gen_bishop_vector() {
         let white_bishop_pinned =
             "rnbqk1nr/pppp1ppp/4p3/8/1b1P4/5N2/PPPBPPPP/RN1QKB1R b KQkq - 3 3";
-        let game_state = fen_reader::make_game_state(white_bishop_pinned);
+        let game_state = fen_reader::make_game_state(white_bishop_pinned).unwrap();
         // white bishop on 4,2 should have 8 moves
         let d_2_bishop = game_state.get_piece_at(&Coordinate::new(4, 2));
         assert!(d_2_bishop.is_some(), "bishop should be on d2");
@@ -2281,7 +2281,7 @@ mod tests {
     fn test_get_slider_pieces_indices_attacking_idx() {
         let white_bishop_pinned =
             "rnbqk1nr/pppp1ppp/4p3/8/1b1P4/5N2/PPPBPPPP/RN1QKB1R b KQkq - 3 3";
-        let game_state = fen_reader::make_game_state(white_bishop_pinned);
+        let game_state = fen_reader::make_game_state(white_bishop_pinned).unwrap();
         let king = game_state.get_king(Color::White).unwrap();
         let king_at = king.at().unwrap();
         // how to make sure the pieces returned are unique ?
@@ -2296,8 +2296,8 @@ mod tests {
             println!("{}", idx);
         }
         assert_eq!(candidate_indices[0], 26);
-        let pos2 = "rnb1k3/pppp1ppp/4pn2/4P3/1b1Pq3/5N2/PPPB1PPP/RN1QKB1r b Qq - 3 3";
-        let game_state = fen_reader::make_game_state(pos2);
+        let pos2 = "rnb1k3/pppp1ppp/4pn2/4P3/1b1Pq3/5N2/PPPB1PPP/RN1QKB1r w Qq - 3 4";
+        let game_state = fen_reader::make_game_state(pos2).unwrap();
         let king = game_state.get_king(Color::White).unwrap();
         let king_at = king.at().unwrap();
         // how to make sure the pieces returned are unique ?
@@ -28,7 +28,7 @@ mod tests {
         // note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.
         let board = fen_reader::make_board(
             "rnb1kbnr/pppp1p1p/4pp2/8/8/3BP3/PPPP1PPP/RNB1K1NR b KQkq - 3 4",
-        );
+        ).unwrap();
         let king = board.get_pieces(Color::White, PieceType::King).remove(0);
         assert_eq!(king.piece_type, PieceType::King);
         let king_moves = gen_king_moves(&board, &king);
@@ -49,8 +49,10 @@ mod tests {
 
     #[test]
     fn test_gen_queen_moves() {
-        let board =
-            fen_reader::make_board("rnb3nr/pp1kpp1p/6pb/1Qpp4/qPPP4/N7/P3PPPP/R1B1KBNR b KQ - 2 7");
+        let board = fen_reader::make_board(
+            "rnb3nr/pp1kpp1p/6pb/1Qpp4/qPPP4/N7/P3PPPP/R1B1KBNR b KQ - 2 7",
+        )
+        .unwrap();
         let white_queen = board.get_piece_at(&Coordinate::new(2, 5)).unwrap();
         println!("{:?}", white_queen);
         let test_move = Move::new(
@@ -9,10 +9,10 @@ mod eval_tester {
 
     #[test]
     fn test_eval() {
-        let board = fen_reader::make_board(fen_reader::INITIAL_BOARD);
+        let board = fen_reader::make_board(fen_reader::INITIAL_BOARD).unwrap();
         let game_state = GameState::starting_game();
         assert_eq!(evaluator::evaluate(&game_state, None, None).score, 0.0);
-        let board = fen_reader::make_board(fen_reader::WHITE_IN_CHECK);
+        let board = fen_reader::make_board(fen_reader::WHITE_IN_CHECK).unwrap();
         println!("{:?}", evaluator::evaluate(&game_state, None, None));
     }
 }
@@ -29,7 +29,7 @@ mod test {
     #[test]
     fn test_opera_game() {
         let fail_fen = "4kb1r/p2r1ppp/4qn2/1B2p1B1/4P3/1Q6/PPP2PPP/2KR4 w k - 10 20";
-        let mut game_state = fen_reader::make_game_state(fail_fen);
+        let mut game_state = fen_reader::make_game_state(fail_fen).unwrap();
         // let moves = gen_legal_moves(&game_state, Color::White);
         let mut ai = Ai::new(Color::White);
         let chosen_move = ai.make_move(&mut game_state, None);
@@ -67,7 +67,7 @@ mod move_gen_tester {
 
     #[test]
     fn move_gen() {
-        let board = fen_reader::make_board(fen_reader::INITIAL_BOARD);
+        let board = fen_reader::make_board(fen_reader::INITIAL_BOARD).unwrap();
         let game_state = GameState::starting_game();
         let white_moves = gen_legal_moves(&game_state, Color::White);
         let black_moves = gen_legal_moves(&game_state, Color::Black);